@@ -0,0 +1,73 @@
+//! Compare clause storage in a `ClauseArena` with individually boxed `Clause`s
+//!
+//! Run with `cargo bench --bench clause_storage`.  This reports wall-clock
+//! times only; to see the effect on cache misses, run the bench binary under
+//! `perf stat -e cache-misses`.
+
+use std::time::{Duration, Instant};
+
+use satirlib::satir::arena::ClauseArena;
+use satirlib::satir::clause::{Clause, ClauseHeader, ClauseId};
+use satirlib::satir::core::{Literal, Variable};
+use satirlib::satir::dpll::{Solver, SolverConfig};
+use satirlib::satir::tagged::TaggedIndexable;
+
+const NUM_VARS : usize = 150;
+const NUM_CLAUSES : usize = 639;
+const VISITS : usize = 2_000_000;
+
+/// A xorshift generator, so that every run uses the same instances
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound : usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// A random 3-SAT instance at the satisfiability threshold
+fn random_3sat(rng : &mut Rng) -> Vec<Clause> {
+    (0..NUM_CLAUSES).map(|ix| {
+        let mut lits : Vec<Literal> = Vec::with_capacity(3);
+        while lits.len() < 3 {
+            let v = Variable::from_index(rng.below(NUM_VARS));
+            if lits.iter().all(|l| l.variable() != v) {
+                lits.push(if rng.below(2) == 0 { v.to_positive_literal() } else { v.to_negative_literal() });
+            }
+        }
+        Clause::new(ClauseHeader::new(ClauseId(ix as i64), lits.len(), 0), lits)
+    }).collect()
+}
+
+/// Visit clauses in a random order (as propagation does), summing their literals
+fn visit<F : Fn(usize) -> usize>(rng : &mut Rng, num_clauses : usize, clause_sum : F) -> (Duration, usize) {
+    let order : Vec<usize> = (0..VISITS).map(|_| rng.below(num_clauses)).collect();
+    let start = Instant::now();
+    let total = order.iter().map(|ix| clause_sum(*ix)).sum();
+    (start.elapsed(), total)
+}
+
+fn main() {
+    let mut rng = Rng(0x5eed);
+    let clauses : Vec<Clause> = (0..50).flat_map(|_| random_3sat(&mut rng)).collect();
+    let arena : ClauseArena = clauses.iter().cloned().collect();
+    let sum = |lits : &[Literal]| lits.iter().map(|l| l.as_index()).sum::<usize>();
+
+    let (boxed_time, boxed_total) = visit(&mut Rng(1), clauses.len(), |ix| sum(clauses[ix].literals()));
+    let (arena_time, arena_total) = visit(&mut Rng(1), arena.len(), |ix| sum(arena.clause(ClauseId(ix as i64)).literals()));
+    assert_eq!(boxed_total, arena_total);
+    println!("clause visits ({} clauses): boxed {:?}, arena {:?}", clauses.len(), boxed_time, arena_time);
+
+    // The solver always uses an arena, so this is a baseline for comparing
+    // against earlier revisions (which boxed each clause)
+    let start = Instant::now();
+    let num_sat = (0..20).filter(|_| {
+        let mut solver = Solver::new(random_3sat(&mut rng), Variable::from_index(NUM_VARS), SolverConfig::default());
+        solver.solve() == satirlib::satir::core::Result::Sat
+    }).count();
+    println!("solve 20 random 3-SAT instances ({} variables, {} clauses, {} sat): {:?}",
+             NUM_VARS, NUM_CLAUSES, num_sat, start.elapsed());
+}
@@ -1,5 +1,8 @@
 // Remove these later
 #![allow(dead_code)]
 #![allow(unused_variables)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 pub mod satir;
@@ -1,3 +1,4 @@
+use std::io::Write;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -5,21 +6,190 @@ use satirlib;
 
 #[derive(Debug,StructOpt)]
 #[structopt(version = "1.0", author = "Tristan Ravitch")]
+#[structopt(setting = structopt::clap::AppSettings::ArgsNegateSubcommands)]
 struct Options {
     /// Input file
     #[structopt(parse(from_os_str))]
-    input: PathBuf
+    input: Option<PathBuf>,
+    /// Report results in SAT competition format (an `s` line, `v` lines with
+    /// the model, and exit status 10 for sat or 20 for unsat)
+    #[structopt(long)]
+    competition: bool,
+    /// How to report the result: `text` (the default), or `json` for an object
+    /// like `{"sat": true, "model": {"1": true, "2": false}}` keyed by DIMACS
+    /// variable numbers (with `--competition`, the exit status is still set)
+    #[structopt(long, default_value = "text")]
+    model_format: ModelFormat,
+    /// Check every round of unit propagation against a naive scan of the
+    /// clauses (very slow; for debugging the solver)
+    #[structopt(long)]
+    verify_propagation: bool,
+    /// Print solver statistics (as `c` comment lines) to stderr after solving,
+    /// or with `--model-format json`, add them to the result as a `stats` object
+    #[structopt(long)]
+    stats: bool,
+    /// Write a DRUP proof to this file (ending with the empty clause if the
+    /// formula is unsatisfiable)
+    #[structopt(long, parse(from_os_str))]
+    proof: Option<PathBuf>,
+    /// Print a trace of the search (as `c` comment lines) to stderr after
+    /// solving
+    #[structopt(short, long)]
+    verbose: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>
 }
 
-fn main() -> anyhow::Result<()> {
-    let opts = Options::from_args();
-    let contents = std::fs::read_to_string(opts.input)?;
-    let dimacs = satirlib::satir::parse::dimacs::parse_dimacs(&contents)?;
-    let res = satirlib::satir::dpll::solve(dimacs.clauses, dimacs.next_var);
-    match res {
-        satirlib::satir::core::Result::Unsat => print!("unsat\n"),
-        satirlib::satir::core::Result::Sat => print!("sat\n")
+#[derive(Debug)]
+enum ModelFormat {
+    Text,
+    Json
+}
+
+impl std::str::FromStr for ModelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ModelFormat::Text),
+            "json" => Ok(ModelFormat::Json),
+            _ => Err(format!("unknown model format `{}` (expected `text` or `json`)", s))
+        }
+    }
+}
+
+#[derive(Debug,StructOpt)]
+enum Command {
+    /// Check that a model satisfies a formula (exiting with a nonzero status if it does not)
+    Check {
+        /// The formula (in DIMACS format)
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+        /// The proposed model (as `v` lines)
+        #[structopt(parse(from_os_str))]
+        model: PathBuf
+    }
+}
+
+fn report_stats(stats: &satirlib::satir::dpll::Stats) {
+    for (name, value) in stats.counters() {
+        eprintln!("c {}: {}", name, value);
+    }
+}
+
+/// Report the result on stdout, with the proof, statistics, and trace (each if
+/// requested) going to their own outputs so that they never mix with the result
+fn solve(input: PathBuf, competition: bool, model_format: ModelFormat, verify_propagation: bool, stats: bool,
+         proof: Option<PathBuf>, verbose: bool) -> anyhow::Result<()> {
+    let dimacs = satirlib::satir::parse::dimacs::parse_dimacs_reader(std::fs::File::open(input)?)?;
+    let dimacs_variables = dimacs.dimacs_variables();
+    let config = satirlib::satir::dpll::SolverConfig {
+        verify_propagation,
+        trace: verbose,
+        ..satirlib::satir::dpll::SolverConfig::default()
     };
+    let mut solver = satirlib::satir::dpll::Solver::new(dimacs.clauses, dimacs.next_var, config);
+    solver.set_dimacs_variables(dimacs_variables);
+    for (var, polarity) in dimacs.phases {
+        solver.set_phase(var, polarity);
+    }
+    if let Some(path) = proof {
+        solver.set_proof_writer(Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)));
+    }
+    let res = solver.solve();
+    solver.finish_proof()?;
+    if verbose {
+        for line in solver.trace().lines() {
+            eprintln!("c {}", line);
+        }
+    }
+    if let ModelFormat::Json = model_format {
+        // The statistics go in the result object rather than on stderr
+        let stdout = std::io::stdout();
+        let mut out = std::io::BufWriter::new(stdout.lock());
+        match res {
+            satirlib::satir::core::Result::Sat => {
+                write!(out, "{{\"sat\": true, \"model\": ")?;
+                solver.write_model_json(&mut out)?;
+            },
+            satirlib::satir::core::Result::Unsat => write!(out, "{{\"sat\": false")?,
+            satirlib::satir::core::Result::Unknown => write!(out, "{{\"sat\": null")?
+        }
+        if stats {
+            write!(out, ", \"stats\": {}", solver.stats().to_json())?;
+        }
+        writeln!(out, "}}")?;
+        out.flush()?;
+        drop(out);
+        if competition {
+            match res {
+                satirlib::satir::core::Result::Sat => std::process::exit(10),
+                satirlib::satir::core::Result::Unsat => std::process::exit(20),
+                satirlib::satir::core::Result::Unknown => {}
+            }
+        }
+        return Ok(());
+    }
+
+    if stats {
+        report_stats(&solver.stats());
+    }
+    if !competition {
+        match res {
+            satirlib::satir::core::Result::Unsat => println!("unsat"),
+            satirlib::satir::core::Result::Sat => println!("sat"),
+            satirlib::satir::core::Result::Unknown => println!("unknown")
+        };
+
+        return Ok(());
+    }
+
+    match res {
+        satirlib::satir::core::Result::Unsat => {
+            println!("s UNSATISFIABLE");
+            std::process::exit(20);
+        },
+        satirlib::satir::core::Result::Sat => {
+            println!("s SATISFIABLE");
+            let stdout = std::io::stdout();
+            let mut out = std::io::BufWriter::new(stdout.lock());
+            solver.write_model(&mut out)?;
+            out.flush()?;
+            drop(out);
+            std::process::exit(10);
+        },
+        satirlib::satir::core::Result::Unknown => {
+            println!("s UNKNOWN");
+            Ok(())
+        }
+    }
+}
+
+fn check(input: PathBuf, model: PathBuf) -> anyhow::Result<()> {
+    let dimacs = satirlib::satir::parse::dimacs::parse_dimacs_reader(std::fs::File::open(input)?)?;
+    let model_contents = std::fs::read_to_string(model)?;
+    let model = satirlib::satir::parse::model::parse_model(&model_contents)?;
+    let assignment = dimacs.assignment_from_model(&model);
+    if satirlib::satir::clause::evaluate(&dimacs.clauses, &assignment) {
+        println!("valid");
+    } else {
+        println!("invalid");
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+fn main() -> anyhow::Result<()> {
+    let opts = Options::from_args();
+    match (opts.command, opts.input) {
+        (Some(Command::Check { input, model }), _) => check(input, model),
+        (None, Some(input)) => solve(input, opts.competition, opts.model_format, opts.verify_propagation, opts.stats,
+                                     opts.proof, opts.verbose),
+        (None, None) => {
+            Options::clap().print_help()?;
+            println!();
+            std::process::exit(2);
+        }
+    }
+}
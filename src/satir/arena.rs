@@ -0,0 +1,335 @@
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+use crate::satir::clause::{Clause, ClauseHeader, ClauseId, KEEP_MASK, LEARNED_MASK};
+use crate::satir::core::Literal;
+use crate::satir::tagged::TaggedIndexable;
+
+/// The clause database of the solver
+///
+/// Unlike a `Clause`, which is a separate allocation, clauses in the arena
+/// store their literals in a single contiguous buffer (in the order the
+/// clauses were added), with the headers in a parallel vector.  This keeps the
+/// database compact and the clauses visited by propagation close together in
+/// memory.
+///
+/// Clauses are referred to by `ClauseId`, which is the index of the clause in
+/// the arena; they are accessed through `ClauseRef` and `ClauseMut` views.
+/// Literals removed from a clause (e.g., by `ClauseMut::retain_literals`) leave
+/// a gap in the buffer until the arena is compacted.
+#[derive(Default)]
+pub struct ClauseArena {
+    headers : Vec<ClauseHeader>,
+    /// The index into `literals` of the first literal of each clause; the
+    /// literals of a clause extend to the start of the next
+    offsets : Vec<usize>,
+    literals : Vec<Literal>
+}
+
+/// A shared view of a clause in a `ClauseArena`
+///
+/// Like `Clause`, indexing covers every literal of the clause (including
+/// inactive ones), while `literals` only covers the active literals.
+#[derive(Clone, Copy)]
+pub struct ClauseRef<'a> {
+    header : &'a ClauseHeader,
+    lits : &'a [Literal]
+}
+
+/// A mutable view of a clause in a `ClauseArena`
+pub struct ClauseMut<'a> {
+    header : &'a mut ClauseHeader,
+    lits : &'a mut [Literal]
+}
+
+impl ClauseArena {
+    pub fn new() -> Self {
+        ClauseArena::default()
+    }
+
+    /// An empty arena with room for the given number of clauses and literals
+    pub fn with_capacity(clauses : usize, literals : usize) -> Self {
+        ClauseArena {
+            headers : Vec::with_capacity(clauses),
+            offsets : Vec::with_capacity(clauses),
+            literals : Vec::with_capacity(literals)
+        }
+    }
+
+    /// The number of clauses in the arena
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// The number of clauses the arena can hold without reallocating its
+    /// headers
+    pub fn capacity(&self) -> usize {
+        self.headers.capacity()
+    }
+
+    /// The number of literal slots allocated to clauses (including those of
+    /// removed literals)
+    pub fn literal_slots(&self) -> usize {
+        self.literals.len()
+    }
+
+    /// Add a clause with the given header and (active) literals
+    ///
+    /// The identifier and literal count of the header are replaced to match
+    /// the position of the clause in the arena and the literals.
+    pub fn push(&mut self, mut header : ClauseHeader, lits : &[Literal]) -> ClauseId {
+        let cid = ClauseId(self.headers.len() as i64);
+        header.id = cid;
+        header.lit_count = lits.len();
+        self.headers.push(header);
+        self.offsets.push(self.literals.len());
+        self.literals.extend_from_slice(lits);
+        cid
+    }
+
+    /// Add a copy of the active literals (and the header) of a clause
+    pub fn push_clause(&mut self, cl : &Clause) -> ClauseId {
+        self.push(cl.header().clone(), cl.literals())
+    }
+
+    /// The range of `literals` holding the given clause
+    fn span(&self, ix : usize) -> core::ops::Range<usize> {
+        let end = self.offsets.get(ix + 1).copied().unwrap_or(self.literals.len());
+        self.offsets[ix]..end
+    }
+
+    pub fn clause(&self, cid : ClauseId) -> ClauseRef<'_> {
+        let ix = cid.as_index();
+        ClauseRef { header : &self.headers[ix], lits : &self.literals[self.span(ix)] }
+    }
+
+    pub fn clause_mut(&mut self, cid : ClauseId) -> ClauseMut<'_> {
+        let ix = cid.as_index();
+        let span = self.span(ix);
+        ClauseMut { header : &mut self.headers[ix], lits : &mut self.literals[span] }
+    }
+
+    /// The clauses in order of their identifiers
+    pub fn iter(&self) -> impl Iterator<Item = ClauseRef<'_>> {
+        (0..self.len()).map(move |ix| ClauseRef { header : &self.headers[ix], lits : &self.literals[self.span(ix)] })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = ClauseMut<'_>> {
+        let mut rest = &mut self.literals[..];
+        let mut start = 0;
+        let ends : Vec<usize> = self.offsets.iter().skip(1).copied().chain(core::iter::once(rest.len())).collect();
+        self.headers.iter_mut().zip(ends).map(move |(header, end)| {
+            let (lits, tail) = core::mem::take(&mut rest).split_at_mut(end - start);
+            rest = tail;
+            start = end;
+            ClauseMut { header, lits }
+        })
+    }
+
+    /// Remove the clauses marked in `deleted` (indexed by `ClauseId`), along
+    /// with the gaps left by removed literals
+    ///
+    /// The surviving clauses keep their relative order, but are renumbered
+    /// densely; the result maps each old `ClauseId` to the new one (or `None`
+    /// if the clause was deleted).
+    pub fn compact(&mut self, deleted : &[bool]) -> Vec<Option<ClauseId>> {
+        let old = core::mem::replace(self, ClauseArena::with_capacity(self.len(), self.literals.len()));
+        old.headers.iter().enumerate().map(|(ix, header)| {
+            if deleted[ix] {
+                None
+            } else {
+                let lits = &old.literals[old.offsets[ix]..old.offsets[ix] + header.lit_count];
+                Some(self.push(header.clone(), lits))
+            }
+        }).collect()
+    }
+}
+
+impl<'a> ClauseRef<'a> {
+    /// The number of active literals
+    pub fn lit_count(&self) -> usize {
+        self.header.lit_count
+    }
+
+    pub fn identifier(&self) -> ClauseId {
+        self.header.id
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.header.flags & KEEP_MASK != 0
+    }
+
+    pub fn is_learned(&self) -> bool {
+        self.header.flags & LEARNED_MASK != 0
+    }
+
+    pub fn activity(&self) -> f64 {
+        self.header.activity()
+    }
+
+    #[cfg(feature = "clause-heat")]
+    pub fn heat(&self) -> u64 {
+        self.header.heat
+    }
+
+    /// The active literals of the clause
+    pub fn literals(&self) -> &'a [Literal] {
+        &self.lits[0..self.header.lit_count]
+    }
+
+    /// Copy the clause out of the arena
+    pub fn to_clause(&self) -> Clause {
+        Clause::new(self.header.clone(), self.lits.iter().copied())
+    }
+}
+
+impl<'a> ClauseMut<'a> {
+    /// A shared view of the same clause
+    pub fn as_shared(&self) -> ClauseRef<'_> {
+        ClauseRef { header : self.header, lits : self.lits }
+    }
+
+    pub fn lit_count(&self) -> usize {
+        self.header.lit_count
+    }
+
+    pub fn identifier(&self) -> ClauseId {
+        self.header.id
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.as_shared().is_pinned()
+    }
+
+    pub fn is_learned(&self) -> bool {
+        self.as_shared().is_learned()
+    }
+
+    /// See `Clause::pin`
+    pub fn pin(&mut self) {
+        self.header.flags |= KEEP_MASK;
+    }
+
+    /// See `ClauseHeader::bump_activity`
+    pub fn bump_activity(&mut self, inc : f64) {
+        self.header.bump_activity(inc);
+    }
+
+    /// See `ClauseHeader::scale_activity`
+    pub fn scale_activity(&mut self, factor : f64) {
+        self.header.scale_activity(factor);
+    }
+
+    #[cfg(feature = "clause-heat")]
+    pub fn heat_mut(&mut self) -> &mut u64 {
+        &mut self.header.heat
+    }
+
+    pub fn literals(&self) -> &[Literal] {
+        &self.lits[0..self.header.lit_count]
+    }
+
+    pub fn literals_mut(&mut self) -> &mut [Literal] {
+        &mut self.lits[0..self.header.lit_count]
+    }
+
+    /// Keep only the active literals that satisfy the predicate (in their
+    /// current order); the others become inactive (see
+    /// `Clause::retain_literals`)
+    pub fn retain_literals<F : FnMut(Literal) -> bool>(&mut self, mut keep : F) {
+        let mut kept = 0;
+        for ix in 0..self.header.lit_count {
+            let lit = self.lits[ix];
+            if keep(lit) {
+                self.lits[kept] = lit;
+                kept += 1;
+            }
+        }
+        self.header.lit_count = kept;
+    }
+}
+
+impl Index<usize> for ClauseRef<'_> {
+    type Output = Literal;
+
+    fn index(&self, num : usize) -> &Literal {
+        &self.lits[num]
+    }
+}
+
+impl Index<usize> for ClauseMut<'_> {
+    type Output = Literal;
+
+    fn index(&self, num : usize) -> &Literal {
+        &self.lits[num]
+    }
+}
+
+impl IndexMut<usize> for ClauseMut<'_> {
+    fn index_mut(&mut self, num : usize) -> &mut Literal {
+        &mut self.lits[num]
+    }
+}
+
+impl core::iter::FromIterator<Clause> for ClauseArena {
+    /// Intern clauses in order, so that each clause's `ClauseId` is its
+    /// position in the iterator
+    fn from_iter<I : IntoIterator<Item = Clause>>(clauses : I) -> Self {
+        let mut arena = ClauseArena::new();
+        for cl in clauses {
+            arena.push_clause(&cl);
+        }
+        arena
+    }
+}
+
+#[cfg(test)]
+fn lits(dimacs : &[i32]) -> Vec<Literal> {
+    dimacs.iter().map(|l| Literal::from_dimacs(*l).unwrap()).collect()
+}
+
+#[test]
+fn test_arena_layout() {
+    let mut arena = ClauseArena::new();
+    let c0 = arena.push(ClauseHeader::new(ClauseId(7), 0, 0), &lits(&[1, -2]));
+    let c1 = arena.push(ClauseHeader::new(ClauseId(7), 0, LEARNED_MASK), &lits(&[2, 3, -4]));
+    assert_eq!((c0, c1), (ClauseId(0), ClauseId(1)));
+    assert_eq!(arena.literal_slots(), 5);
+
+    let cl = arena.clause(c1);
+    assert_eq!(cl.identifier(), c1);
+    assert!(cl.is_learned());
+    assert_eq!(cl.literals(), &lits(&[2, 3, -4])[..]);
+    assert_eq!(cl[2], Literal::from_dimacs(-4).unwrap());
+
+    arena.clause_mut(c0).literals_mut().swap(0, 1);
+    let all : Vec<Vec<Literal>> = arena.iter().map(|cl| cl.literals().to_vec()).collect();
+    assert_eq!(all, vec![lits(&[-2, 1]), lits(&[2, 3, -4])]);
+}
+
+#[test]
+fn test_arena_compact() {
+    let mut arena : ClauseArena = [&[1, 2, 3][..], &[-1, 2], &[3, -4, 5]].iter().map(|l| {
+        let l = lits(l);
+        Clause::new(ClauseHeader::new(ClauseId(0), l.len(), 0), l)
+    }).collect();
+    for mut cl in arena.iter_mut() {
+        cl.retain_literals(|l| l != Literal::from_dimacs(3).unwrap());
+    }
+    arena.clause_mut(ClauseId(2)).pin();
+    assert_eq!(arena.literal_slots(), 8);
+
+    let renumbered = arena.compact(&[false, true, false]);
+    assert_eq!(renumbered, vec![Some(ClauseId(0)), None, Some(ClauseId(1))]);
+    assert_eq!(arena.len(), 2);
+    // The gaps left by the removed literals are gone
+    assert_eq!(arena.literal_slots(), 4);
+    assert_eq!(arena.clause(ClauseId(0)).literals(), &lits(&[1, 2])[..]);
+    let cl = arena.clause(ClauseId(1));
+    assert_eq!((cl.identifier(), cl.is_pinned()), (ClauseId(1), true));
+    assert_eq!(cl.literals(), &lits(&[-4, 5])[..]);
+}
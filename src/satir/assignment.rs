@@ -0,0 +1,193 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::satir::core::{Value, Variable};
+use crate::satir::tagged::{TaggedIndexable, TaggedVec};
+
+/// Storage for the value of each variable
+///
+/// The solver reads and writes the current assignment through this interface,
+/// so that the representation can be chosen per instance (see
+/// `SolverConfig::packed_assignment`).
+pub trait Assignment {
+    fn value_of(&self, var : Variable) -> Value;
+
+    fn set(&mut self, var : Variable, value : Value);
+
+    /// The number of variables with a slot in the assignment
+    fn num_variables(&self) -> usize;
+
+    /// The number of bytes allocated on the heap to store the values
+    fn heap_bytes(&self) -> usize;
+}
+
+/// One byte per variable, which is the fastest to access
+impl Assignment for TaggedVec<Variable, Value> {
+    fn value_of(&self, var : Variable) -> Value {
+        self[var]
+    }
+
+    fn set(&mut self, var : Variable, value : Value) {
+        self[var] = value;
+    }
+
+    fn num_variables(&self) -> usize {
+        self.len()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * core::mem::size_of::<Value>()
+    }
+}
+
+/// The number of bits used to store each value in a `PackedAssignment`
+const BITS_PER_VALUE : usize = 2;
+
+const VALUES_PER_WORD : usize = 64 / BITS_PER_VALUE;
+
+/// The values, indexed by their packed encoding
+const DECODE : [Value; 4] = [Value::LIFTED_TRUE, Value::LIFTED_FALSE, Value::UNASSIGNED, Value::UNASSIGNED];
+
+/// An assignment that packs the value of each variable into two bits
+///
+/// This takes a quarter of the memory of the unpacked representation (at the
+/// cost of some shifting and masking on every access), which matters for
+/// instances with many variables that are limited by memory bandwidth.
+pub struct PackedAssignment {
+    words : Vec<u64>,
+    num_variables : usize
+}
+
+impl PackedAssignment {
+    /// An assignment with every variable up to (and including) `last` unassigned
+    pub fn new(last : Variable) -> Self {
+        let num_variables = last.as_index() + 1;
+        let unassigned = encode(Value::UNASSIGNED);
+        let word = (0..VALUES_PER_WORD).fold(0, |w, ix| w | (unassigned << (ix * BITS_PER_VALUE)));
+        PackedAssignment {
+            words : vec![word; num_variables.div_ceil(VALUES_PER_WORD)],
+            num_variables
+        }
+    }
+}
+
+fn encode(value : Value) -> u64 {
+    if value == Value::LIFTED_TRUE {
+        0
+    } else if value == Value::LIFTED_FALSE {
+        1
+    } else {
+        2
+    }
+}
+
+impl Assignment for PackedAssignment {
+    fn value_of(&self, var : Variable) -> Value {
+        let ix = var.as_index();
+        let shift = (ix % VALUES_PER_WORD) * BITS_PER_VALUE;
+        DECODE[((self.words[ix / VALUES_PER_WORD] >> shift) & 0b11) as usize]
+    }
+
+    fn set(&mut self, var : Variable, value : Value) {
+        let ix = var.as_index();
+        assert!(ix < self.num_variables, "Variable {:?} is out of range", var);
+        let shift = (ix % VALUES_PER_WORD) * BITS_PER_VALUE;
+        let word = &mut self.words[ix / VALUES_PER_WORD];
+        *word = (*word & !(0b11 << shift)) | (encode(value) << shift);
+    }
+
+    fn num_variables(&self) -> usize {
+        self.num_variables
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.words.capacity() * core::mem::size_of::<u64>()
+    }
+}
+
+/// The assignment representation selected when the solver is constructed
+pub enum AssignmentStorage {
+    Unpacked(TaggedVec<Variable, Value>),
+    Packed(PackedAssignment)
+}
+
+impl AssignmentStorage {
+    /// Store the given assignment, packing it if requested
+    pub fn new(assignment : TaggedVec<Variable, Value>, packed : bool) -> Self {
+        if !packed {
+            return AssignmentStorage::Unpacked(assignment);
+        }
+
+        let mut storage = PackedAssignment::new(Variable::from_index(assignment.len().saturating_sub(1)));
+        for (ix, value) in assignment.iter().enumerate() {
+            storage.set(Variable::from_index(ix), *value);
+        }
+        AssignmentStorage::Packed(storage)
+    }
+}
+
+impl Assignment for AssignmentStorage {
+    #[inline]
+    fn value_of(&self, var : Variable) -> Value {
+        match self {
+            AssignmentStorage::Unpacked(a) => a.value_of(var),
+            AssignmentStorage::Packed(a) => a.value_of(var)
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, var : Variable, value : Value) {
+        match self {
+            AssignmentStorage::Unpacked(a) => a.set(var, value),
+            AssignmentStorage::Packed(a) => a.set(var, value)
+        }
+    }
+
+    fn num_variables(&self) -> usize {
+        match self {
+            AssignmentStorage::Unpacked(a) => a.num_variables(),
+            AssignmentStorage::Packed(a) => a.num_variables()
+        }
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            AssignmentStorage::Unpacked(a) => a.heap_bytes(),
+            AssignmentStorage::Packed(a) => a.heap_bytes()
+        }
+    }
+}
+
+#[test]
+fn test_packed_assignment() {
+    let last = Variable::from_index(100);
+    let mut packed = PackedAssignment::new(last);
+    let mut unpacked = TaggedVec::new();
+    unpacked.ensure_index(&last, Value::UNASSIGNED);
+    assert_eq!(packed.num_variables(), unpacked.num_variables());
+
+    let values = [Value::LIFTED_TRUE, Value::LIFTED_FALSE, Value::UNASSIGNED];
+    for round in 0..3 {
+        for ix in 0..=100 {
+            let v = Variable::from_index(ix);
+            let value = values[(ix * 7 + round) % 3];
+            packed.set(v, value);
+            unpacked.set(v, value);
+        }
+        for ix in 0..=100 {
+            let v = Variable::from_index(ix);
+            assert_eq!(packed.value_of(v), unpacked.value_of(v), "variable {} in round {}", ix, round);
+        }
+    }
+}
+
+#[test]
+fn test_packed_assignment_memory() {
+    let last = Variable::from_index(1_000_000 - 1);
+    let packed = PackedAssignment::new(last);
+    let mut unpacked = TaggedVec::new();
+    unpacked.ensure_index(&last, Value::UNASSIGNED);
+    assert_eq!(unpacked.heap_bytes(), 1_000_000);
+    assert_eq!(packed.heap_bytes(), 250_000);
+    assert!(packed.value_of(last).is_unassigned());
+}
@@ -1,3 +1,5 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 use slice_dst;
 
 use crate::satir::core::{Variable, Value, Literal};
@@ -8,7 +10,7 @@ use crate::satir::tagged::{TaggedIndexable, TaggedVec};
 /// This is intended to be the index into the clause array that holds the
 /// `Clause`.  We need these indirect references because we can't have
 /// references to clauses (since we need to borrow them mutably in many places).
-#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct ClauseId(pub i64);
 
 impl TaggedIndexable for ClauseId {
@@ -18,11 +20,59 @@ impl TaggedIndexable for ClauseId {
     }
 }
 
+/// Set in `ClauseHeader::flags` for clauses that must never be deleted or
+/// simplified away (see `Clause::pin`)
+pub const KEEP_MASK : u16 = 0x1;
+
+/// Set in `ClauseHeader::flags` for clauses learned during search (as opposed
+/// to clauses of the original problem)
+pub const LEARNED_MASK : u16 = 0x2;
+
 /// Fixed-length clause metadata
+#[derive(Clone)]
 pub struct ClauseHeader {
     pub id : ClauseId,
     pub lit_count : usize,
-    pub activity : f64
+    /// How often the clause has been involved in recent conflicts (see
+    /// `ClauseHeader::bump_activity`)
+    activity : f64,
+    /// A bit set of properties of the clause (e.g., `KEEP_MASK` and
+    /// `LEARNED_MASK`)
+    pub flags : u16,
+    /// The number of times the clause has propagated a literal (or caused a
+    /// conflict doing so), for finding the hot spots of an encoding
+    #[cfg(feature = "clause-heat")]
+    pub heat : u64
+}
+
+impl ClauseHeader {
+    /// The header of a clause with no activity (or heat)
+    pub fn new(id : ClauseId, lit_count : usize, flags : u16) -> Self {
+        ClauseHeader {
+            id,
+            lit_count,
+            activity : 0.0,
+            flags,
+            #[cfg(feature = "clause-heat")]
+            heat : 0
+        }
+    }
+
+    pub fn activity(&self) -> f64 {
+        self.activity
+    }
+
+    /// Add to the activity of the clause
+    pub fn bump_activity(&mut self, inc : f64) {
+        self.activity += inc;
+        debug_assert!(self.activity.is_finite());
+    }
+
+    /// Multiply the activity of the clause by a factor (e.g., to rescale all
+    /// activities while preserving their order)
+    pub fn scale_activity(&mut self, factor : f64) {
+        self.activity *= factor;
+    }
 }
 
 /// A SAT clause
@@ -43,10 +93,37 @@ pub enum PropagateResult {
     NoConflict
 }
 
+/// The reasons `Clause::new_checked` can reject a clause
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(thiserror::Error))]
+pub enum ClauseError {
+    /// The clause has no literals (and so can never be satisfied)
+    #[cfg_attr(feature = "std", error("the clause is empty"))]
+    Empty,
+    /// The clause contains a literal and its negation (and so is always
+    /// satisfied)
+    #[cfg_attr(feature = "std", error("the clause contains both {0} and its negation"))]
+    Tautology(Literal),
+    /// Every literal of the clause is false under the given assignment, so
+    /// there is nothing to watch
+    #[cfg_attr(feature = "std", error("every literal of the clause is false"))]
+    Falsified
+}
+
 fn lit_value(assignment : &TaggedVec<Variable, Value>, lit : &Literal) -> Value {
     assignment[lit.variable()]
 }
 
+/// Check whether an assignment satisfies every clause
+///
+/// A clause is satisfied if at least one of its active literals is true;
+/// unassigned literals do not count.
+pub fn evaluate(clauses : &[Clause], assignment : &TaggedVec<Variable, Value>) -> bool {
+    clauses.iter().all(|cl| {
+        cl.literals().iter().any(|l| l.under_value(lit_value(assignment, l)) == Value::LIFTED_TRUE)
+    })
+}
+
 impl Clause {
     pub fn new<I>(head : ClauseHeader, lits : I) -> Self
     where
@@ -56,6 +133,46 @@ impl Clause {
         Clause(slice_dst::SliceWithHeader::new::<Box<_>, I>(head, lits).into())
     }
 
+    /// Make a clause, rejecting empty clauses and tautologies
+    ///
+    /// Duplicate literals are removed (keeping the first occurrence), so the
+    /// first two literals are distinct, and the literal count of the header is
+    /// set to match.
+    pub fn new_checked<I>(head : ClauseHeader, lits : I) -> Result<Self, ClauseError>
+    where
+        I : IntoIterator<Item = Literal>
+    {
+        let lits : Vec<Literal> = lits.into_iter().collect();
+        let mut cl = Clause::new(ClauseHeader { lit_count : lits.len(), ..head }, lits);
+        cl.remove_duplicate_literals();
+        if cl.lit_count() == 0 {
+            return Err(ClauseError::Empty);
+        }
+        match cl.literals().iter().find(|l| cl.contains_literal(l.negate())) {
+            Some(lit) => Err(ClauseError::Tautology(*lit)),
+            None => Ok(cl)
+        }
+    }
+
+    /// Like `new_checked`, but also choose the initial watches under an
+    /// assignment
+    ///
+    /// The literals that are not false are moved to the front (keeping their
+    /// order), so the clause watches two of them if it has two.  A clause with
+    /// every literal false is rejected.
+    pub fn new_checked_under<I>(head : ClauseHeader, lits : I, assignment : &TaggedVec<Variable, Value>) -> Result<Self, ClauseError>
+    where
+        I : IntoIterator<Item = Literal>
+    {
+        let mut cl = Clause::new_checked(head, lits)?;
+        let is_false = |l : &Literal| l.under_value(lit_value(assignment, l)) == Value::LIFTED_FALSE;
+        if cl.literals().iter().all(is_false) {
+            return Err(ClauseError::Falsified);
+        }
+        cl.literals_mut().sort_by_key(is_false);
+        Ok(cl)
+    }
+
     /// The number of active literals (i.e., non-deleted literals)
     pub fn lit_count(&self) -> usize {
         self.0.header.lit_count
@@ -68,6 +185,141 @@ impl Clause {
     pub fn identifier_mut(&mut self) -> &mut ClauseId {
         &mut self.0.header.id
     }
+
+    pub fn header(&self) -> &ClauseHeader {
+        &self.0.header
+    }
+
+    /// Mark the clause as one that must never be removed, e.g., because it is
+    /// definitional
+    ///
+    /// Clause deletion and simplification passes skip pinned clauses.
+    pub fn pin(&mut self) {
+        self.0.header.flags |= KEEP_MASK;
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.0.header.flags & KEEP_MASK != 0
+    }
+
+    pub fn is_learned(&self) -> bool {
+        self.0.header.flags & LEARNED_MASK != 0
+    }
+
+    pub fn set_learned(&mut self, learned : bool) {
+        if learned {
+            self.0.header.flags |= LEARNED_MASK;
+        } else {
+            self.0.header.flags &= !LEARNED_MASK;
+        }
+    }
+
+    /// How often the clause has been involved in recent conflicts
+    pub fn activity(&self) -> f64 {
+        self.0.header.activity()
+    }
+
+    /// See `ClauseHeader::bump_activity`
+    pub fn bump_activity(&mut self, inc : f64) {
+        self.0.header.bump_activity(inc);
+    }
+
+    /// See `ClauseHeader::scale_activity`
+    pub fn scale_activity(&mut self, factor : f64) {
+        self.0.header.scale_activity(factor);
+    }
+
+    /// The number of times the clause has propagated (see `ClauseHeader::heat`)
+    #[cfg(feature = "clause-heat")]
+    pub fn heat(&self) -> u64 {
+        self.0.header.heat
+    }
+
+    #[cfg(feature = "clause-heat")]
+    pub fn heat_mut(&mut self) -> &mut u64 {
+        &mut self.0.header.heat
+    }
+
+    /// The active literals of the clause
+    pub fn literals(&self) -> &[Literal] {
+        &self.0.slice[0..self.lit_count()]
+    }
+
+    pub fn literals_mut(&mut self) -> &mut [Literal] {
+        let lit_count = self.lit_count();
+        &mut self.0.slice[0..lit_count]
+    }
+
+    /// True if the literal is one of the active literals in the clause
+    pub fn contains_literal(&self, l : Literal) -> bool {
+        self.literals().contains(&l)
+    }
+
+    /// True if any active literal in the clause (of either polarity) refers to
+    /// the given variable
+    pub fn contains_variable(&self, v : Variable) -> bool {
+        self.literals().iter().any(|l| l.variable() == v)
+    }
+
+    /// A Bloom-style summary of the variables in the clause, with bit
+    /// `v % 64` set for each variable `v`
+    ///
+    /// If this clause subsumes another, every bit of its signature is also set
+    /// in the signature of the other clause, so a missing bit rules out
+    /// subsumption without looking at the literals.
+    pub fn signature(&self) -> u64 {
+        self.literals().iter().fold(0, |sig, l| sig | (1 << (l.variable().as_index() % 64)))
+    }
+
+    /// True if every active literal of this clause is an active literal of the
+    /// other clause
+    pub fn subsumes(&self, other : &Clause) -> bool {
+        if self.lit_count() > other.lit_count() || self.signature() & !other.signature() != 0 {
+            return false;
+        }
+
+        self.literals().iter().all(|l| other.contains_literal(*l))
+    }
+
+    /// Remove repeated occurrences of active literals, keeping the first
+    /// occurrence of each (so the order of the remaining literals is
+    /// unchanged)
+    ///
+    /// The removed occurrences become inactive.  Returns true if any literal
+    /// was removed.
+    pub fn remove_duplicate_literals(&mut self) -> bool {
+        let lit_count = self.lit_count();
+        let mut kept = 0;
+        for ix in 0..lit_count {
+            let lit = self.0.slice[ix];
+            if !self.0.slice[0..kept].contains(&lit) {
+                self.0.slice[kept] = lit;
+                kept += 1;
+            }
+        }
+        self.0.header.lit_count = kept;
+        kept != lit_count
+    }
+
+    /// Keep only the active literals that satisfy the predicate (in their
+    /// current order); the others become inactive
+    pub fn retain_literals<F : FnMut(Literal) -> bool>(&mut self, mut keep : F) {
+        let mut kept = 0;
+        for ix in 0..self.lit_count() {
+            let lit = self.0.slice[ix];
+            if keep(lit) {
+                self.0.slice[kept] = lit;
+                kept += 1;
+            }
+        }
+        self.0.header.lit_count = kept;
+    }
+
+    /// True if the clause contains both polarities of some variable (and is
+    /// thus always satisfied)
+    pub fn is_tautology(&self) -> bool {
+        self.literals().iter().any(|l| self.contains_literal(l.negate()))
+    }
 }
 
 // Note: Morally, `Clause` is this type:
@@ -83,7 +335,7 @@ impl Clause {
 // difficult, so we use slice_dst to do the heavy lifting for us. It ensures
 // that the header and slice are inlined in each object
 
-impl std::ops::Index<usize> for Clause {
+impl core::ops::Index<usize> for Clause {
     type Output = Literal;
 
     fn index(&self, num : usize) -> &Self::Output {
@@ -91,12 +343,18 @@ impl std::ops::Index<usize> for Clause {
     }
 }
 
-impl std::ops::IndexMut<usize> for Clause {
+impl core::ops::IndexMut<usize> for Clause {
     fn index_mut<'a>(&'a mut self, i : usize) -> &'a mut Literal {
         &mut self.0.slice[i]
     }
 }
 
+impl Clone for Clause {
+    fn clone(&self) -> Self {
+        Clause::new(self.0.header.clone(), self.0.slice.iter().copied())
+    }
+}
+
 // impl constraint::Constraint for Clause {
 //     fn unique_id(&self) -> ClauseId {
 //         self.0.header.id
@@ -112,3 +370,231 @@ impl std::ops::IndexMut<usize> for Clause {
 //         }
 //     }
 // }
+
+
+#[cfg(test)]
+fn test_clause(lits : Vec<Literal>) -> Clause {
+    let hdr = ClauseHeader::new(ClauseId(0), lits.len(), 0);
+    Clause::new(hdr, lits)
+}
+
+#[test]
+fn test_contains_literal() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let v2 = v1.next_variable();
+    let cl = test_clause(vec![v0.to_positive_literal(), v1.to_negative_literal()]);
+
+    assert!(cl.contains_literal(v0.to_positive_literal()));
+    assert!(cl.contains_literal(v1.to_negative_literal()));
+    // Opposite polarity
+    assert!(!cl.contains_literal(v0.to_negative_literal()));
+    assert!(!cl.contains_literal(v1.to_positive_literal()));
+    // Absent
+    assert!(!cl.contains_literal(v2.to_positive_literal()));
+}
+
+#[test]
+fn test_contains_variable() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let v2 = v1.next_variable();
+    let cl = test_clause(vec![v0.to_positive_literal(), v1.to_negative_literal()]);
+
+    assert!(cl.contains_variable(v0));
+    assert!(cl.contains_variable(v1));
+    assert!(!cl.contains_variable(v2));
+}
+
+#[test]
+fn test_contains_ignores_inactive_literals() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let lits = vec![v0.to_positive_literal(), v1.to_positive_literal()];
+    let hdr = ClauseHeader::new(ClauseId(0), 1, 0);
+    let cl = Clause::new(hdr, lits);
+
+    assert!(cl.contains_variable(v0));
+    assert!(!cl.contains_variable(v1));
+    assert!(!cl.contains_literal(v1.to_positive_literal()));
+}
+
+#[test]
+fn test_evaluate() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let clauses = vec![test_clause(vec![v0.to_positive_literal(), v1.to_positive_literal()]),
+                       test_clause(vec![v0.to_negative_literal()])];
+    let mut assignment = TaggedVec::new();
+    assignment.ensure_index(&v1, Value::UNASSIGNED);
+
+    // Unassigned literals do not satisfy anything
+    assert!(!evaluate(&clauses, &assignment));
+
+    assignment[v0] = v0.to_negative_literal().satisfy();
+    assert!(!evaluate(&clauses, &assignment));
+
+    assignment[v1] = v1.to_positive_literal().satisfy();
+    assert!(evaluate(&clauses, &assignment));
+
+    assignment[v0] = v0.to_positive_literal().satisfy();
+    assert!(!evaluate(&clauses, &assignment));
+}
+
+#[test]
+fn test_signature_never_rejects_subsumption() {
+    // Pseudo-random clauses over enough variables that signature bits collide
+    let mut state : u64 = 0x2545f4914f6cdd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut clauses = Vec::new();
+    for _ in 0..200 {
+        let len = (next() % 5) as usize + 1;
+        let mut lits : Vec<Literal> = (0..len).map(|_| Literal::from_index((next() % 300) as usize)).collect();
+        lits.sort();
+        lits.dedup();
+        // Include some guaranteed subsets
+        let subset : Vec<Literal> = lits.iter().copied().step_by(2).collect();
+        clauses.push(test_clause(lits));
+        clauses.push(test_clause(subset));
+    }
+
+    for a in clauses.iter() {
+        for b in clauses.iter() {
+            let naive = a.literals().iter().all(|l| b.contains_literal(*l));
+            if naive {
+                assert_eq!(a.signature() & !b.signature(), 0);
+            }
+            assert_eq!(a.subsumes(b), naive);
+        }
+    }
+}
+
+#[test]
+fn test_signature_bits() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v64 = Variable::from_index(64);
+    let v3 = Variable::from_index(3);
+    let cl = test_clause(vec![v0.to_positive_literal(), v3.to_negative_literal()]);
+    assert_eq!(cl.signature(), 0b1001);
+
+    // Variables that collide modulo 64 share a bit
+    let cl = test_clause(vec![v64.to_negative_literal()]);
+    assert_eq!(cl.signature(), 1);
+}
+
+#[test]
+fn test_pin() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let mut cl = test_clause(vec![v0.to_positive_literal()]);
+    assert!(!cl.is_pinned());
+    cl.pin();
+    assert!(cl.is_pinned());
+    cl.pin();
+    assert!(cl.is_pinned());
+}
+
+#[test]
+fn test_learned_flag() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let mut cl = test_clause(vec![v0.to_positive_literal()]);
+    assert!(!cl.is_learned());
+    cl.set_learned(true);
+    assert!(cl.is_learned());
+    // Flags are independent
+    cl.pin();
+    cl.set_learned(false);
+    assert!(!cl.is_learned());
+    assert!(cl.is_pinned());
+    cl.set_learned(true);
+    assert!(cl.is_learned());
+    assert!(cl.is_pinned());
+}
+
+#[test]
+fn test_clone() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let lits = vec![v0.to_positive_literal(), v1.to_negative_literal()];
+    let mut hdr = ClauseHeader::new(ClauseId(3), 1, KEEP_MASK);
+    hdr.bump_activity(2.0);
+    let cl = Clause::new(hdr, lits);
+    let copy = cl.clone();
+    assert_eq!(copy.identifier(), ClauseId(3));
+    assert_eq!(copy.literals(), cl.literals());
+    assert_eq!(copy.activity(), 2.0);
+    assert!(copy.is_pinned());
+    // Inactive literals are copied too
+    assert_eq!(copy[1], v1.to_negative_literal());
+}
+
+#[test]
+fn test_remove_duplicate_literals() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let lits = vec![v1.to_positive_literal(), v1.to_positive_literal(), v0.to_negative_literal(), v1.to_positive_literal()];
+    let mut cl = Clause::new(ClauseHeader::new(ClauseId(0), lits.len(), 0), lits);
+    assert!(cl.remove_duplicate_literals());
+    assert_eq!(cl.literals(), &[v1.to_positive_literal(), v0.to_negative_literal()]);
+    assert!(!cl.remove_duplicate_literals());
+    assert!(!cl.is_tautology());
+
+    let lits = vec![v0.to_negative_literal(), v1.to_positive_literal(), v0.to_positive_literal()];
+    assert!(Clause::new(ClauseHeader::new(ClauseId(0), lits.len(), 0), lits).is_tautology());
+}
+
+#[test]
+fn test_clause_activity() {
+    let lits = vec![Variable::FIRST_VARIABLE.to_positive_literal()];
+    let mut cl = Clause::new(ClauseHeader::new(ClauseId(0), 1, 0), lits);
+    assert_eq!(cl.activity(), 0.0);
+    cl.bump_activity(1.5);
+    cl.bump_activity(2.5);
+    assert_eq!(cl.activity(), 4.0);
+    cl.scale_activity(0.25);
+    assert_eq!(cl.activity(), 1.0);
+    assert_eq!(cl.header().activity(), 1.0);
+}
+
+#[test]
+fn test_new_checked() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let hdr = || ClauseHeader::new(ClauseId(3), 0, 0);
+
+    let cl = Clause::new_checked(hdr(), vec![v1.to_positive_literal(), v1.to_positive_literal(), v0.to_negative_literal()]).unwrap();
+    assert_eq!(cl.literals(), &[v1.to_positive_literal(), v0.to_negative_literal()]);
+    assert_eq!((cl.identifier(), cl.lit_count()), (ClauseId(3), 2));
+
+    assert_eq!(Clause::new_checked(hdr(), vec![]).err(), Some(ClauseError::Empty));
+    let tautology = vec![v0.to_positive_literal(), v1.to_positive_literal(), v0.to_negative_literal()];
+    assert_eq!(Clause::new_checked(hdr(), tautology).err(), Some(ClauseError::Tautology(v0.to_positive_literal())));
+}
+
+#[test]
+fn test_new_checked_under() {
+    let v0 = Variable::FIRST_VARIABLE;
+    let v1 = v0.next_variable();
+    let v2 = v1.next_variable();
+    let v3 = v2.next_variable();
+    let mut assignment = TaggedVec::new();
+    assignment.ensure_index(&v3, Value::UNASSIGNED);
+    assignment[v0] = Value::LIFTED_TRUE;
+    assignment[v1] = Value::LIFTED_FALSE;
+    let hdr = || ClauseHeader::new(ClauseId(0), 0, 0);
+
+    // The false literals move behind the others
+    let lits = vec![v0.to_negative_literal(), v1.to_positive_literal(), v2.to_positive_literal(), v0.to_positive_literal(), v3.to_negative_literal()];
+    let cl = Clause::new_checked_under(hdr(), lits, &assignment);
+    assert_eq!(cl.err(), Some(ClauseError::Tautology(v0.to_negative_literal())));
+    let lits = vec![v0.to_negative_literal(), v1.to_positive_literal(), v2.to_positive_literal(), v3.to_negative_literal()];
+    let cl = Clause::new_checked_under(hdr(), lits, &assignment).unwrap();
+    assert_eq!(cl.literals(), &[v2.to_positive_literal(), v3.to_negative_literal(), v0.to_negative_literal(), v1.to_positive_literal()]);
+
+    let lits = vec![v0.to_negative_literal(), v1.to_positive_literal()];
+    assert_eq!(Clause::new_checked_under(hdr(), lits, &assignment).err(), Some(ClauseError::Falsified));
+}
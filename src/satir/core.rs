@@ -6,6 +6,13 @@ pub struct Variable(i32);
 #[derive(Clone,Copy,Debug,Eq,Ord,PartialEq,PartialOrd)]
 pub struct Literal(i32);
 
+/// The polarity of a literal: whether it asserts its variable or its negation
+#[derive(Clone,Copy,Debug,Eq,PartialEq,Hash)]
+pub enum Polarity {
+    Positive,
+    Negative
+}
+
 /// Values are True, False, or Unassigned
 #[derive(Clone,Copy,Debug,Eq,Ord,PartialEq,PartialOrd)]
 pub struct Value(i8);
@@ -28,31 +35,137 @@ impl tagged::TaggedIndexable for Literal {
 impl Variable {
     pub const FIRST_VARIABLE : Variable = Variable(0);
 
+    /// The largest variable whose literals can be represented
+    ///
+    /// A literal stores its variable shifted left by one (with the polarity in
+    /// the low bit) in an `i32`, so this is `2^30 - 1` (DIMACS variable
+    /// `2^30`), whose negative literal is `i32::MAX`.  The unchecked
+    /// constructors silently wrap around past it; use the `checked_` variants
+    /// on untrusted input.
+    pub const MAX_VARIABLE : Variable = Variable(i32::MAX >> 1);
+
+    /// The variable with the given index (the inverse of `as_index`)
+    pub fn from_index(ix : usize) -> Variable {
+        Variable(ix as i32)
+    }
+
     pub fn next_variable(&self) -> Variable {
         let Variable(num) = self;
         Variable(num + 1)
     }
 
+    /// The next variable, or `None` if it would be past `MAX_VARIABLE`
+    pub fn checked_next_variable(&self) -> Option<Variable> {
+        Some(self.next_variable()).filter(|_| *self < Variable::MAX_VARIABLE)
+    }
+
     pub fn to_positive_literal(&self) -> Literal {
-        let Variable(vnum) = self;
-        Literal(vnum << 1)
+        Literal::with_polarity(*self, Polarity::Positive)
     }
 
     pub fn to_negative_literal(&self) -> Literal {
+        Literal::with_polarity(*self, Polarity::Negative)
+    }
+
+    /// The positive literal of the variable, or `None` if the variable is out
+    /// of the representable range (see `MAX_VARIABLE`)
+    pub fn checked_to_positive_literal(&self) -> Option<Literal> {
+        self.checked_with_polarity(Polarity::Positive)
+    }
+
+    /// The negative literal of the variable, or `None` if the variable is out
+    /// of the representable range (see `MAX_VARIABLE`)
+    pub fn checked_to_negative_literal(&self) -> Option<Literal> {
+        self.checked_with_polarity(Polarity::Negative)
+    }
+
+    fn checked_with_polarity(&self, polarity : Polarity) -> Option<Literal> {
+        let Variable(vnum) = self;
+        if (0..=Variable::MAX_VARIABLE.0).contains(vnum) {
+            Some(Literal::with_polarity(*self, polarity))
+        } else {
+            None
+        }
+    }
+
+    /// The variable with the given DIMACS number (`None` for 0, which is not a
+    /// variable)
+    ///
+    /// This (with `to_dimacs`) is the single place where DIMACS numbering is
+    /// related to ours: DIMACS variables start at 1, while ours start at
+    /// `FIRST_VARIABLE` (0), so DIMACS variable `n` is internal variable
+    /// `n - 1`.  The DIMACS parser numbers the variables of a formula densely in
+    /// ascending DIMACS order, which coincides with this numbering whenever
+    /// every variable up to the largest appears in the formula; otherwise the
+    /// mapping is given by `DIMACS::variable` and `DIMACS::dimacs_variables`.
+    pub fn from_dimacs(dimacs_var : u32) -> Option<Variable> {
+        dimacs_var.checked_sub(1).map(|ix| Variable(ix as i32))
+    }
+
+    /// The DIMACS number of the variable (the inverse of `from_dimacs`)
+    pub fn to_dimacs(&self) -> u32 {
         let Variable(vnum) = self;
-        Literal((vnum << 1) | 1)
+        *vnum as u32 + 1
+    }
+
+    /// The index of the largest literal that a literal-indexed `TaggedVec`
+    /// for the variables before `next_var` needs to hold
+    ///
+    /// Like variable-indexed vectors, these keep slots for the literals of
+    /// `next_var` itself.  The negative literal has the larger index (its low
+    /// bit is set), so the vector needs one more slot than this.
+    pub fn max_literal_index(next_var : &Variable) -> usize {
+        tagged::TaggedIndexable::as_index(&next_var.to_negative_literal())
     }
 }
 
 impl Literal {
+    /// The literal with the given index (the inverse of `as_index`)
+    pub fn from_index(ix : usize) -> Literal {
+        Literal(ix as i32)
+    }
+
+    /// The literal of the given variable with the given polarity
+    ///
+    /// The low bit of a literal is set for negative literals
+    pub fn with_polarity(var : Variable, polarity : Polarity) -> Literal {
+        let Variable(vnum) = var;
+        match polarity {
+            Polarity::Positive => Literal(vnum << 1),
+            Polarity::Negative => Literal((vnum << 1) | 1)
+        }
+    }
+
+    /// The literal corresponding to a signed DIMACS literal (`None` for 0,
+    /// which terminates clauses); see `Variable::from_dimacs`
+    pub fn from_dimacs(dimacs_lit : i32) -> Option<Literal> {
+        let polarity = if dimacs_lit < 0 { Polarity::Negative } else { Polarity::Positive };
+        Variable::from_dimacs(dimacs_lit.unsigned_abs()).map(|v| Literal::with_polarity(v, polarity))
+    }
+
+    /// The signed DIMACS literal corresponding to this literal (the inverse of
+    /// `from_dimacs`)
+    pub fn to_dimacs(&self) -> i32 {
+        let dimacs_var = self.variable().to_dimacs() as i32;
+        if self.is_negated() { -dimacs_var } else { dimacs_var }
+    }
+
     pub fn variable(&self) -> Variable {
         let Literal(lnum) = self;
         Variable(lnum >> 1)
     }
 
-    pub fn is_negated(&self) -> bool {
+    pub fn polarity(&self) -> Polarity {
         let Literal(lnum) = self;
-        lnum & 1 == 0
+        if lnum & 1 == 0 {
+            Polarity::Positive
+        } else {
+            Polarity::Negative
+        }
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.polarity() == Polarity::Negative
     }
 
     pub fn negate(&self) -> Literal {
@@ -60,15 +173,39 @@ impl Literal {
         Literal(lnum ^ 1)
     }
 
-    pub fn under_value(&self, v : Value) -> Value {
+    /// The polarity bit of the literal (1 for negative literals), as a `Value`
+    /// bit
+    ///
+    /// Values encode true as 0 and false as 1, so flipping a value by this bit
+    /// evaluates the literal; the unassigned values keep their higher bit.
+    fn polarity_bit(&self) -> i8 {
         let Literal(lval) = self;
+        (lval & 1) as i8
+    }
+
+    pub fn under_value(&self, v : Value) -> Value {
         let Value(val) = v;
-        Value(val ^ ((lval & 1) as i8))
+        Value(val ^ self.polarity_bit())
     }
 
     pub fn satisfy(&self) -> Value {
-        let Literal(lval) = self;
-        Value((lval & 1) as i8)
+        Value(self.polarity_bit())
+    }
+}
+
+/// Variables are displayed by their DIMACS number (see `Variable::to_dimacs`);
+/// `Debug` shows the internal number instead
+impl core::fmt::Display for Variable {
+    fn fmt(&self, f : &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.to_dimacs(), f)
+    }
+}
+
+/// Literals are displayed as signed DIMACS literals (see `Literal::to_dimacs`);
+/// `Debug` shows the internal encoding instead
+impl core::fmt::Display for Literal {
+    fn fmt(&self, f : &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.to_dimacs(), f)
     }
 }
 
@@ -80,10 +217,141 @@ impl Value {
     pub fn is_unassigned(&self) -> bool {
         *self >= Value::UNASSIGNED
     }
-}
 
+    /// Disjunction in three-valued (Kleene) logic: true if either value is
+    /// true, false if both are false, and unassigned otherwise
+    pub fn or(self, other : Value) -> Value {
+        if self == Value::LIFTED_TRUE || other == Value::LIFTED_TRUE {
+            Value::LIFTED_TRUE
+        } else if self.is_unassigned() || other.is_unassigned() {
+            Value::UNASSIGNED
+        } else {
+            Value::LIFTED_FALSE
+        }
+    }
+
+    /// Conjunction in three-valued (Kleene) logic: false if either value is
+    /// false, true if both are true, and unassigned otherwise
+    pub fn and(self, other : Value) -> Value {
+        if self == Value::LIFTED_FALSE || other == Value::LIFTED_FALSE {
+            Value::LIFTED_FALSE
+        } else if self.is_unassigned() || other.is_unassigned() {
+            Value::UNASSIGNED
+        } else {
+            Value::LIFTED_TRUE
+        }
+    }
+}
 
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
 pub enum Result {
     Unsat,
-    Sat
+    Sat,
+    /// The solver stopped before finding an answer (e.g., it was interrupted)
+    Unknown
+}
+
+
+#[test]
+fn test_polarity() {
+    let v = Variable::FIRST_VARIABLE.next_variable();
+    assert_eq!(v.to_positive_literal().polarity(), Polarity::Positive);
+    assert_eq!(v.to_negative_literal().polarity(), Polarity::Negative);
+    assert!(!v.to_positive_literal().is_negated());
+    assert!(v.to_negative_literal().is_negated());
+    assert_eq!(v.to_positive_literal().negate().polarity(), Polarity::Negative);
+}
+
+#[test]
+fn test_with_polarity() {
+    let v = Variable::FIRST_VARIABLE.next_variable();
+    for polarity in &[Polarity::Positive, Polarity::Negative] {
+        let lit = Literal::with_polarity(v, *polarity);
+        assert_eq!(lit.variable(), v);
+        assert_eq!(lit.polarity(), *polarity);
+    }
+
+    assert_eq!(Literal::with_polarity(v, Polarity::Positive), v.to_positive_literal());
+    assert_eq!(Literal::with_polarity(v, Polarity::Negative), v.to_negative_literal());
+}
+
+#[test]
+fn test_max_literal_index() {
+    for n in 0..5 {
+        let next_var = Variable::from_index(n);
+        let max_ix = Variable::max_literal_index(&next_var);
+        assert_eq!(max_ix, 2 * n + 1);
+        for ix in 0..=n {
+            let v = Variable::from_index(ix);
+            assert!(tagged::TaggedIndexable::as_index(&v.to_positive_literal()) <= max_ix);
+            assert!(tagged::TaggedIndexable::as_index(&v.to_negative_literal()) <= max_ix);
+        }
+    }
+}
+
+#[test]
+fn test_representable_boundary() {
+    let max = Variable::MAX_VARIABLE;
+    assert_eq!(max.to_dimacs(), 1 << 30);
+    let neg = max.checked_to_negative_literal().unwrap();
+    assert_eq!(neg, max.to_negative_literal());
+    assert_eq!(tagged::TaggedIndexable::as_index(&neg), i32::MAX as usize);
+    assert_eq!(neg.variable(), max);
+    assert_eq!(max.checked_to_positive_literal().unwrap().variable(), max);
+    assert_eq!(neg.to_dimacs(), -(1 << 30));
+
+    // One past the boundary wraps around with the unchecked operations
+    assert_eq!(max.checked_next_variable(), None);
+    let past = max.next_variable();
+    assert_eq!(past.checked_to_positive_literal(), None);
+    assert_eq!(past.checked_to_negative_literal(), None);
+    assert_ne!(past.to_positive_literal().variable(), past);
+
+    let below = Variable::from_index(tagged::TaggedIndexable::as_index(&max) - 1);
+    assert_eq!(below.checked_next_variable(), Some(max));
+    assert_eq!(Variable::FIRST_VARIABLE.checked_to_positive_literal(), Some(Variable::FIRST_VARIABLE.to_positive_literal()));
+}
+
+#[test]
+fn test_dimacs_numbering() {
+    assert_eq!(Variable::from_dimacs(0), None);
+    assert_eq!(Variable::from_dimacs(1), Some(Variable::FIRST_VARIABLE));
+    assert_eq!(Variable::FIRST_VARIABLE.to_dimacs(), 1);
+    assert_eq!(Literal::from_dimacs(0), None);
+    assert_eq!(Literal::from_dimacs(-1), Some(Variable::FIRST_VARIABLE.to_negative_literal()));
+    for dimacs_lit in &[1, -1, 2, -7, 100] {
+        assert_eq!(Literal::from_dimacs(*dimacs_lit).unwrap().to_dimacs(), *dimacs_lit);
+    }
+}
+
+#[test]
+fn test_display_dimacs() {
+    let v1 = Variable::FIRST_VARIABLE;
+    let v3 = Variable::from_index(2);
+    assert_eq!(v1.to_string(), "1");
+    assert_eq!(v3.to_string(), "3");
+    assert_eq!(v1.to_positive_literal().to_string(), "1");
+    assert_eq!(v3.to_negative_literal().to_string(), "-3");
+    assert_eq!(format!("{}|{:>3}", Literal::from_dimacs(-12).unwrap(), Literal::from_dimacs(7).unwrap()), "-12|  7");
+    // Debug still shows the internal encoding
+    assert_eq!(format!("{:?}", v3.to_negative_literal()), "Literal(5)");
+    assert_eq!(format!("{:?}", v3), "Variable(2)");
+}
+
+#[test]
+fn test_kleene_truth_tables() {
+    let (t, f, u) = (Value::LIFTED_TRUE, Value::LIFTED_FALSE, Value::UNASSIGNED);
+    let or_table = [(t, t, t), (t, f, t), (t, u, t),
+                    (f, t, t), (f, f, f), (f, u, u),
+                    (u, t, t), (u, f, u), (u, u, u)];
+    for (a, b, expected) in or_table.iter() {
+        assert_eq!(a.or(*b), *expected, "{:?} or {:?}", a, b);
+    }
+
+    let and_table = [(t, t, t), (t, f, f), (t, u, u),
+                     (f, t, f), (f, f, f), (f, u, f),
+                     (u, t, u), (u, f, f), (u, u, u)];
+    for (a, b, expected) in and_table.iter() {
+        assert_eq!(a.and(*b), *expected, "{:?} and {:?}", a, b);
+    }
 }
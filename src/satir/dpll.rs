@@ -1,35 +1,472 @@
-use std::collections::{BTreeSet, VecDeque};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use ::core::sync::atomic::{AtomicBool, Ordering};
+use ::core::cmp::Reverse;
+use ::core::hash::{BuildHasherDefault, Hasher};
 use ordered_float::OrderedFloat;
 use priority_queue::PriorityQueue;
 
-use crate::satir::core::{Literal, Variable, Value};
+use crate::satir::core::{Literal, Polarity, Variable, Value};
 use crate::satir::core;
-use crate::satir::clause::{Clause, ClauseId};
-use crate::satir::tagged::TaggedVec;
+use crate::satir::arena::{ClauseArena, ClauseMut, ClauseRef};
+use crate::satir::assignment::{Assignment, AssignmentStorage};
+use crate::satir::clause::{Clause, ClauseHeader, ClauseId, LEARNED_MASK};
+use crate::satir::tagged::{TaggedIndexable, TaggedVec};
 
 /// Solver statistics tracked for reporting purposes
-struct Statistics {
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stats {
     /// The number of conflicts encountered while solving
-    conflicts : usize,
+    pub conflicts : usize,
+    /// The number of clauses learned from conflicts
+    pub learned_clauses : usize,
     /// The total number of decisions attempted
-    decisions : usize,
+    pub decisions : usize,
     /// The total number of times that the unit propagation rule has been applied
-    propagations : usize
+    pub propagations : usize,
+    /// The number of times the variable order was rebuilt to drop stale
+    /// (already assigned) variables
+    pub order_rebuilds : usize,
+    /// The number of restarts (excluding those skipped because nothing was
+    /// learned since the last one)
+    pub restarts : usize,
+    /// The seed of the random number generator; passing this as
+    /// `SolverConfig::seed` reproduces the run (even if the seed was generated
+    /// because none was given)
+    pub seed : u64
 }
 
-fn empty_statistics() -> Statistics {
-    Statistics {
+impl Stats {
+    /// Every statistic, named as in `to_json`
+    pub fn counters(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("conflicts", self.conflicts as u64),
+            ("learned_clauses", self.learned_clauses as u64),
+            ("decisions", self.decisions as u64),
+            ("propagations", self.propagations as u64),
+            ("restarts", self.restarts as u64),
+            ("order_rebuilds", self.order_rebuilds as u64),
+            ("seed", self.seed)
+        ]
+    }
+
+    /// The statistics as a JSON object mapping each name to its (integer)
+    /// value, e.g., `{"conflicts": 12, "learned_clauses": 11, ...}`
+    pub fn to_json(&self) -> String {
+        let fields : Vec<String> = self.counters().iter().map(|(name, value)| format!("\"{}\": {}", name, value)).collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+fn empty_statistics(seed : u64) -> Stats {
+    Stats {
         conflicts : 0,
+        learned_clauses : 0,
         decisions : 0,
-        propagations : 0
+        propagations : 0,
+        order_rebuilds : 0,
+        restarts : 0,
+        seed
+    }
+}
+
+/// The seed used for the random number generator in deterministic mode
+pub const DETERMINISTIC_SEED : u64 = 0x5eed_5a71_2021_0001;
+
+/// The decision level recorded for a variable that is not assigned
+pub const NO_LEVEL : usize = usize::MAX;
+
+/// True if the level is that of an assigned variable (i.e., not `NO_LEVEL`)
+pub fn is_assigned_level(level : usize) -> bool {
+    level != NO_LEVEL
+}
+
+/// Heuristics for choosing the initial variable order
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrderHeuristic {
+    /// Prioritize variables in the order they are first encountered in the
+    /// clauses
+    EncounterOrder,
+    /// Prioritize variables by the number of clauses they occur in
+    OccurrenceCount,
+    /// Prioritize variables by their (two-sided) Jeroslow-Wang score, and
+    /// decide each with the polarity of its higher-scoring literal
+    JeroslowWang
+}
+
+/// Where conflict analysis stops resolving, which determines the clause learned
+/// from each conflict
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LearningScheme {
+    /// Stop at the first unique implication point: the first literal (counting
+    /// back from the conflict) that every path from the decision to the
+    /// conflict passes through
+    FirstUip,
+    /// Keep resolving until the only literal left from the conflict level is
+    /// its decision
+    ///
+    /// The learned clause then blames the decision itself, which usually
+    /// prunes less of the search than the first UIP clause, but it is still
+    /// asserting.
+    LastUip
+}
+
+/// The order in which propagation scans the unwatched literals of a clause for
+/// a replacement watch
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WatchSearch {
+    /// From the third literal to the last
+    Forward,
+    /// From the last literal back to the third
+    Backward
+}
+
+/// When the solver abandons its current decisions and starts over (keeping
+/// what it has learned)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartPolicy {
+    /// Never restart
+    Never,
+    /// Restart after `unit` times the next term of the Luby sequence
+    /// (1, 1, 2, 1, 1, 2, 4, ...) conflicts
+    Luby { unit : u64 },
+    /// Restart after `first` conflicts, then after `factor` times as many as
+    /// the previous interval
+    Geometric { first : u64, factor : f64 }
+}
+
+/// What to do with a clause learned from a conflict (see `Solver::set_learn_hook`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LearnAction {
+    /// Add the learned clause to the clause database and backjump
+    Keep,
+    /// Discard the learned clause; the solver backtracks chronologically (one
+    /// decision at a time) instead of backjumping
+    Drop,
+    /// Add the given clause instead of the learned clause, then backtrack
+    /// chronologically
+    ///
+    /// The replacement must be implied by the formula (it is added to the
+    /// clause database), but it does not need to be falsified by the current
+    /// assignment.  An empty replacement proves the formula unsatisfiable.
+    Replace(Vec<Literal>)
+}
+
+/// A callback consulted after each conflict with the clause learned from it
+pub type LearnHook = Box<dyn FnMut(&[Literal]) -> LearnAction + Send>;
+
+/// A total assignment to the variables of a formula
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Model {
+    /// The value of each variable (by index)
+    values : Vec<bool>
+}
+
+impl Model {
+    /// The value of the variable in the model
+    pub fn value(&self, var : Variable) -> bool {
+        self.values[var.as_index()]
+    }
+
+    /// The number of variables in the model
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The literals that are true in the model, one per variable (in variable
+    /// order)
+    pub fn literals(&self) -> impl Iterator<Item = Literal> + '_ {
+        self.values.iter().enumerate().map(|(ix, value)| {
+            let polarity = if *value { Polarity::Positive } else { Polarity::Negative };
+            Literal::with_polarity(Variable::from_index(ix), polarity)
+        })
+    }
+}
+
+/// Everything known about a finished solve
+#[derive(Clone, Debug)]
+pub struct SolveOutcome {
+    pub result : core::Result,
+    pub stats : Stats,
+    /// The model (only if `result` is `Sat`)
+    pub model : Option<Model>,
+    /// The time spent solving (including preprocessing)
+    pub elapsed : ::core::time::Duration
+}
+
+/// A handle for stopping a running solver from another thread (see
+/// `Solver::interrupt_handle`)
+#[derive(Clone, Debug)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Ask the solver to stop; its current (or next) call to solve returns
+    /// `Unknown`
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Configuration options for the solver
+#[derive(Clone, Debug)]
+pub struct SolverConfig {
+    /// Make every run on the same input behave identically
+    ///
+    /// In this mode, the random number generator always uses
+    /// `DETERMINISTIC_SEED` (ignoring `seed`), so randomized heuristics are
+    /// still applied, but reproducibly.  The remaining sources of ordering in
+    /// the solver are already stable and must stay that way: watchlists are
+    /// `BTreeSet`s (iterated in `ClauseId` order), and ties in the variable
+    /// order are broken purely by the sequence of queue operations, never by
+    /// hashing.
+    pub deterministic : bool,
+    /// The seed for the random number generator; if `None` (and the solver is
+    /// not in deterministic mode), a seed is derived from the clock (and
+    /// reported in `Stats::seed`); without `std` there is no clock, so
+    /// `DETERMINISTIC_SEED` is used
+    pub seed : Option<u64>,
+    /// The probability (in [0, 1]) of deciding a random variable rather than
+    /// the next one in the variable order
+    pub random_decision_freq : f64,
+    /// Record a textual trace of the search (see `Solver::trace`)
+    pub trace : bool,
+    /// How to compute the initial variable order
+    pub order_heuristic : OrderHeuristic,
+    /// Discard learned clauses longer than this, backtracking chronologically
+    /// instead of backjumping
+    ///
+    /// Short clauses are cheaper to store and propagate, but every discarded
+    /// clause loses the pruning it would have provided (and the backjump), so
+    /// a tight limit can make the search much longer.
+    pub max_learned_length : Option<usize>,
+    /// The polarity to decide variables with, unless a heuristic (e.g.,
+    /// `OrderHeuristic::JeroslowWang`) or `Solver::set_phase` says otherwise
+    pub default_phase : Polarity,
+    /// When to restart the search
+    pub restart_policy : RestartPolicy,
+    /// Sort the literals of each clause (by variable) before choosing the
+    /// watched literals, so that the initial watchlists do not depend on the
+    /// order of literals within clauses in the input
+    pub normalize_clauses : bool,
+    /// Give up (returning `Unknown`) if the decision level ever exceeds this
+    ///
+    /// Every decision level assigns a distinct variable, except for the empty
+    /// levels opened for assumptions that already hold, so a correct solver
+    /// never goes deeper than the number of variables plus the number of
+    /// assumptions.  Exceeding a bound of that plus a margin indicates a bug
+    /// (e.g., a loop that keeps deciding without backtracking); in unit test
+    /// builds, this panics with a diagnostic instead.
+    pub max_decision_depth : Option<usize>,
+    /// An extra bump (as a multiple of the usual one) for the variables of
+    /// each learned clause, on top of the bump that every variable involved in
+    /// the conflict gets
+    ///
+    /// This focuses the search on the most recent conflicts, which helps on
+    /// some structured instances.  Zero (the default) disables it.
+    pub recency_bias : f32,
+    /// Look for pure literals (those whose variable occurs in only one polarity
+    /// among the problem clauses that are not yet satisfied) after every this
+    /// many decisions, and decide them before anything else
+    ///
+    /// A pure literal can be assigned true without losing any models of the
+    /// remaining clauses, but it is not implied by any clause: conflict
+    /// analysis needs a reason for every implied literal, so pure literals are
+    /// decided (each at its own level) rather than assigned with a reason.
+    /// Learned clauses are ignored when checking purity since they are implied
+    /// by the problem clauses.  The check is skipped until every assumption
+    /// has been made, since assumptions must occupy the first decision levels.
+    /// `None` (the default) disables the check.
+    pub pure_literal_interval : Option<usize>,
+    /// Drop the assigned variables from the variable order after every this
+    /// many restarts
+    ///
+    /// Variables assigned by propagation stay in the order until they are
+    /// popped, and those assigned at level 0 never leave; this bounds the
+    /// order by the number of unassigned variables at each such restart.
+    /// `None` (the default) only rebuilds the order when most pops are
+    /// skipped.
+    pub order_gc_interval : Option<u64>,
+    /// Record the resolution steps that derive each learned clause, for
+    /// `Solver::explain_learned`
+    ///
+    /// This keeps copies of the conflict and reason clauses of every conflict,
+    /// so it is meant for small (e.g., teaching) examples.
+    pub record_derivations : bool,
+    /// Check the result of every round of unit propagation against a naive
+    /// scan of all of the clauses, panicking if they disagree
+    ///
+    /// This is very slow, but catches bugs in the two-watched literal scheme
+    /// (e.g., a clause missing from a watchlist) where they happen, rather
+    /// than as a wrong answer much later.
+    pub verify_propagation : bool,
+    /// Store the assignment with two bits per variable (see
+    /// `PackedAssignment`) rather than one byte
+    ///
+    /// This only changes the representation: the search is identical.
+    pub packed_assignment : bool,
+    /// Reset the phase of every variable to `default_phase` on each restart
+    ///
+    /// Otherwise, the phases (including those from `Solver::set_phase` and the
+    /// order heuristic) are kept across restarts.
+    pub reset_phases_on_restart : bool,
+    /// Where conflict analysis stops (the first UIP unless experimenting)
+    pub learning_scheme : LearningScheme,
+    /// How propagation looks for a new literal to watch
+    ///
+    /// Either direction finds a replacement if there is one, but they pick
+    /// different replacements (when there are several), which changes the
+    /// order of propagation and the memory access pattern.
+    pub watch_search : WatchSearch,
+    /// Block a restart that is due if the trail (the number of assigned
+    /// variables) at the last conflict was more than this factor times its
+    /// average size at recent conflicts
+    ///
+    /// A trail that is much longer than usual suggests that the solver is
+    /// close to a model, which a restart would throw away.  A blocked restart
+    /// starts the next restart interval as a skipped one does.  `None` (the
+    /// default) never blocks restarts.
+    pub restart_blocking : Option<f64>
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            deterministic : false,
+            seed : None,
+            random_decision_freq : 0.0,
+            trace : false,
+            order_heuristic : OrderHeuristic::EncounterOrder,
+            max_learned_length : None,
+            default_phase : Polarity::Positive,
+            restart_policy : RestartPolicy::Luby { unit : 100 },
+            normalize_clauses : false,
+            max_decision_depth : None,
+            recency_bias : 0.0,
+            pure_literal_interval : None,
+            order_gc_interval : None,
+            record_derivations : false,
+            verify_propagation : false,
+            packed_assignment : false,
+            reset_phases_on_restart : false,
+            learning_scheme : LearningScheme::FirstUip,
+            watch_search : WatchSearch::Forward,
+            restart_blocking : None
+        }
+    }
+}
+
+/// A small xorshift random number generator
+///
+/// Search heuristics don't need anything stronger, and this keeps each run
+/// reproducible from a single seed.
+struct Rng {
+    state : u64
+}
+
+impl Rng {
+    fn new(seed : u64) -> Self {
+        // The xorshift state can never be zero
+        Rng { state : if seed == 0 { DETERMINISTIC_SEED } else { seed } }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A uniformly distributed float in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniformly distributed integer in [0, bound)
+    fn below(&mut self, bound : usize) -> usize {
+        (self.next_u64() % (bound as u64)) as usize
+    }
+}
+
+/// Pick the seed for a run, honoring deterministic mode
+fn effective_seed(config : &SolverConfig) -> u64 {
+    if config.deterministic {
+        return DETERMINISTIC_SEED;
     }
+
+    match config.seed {
+        Some(seed) => seed,
+        None => clock_seed()
+    }
+}
+
+/// A seed derived from the current time
+#[cfg(feature = "std")]
+fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(DETERMINISTIC_SEED)
+}
+
+#[cfg(not(feature = "std"))]
+fn clock_seed() -> u64 {
+    DETERMINISTIC_SEED
+}
+
+/// Bookkeeping for a single decision level
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DecisionLevel {
+    /// The index into the decision stack of the decision that opened this level
+    start : usize,
+    /// True if the decision is the negation of an earlier decision whose branch
+    /// has already been refuted (i.e., both polarities have been tried) during
+    /// chronological backtracking
+    flipped : bool
+}
+
+/// A saved search state (see `Solver::snapshot`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The assigned literals, in the order they were assigned, with the
+    /// decision level and reason of each
+    trail : Vec<(Literal, usize, Option<ClauseId>)>,
+    decision_levels : Vec<DecisionLevel>,
+    propagation_queue : Vec<Literal>,
+    has_model : bool,
+    /// The number of times the clause database had been compacted, which
+    /// invalidates the reasons
+    compactions : usize
 }
 
 struct SolverState {
-    /// The decisions that have been made (in order)
+    /// The assignments that have been made (in order), including both decisions
+    /// and the literals implied by unit propagation
     decision_stack : Vec<Literal>,
+    /// The decision levels opened so far; level `i + 1` begins at
+    /// `decision_stack[decision_levels[i].start]`, while everything before the
+    /// first level was assigned at level 0
+    decision_levels : Vec<DecisionLevel>,
     /// The current assignment (which could be derived from the decision stack)
-    assignment : TaggedVec<Variable, Value>,
+    assignment : AssignmentStorage,
+    /// The decision level at which each assigned variable was assigned
+    /// (`NO_LEVEL` for unassigned variables)
+    levels : TaggedVec<Variable, usize>,
+    /// The clause that implied the assignment of each variable (if any);
+    /// decisions and the units found by preprocessing have no reason
+    ///
+    /// The implied literal is always the first literal of its reason clause
+    reasons : TaggedVec<Variable, Option<ClauseId>>,
+    /// Scratch space for conflict analysis, marking the variables seen so far
+    seen : TaggedVec<Variable, bool>,
     /// Maintain an index of variables to the clauses watching them; note that
     /// we have to refer to clauses by their index into the clause database
     ///
@@ -39,86 +476,300 @@ struct SolverState {
     /// The order to decide variables; note that this *can* be updated
     /// dynamically. Also note that the variables in this could potentially
     /// already be decided due to e.g., the watched literals queue
-    variable_order : PriorityQueue<Variable, OrderedFloat<f32>>,
-    /// Literals that we must assert next due to findings (via two-watched
-    /// literals) during unit propagation; these take priority over the natural
-    /// variable ordering
+    variable_order : VariableOrder,
+    /// The current activity of each variable, which is its priority in
+    /// `variable_order`; this is kept separately so that variables removed from
+    /// the order (when decided) can be re-inserted with the same priority
+    variable_activity : TaggedVec<Variable, OrderedFloat<f32>>,
+    /// The amount to bump the activity of variables involved in a conflict;
+    /// this grows over time so that recent conflicts matter more
+    variable_increment : f32,
+    /// The amount to bump the activity of learned clauses involved in a
+    /// conflict (which grows like `variable_increment`)
+    clause_increment : f64,
+    /// Learned clauses are reduced once there are this many of them
+    max_learned : f64,
+    /// The number of conflicts since the last restart
+    conflicts_since_restart : u64,
+    /// The number of clauses (including units) learned since the last restart
+    learned_since_restart : usize,
+    /// The number of restart intervals completed so far (including those whose
+    /// restart was skipped or blocked)
+    restart_intervals : u64,
+    /// The moving average of the trail size at each conflict (see
+    /// `SolverConfig::restart_blocking`)
+    trail_average : f64,
+    /// The number of trail sizes in `trail_average` (up to
+    /// `TRAIL_AVERAGE_WINDOW`)
+    trail_samples : u64,
+    /// The trail size at the last conflict
+    conflict_trail : usize,
+    /// The number of level 0 assignments when the clause database was last
+    /// simplified (see `simplify_at_root`)
+    simplified_assignments : usize,
+    /// The number of decisions after which to next look for pure literals (see
+    /// `SolverConfig::pure_literal_interval`)
+    next_pure_check : usize,
+    /// The value to assign each variable when it is decided
+    saved_phase : TaggedVec<Variable, Value>,
+    /// Assigned literals whose consequences (via two-watched literals) have not
+    /// been propagated yet
+    ///
+    /// The queue is always drained (or cleared by a conflict) before the next
+    /// decision, so implied literals are always assigned before any decision
+    /// that could follow them.
     propagation_queue : VecDeque<Literal>,
+    /// The number of variables popped from `variable_order` since it was last
+    /// rebuilt
+    order_pops : usize,
+    /// The number of those pops that were skipped because the variable was
+    /// already assigned
+    order_skips : usize,
     /// Statistics from one run of the algorithm
-    statistics : Statistics
+    statistics : Stats,
+    /// The source of randomness for search heuristics
+    rng : Rng,
+    /// The search trace, if tracing is enabled
+    trace : String
+}
+
+/// The resolution steps by which conflict analysis derived a learned clause
+/// (see `SolverConfig::record_derivations`)
+///
+/// The clauses are copied when the clause is learned, since the clauses
+/// themselves can later be simplified, renumbered, or deleted.
+struct Derivation {
+    /// The clause falsified by the conflict
+    conflict : Vec<Literal>,
+    /// The reason clauses resolved with the conflict clause, in order; the
+    /// first literal of each is the literal it implied, which is resolved on
+    reasons : Vec<Vec<Literal>>,
+    /// The literals of these clauses that were false at level 0, which
+    /// analysis leaves out of the learned clause
+    fixed : Vec<Literal>
+}
+
+impl Derivation {
+    /// Replay the resolution steps, showing the resolvent after each
+    fn explain(&self) -> String {
+        let mut resolvent = self.conflict.clone();
+        let mut out = format!("conflict: {}\n", display_literals(&resolvent));
+        for reason in self.reasons.iter() {
+            let implied = reason[0];
+            resolvent.retain(|l| *l != implied.negate());
+            for lit in reason[1..].iter() {
+                if !resolvent.contains(lit) {
+                    resolvent.push(*lit);
+                }
+            }
+            out.push_str(&format!("resolve on {} with {}: {}\n",
+                                  implied.variable(), display_literals(reason), display_literals(&resolvent)));
+        }
+        if !self.fixed.is_empty() {
+            resolvent.retain(|l| !self.fixed.contains(l));
+            out.push_str(&format!("drop false at level 0 {}: {}\n", display_literals(&self.fixed), display_literals(&resolvent)));
+        }
+        out.push_str(&format!("learned: {}\n", display_literals(&resolvent)));
+        out
+    }
+}
+
+/// A DRUP proof being written (see `Solver::set_proof_writer`)
+#[cfg(feature = "std")]
+struct ProofLog {
+    out : Box<dyn std::io::Write + Send>,
+    /// The DIMACS number of each variable, if they are named (see
+    /// `Solver::set_dimacs_variables`)
+    dimacs_variables : Option<TaggedVec<Variable, u32>>,
+    /// The first error writing the proof, after which nothing more is written
+    error : Option<std::io::Error>
+}
+
+#[cfg(feature = "std")]
+impl ProofLog {
+    /// Add a clause (the empty clause, for the final step) to the proof
+    fn add(&mut self, lits : &[Literal]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let mut line = String::new();
+        for lit in lits {
+            let var = match &self.dimacs_variables {
+                Some(names) => names[lit.variable()],
+                None => lit.variable().to_dimacs()
+            } as i64;
+            line.push_str(&format!("{} ", if lit.is_negated() { -var } else { var }));
+        }
+        line.push_str("0\n");
+        if let Err(e) = self.out.write_all(line.as_bytes()) {
+            self.error = Some(e);
+        }
+    }
 }
 
 struct Env {
-    /// The original clauses of the problem
-    problem : TaggedVec<ClauseId, Clause>,
+    /// The clauses of the problem, followed by learned clauses
+    problem : ClauseArena,
+    /// The number of clauses in `problem` that came from the original problem
+    /// (as opposed to being learned)
+    num_problem_clauses : usize,
+    /// Literals assumed true for the current call to solve
+    assumptions : Vec<Literal>,
+    /// Set (by an `InterruptHandle`) to stop the search
+    interrupted : Arc<AtomicBool>,
+    /// The clauses (in `problem`) containing each variable, in either polarity
+    occurrences : TaggedVec<Variable, Vec<ClauseId>>,
+    /// The callback to consult about learned clauses
+    on_learn : Option<LearnHook>,
+    /// The literals of the clause whose conflict at level 0 proved the formula
+    /// unsatisfiable (see `Solver::final_conflict`)
+    final_conflict : Option<Vec<Literal>>,
+    /// The derivation of each learned clause (keyed by its sorted literals),
+    /// if `SolverConfig::record_derivations` is set
+    derivations : BTreeMap<Vec<Literal>, Derivation>,
+    /// The proof of unsatisfiability, if one is being written
+    #[cfg(feature = "std")]
+    proof : Option<ProofLog>,
+    /// The number of times the clause database has been compacted (which
+    /// renumbers the clauses)
+    compactions : usize,
+    /// One past the largest variable in the problem
+    next_var : Variable,
+    config : SolverConfig,
     solver_state : SolverState
 }
 
+impl Env {
+    fn num_learned_clauses(&self) -> usize {
+        self.problem.len() - self.num_problem_clauses
+    }
+
+    /// Add a clause to the proof, if one is being written
+    fn add_to_proof(&mut self, lits : &[Literal]) {
+        #[cfg(feature = "std")]
+        if let Some(proof) = self.proof.as_mut() {
+            proof.add(lits);
+        }
+    }
+}
+
 impl SolverState {
     // Evaluate this literal with respect to the current assignment
     fn value_of(&self, lit : Literal) -> Value {
-        lit.under_value(self.assignment[lit.variable()])
+        lit.under_value(self.assignment.value_of(lit.variable()))
     }
 
     fn decision_level(&self) -> usize {
-        self.decision_stack.len()
+        self.decision_levels.len()
     }
 }
 
 struct PreprocessResult {
     /// Variables with implied initial assignments
     initial_assignment : TaggedVec<Variable, Value>,
+    /// The unit literals responsible for the initial assignment (in the order
+    /// they were encountered); their consequences still need to be propagated
+    units : Vec<Literal>,
     /// Variables for which we have detected a conflict during preprocessing
-    conflict_vars : Vec<Variable>
+    conflict_vars : Vec<Variable>,
+    /// True if the formula contains the empty clause (and is thus trivially
+    /// unsatisfiable)
+    has_empty_clause : bool
+}
+
+impl PreprocessResult {
+    fn new(next_var : &Variable) -> Self {
+        let mut pr = PreprocessResult {
+            initial_assignment : TaggedVec::new(),
+            units : Vec::new(),
+            conflict_vars : Vec::new(),
+            has_empty_clause : false
+        };
+
+        pr.initial_assignment.ensure_index(next_var, Value::UNASSIGNED);
+        pr
+    }
+
+    /// True if preprocessing alone proved the formula unsatisfiable
+    fn is_unsat(&self) -> bool {
+        self.has_empty_clause || !self.conflict_vars.is_empty()
+    }
+
+    /// A clause falsified by the initial assignment, if preprocessing proved
+    /// the formula unsatisfiable: the empty clause, or the unit clause that
+    /// contradicted an earlier unit
+    fn conflict_clause(&self) -> Option<Vec<Literal>> {
+        if self.has_empty_clause {
+            return Some(Vec::new());
+        }
+
+        self.conflict_vars.first().map(|v| {
+            let polarity = if self.initial_assignment[*v] == Value::LIFTED_TRUE { Polarity::Negative } else { Polarity::Positive };
+            vec![Literal::with_polarity(*v, polarity)]
+        })
+    }
+
+    /// Record the literal of a unit clause in the initial assignment
+    ///
+    /// Returns false if the literal conflicts with an earlier unit (which is
+    /// also recorded in `conflict_vars`)
+    fn record_unit(&mut self, single_lit : Literal) -> bool {
+        let current_assign = self.initial_assignment[single_lit.variable()];
+        if current_assign == Value::UNASSIGNED {
+            self.initial_assignment[single_lit.variable()] = single_lit.satisfy();
+            self.units.push(single_lit);
+            true
+        } else if single_lit.satisfy() == current_assign {
+            true
+        } else {
+            self.conflict_vars.push(single_lit.variable());
+            false
+        }
+    }
 }
 
 /// Preprocess the formula to both simplify it and identify any initial conflicts
 ///
-/// 1. Remove empty clauses
+/// 1. Remove repeated literals from each clause, and remove tautologies
+///    (clauses containing x \/ !x)
 ///
-/// 2. Remove singleton clauses and record them in the `PreprocessResult` as
-///    part of an initial assignment (to be folded into the env)
+/// 2. Remove empty clauses (noting that the formula is unsatisfiable)
 ///
-/// After this, the clause database contains clauses with at least two literals
+/// 3. Remove singleton clauses and record them in the `PreprocessResult` as
+///    part of an initial assignment (to be folded into the env)
 ///
-/// FIXME: Look for intra-clause inconsistencies (i.e., x /\ !x)
+/// After this, the clause database contains clauses with at least two distinct
+/// literals, as watching requires
 fn preprocess(clauses : &mut Vec<Clause>, next_var : &Variable) -> PreprocessResult {
-    let mut pr = PreprocessResult {
-        initial_assignment : TaggedVec::new(),
-        conflict_vars : Vec::new()
-    };
-
-    pr.initial_assignment.ensure_index(next_var, Value::UNASSIGNED);
+    let mut pr = PreprocessResult::new(next_var);
 
-    clauses.retain(|cl| {
-        if cl.lit_count() == 0 {
-            return false;
+    clauses.retain_mut(|cl| {
+        cl.remove_duplicate_literals();
+        if cl.is_tautology() {
+            false
+        } else if cl.lit_count() == 0 {
+            pr.has_empty_clause = true;
+            false
         } else if cl.lit_count() == 1 {
-            let single_lit = cl[0];
-            let current_assign = pr.initial_assignment[single_lit.variable()];
-            if current_assign == Value::UNASSIGNED {
-                // We can assign this variable and discard the clause
-                return false;
-            } else if single_lit.satisfy() == current_assign {
-                // We can eliminate the clause because we already have this assignment
-                return false;
-            } else {
-                // We have found a conflict... no need to remove it, though we could
-
-                pr.conflict_vars.push(single_lit.variable());
-                return true;
-            }
+            // If the unit is consistent with the assignment so far, we can
+            // assign the variable and discard the clause.  Otherwise, we have
+            // found a conflict (recorded in `conflict_vars`); the clause is
+            // removed anyway, as the watchlist cannot hold unit clauses
+            pr.record_unit(cl[0]);
+            false
         } else {
-            return true;
+            true
         }
     });
 
     pr
 }
 
-#[derive(Eq, PartialEq, Clone, Copy)]
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
 enum PropagateResult {
-    Conflict,
+    /// The given clause is falsified by the current assignment
+    Conflict(ClauseId),
     NoConflict
 }
 
@@ -126,19 +777,28 @@ enum PropagateResult {
 // enqueue multiple times
 //
 // Note that this can detect a conflict if an earlier propagation at this
-// decision level enqueued a conflict.
-fn enqueue(env : &mut SolverState, lit : Literal) -> PropagateResult {
+// decision level enqueued a conflict; only implied literals (which have a
+// reason) can conflict, since decisions are always unassigned.
+fn enqueue(env : &mut SolverState, lit : Literal, reason : Option<ClauseId>) -> PropagateResult {
     let val = env.value_of(lit);
-    if val == Value::UNASSIGNED {
+    // Note that the value of a negative literal of an unassigned variable is
+    // not exactly `Value::UNASSIGNED`
+    if val.is_unassigned() {
         // Assign immediately; note that we still enqueue because we have to
         // propagate units still
+        let v = lit.variable();
         env.decision_stack.push(lit);
-        env.assignment[lit.variable()] = lit.satisfy();
+        env.assignment.set(v, lit.satisfy());
+        env.levels[v] = env.decision_level();
+        env.reasons[v] = reason;
         env.propagation_queue.push_back(lit);
         return PropagateResult::NoConflict;
     } else {
         if val == Value::LIFTED_FALSE {
-            return PropagateResult::Conflict;
+            match reason {
+                Some(cid) => PropagateResult::Conflict(cid),
+                None => panic!("Decided literal {:?} is already false", lit)
+            }
         } else {
             // Already assigned, no need to re-enqueue
             return PropagateResult::NoConflict;
@@ -146,7 +806,7 @@ fn enqueue(env : &mut SolverState, lit : Literal) -> PropagateResult {
     }
 }
 
-fn propagate_clause(solver_state : &mut SolverState, cl : &mut Clause, lit : Literal) -> PropagateResult {
+fn propagate_clause(solver_state : &mut SolverState, mut cl : ClauseMut, lit : Literal, direction : WatchSearch) -> PropagateResult {
     // Propagating x means that x becomes satisfied. Thus, we only need to
     // update the watches in this clause if ¬x is watched (as it is now False)
     let false_lit = lit.negate();
@@ -163,13 +823,15 @@ fn propagate_clause(solver_state : &mut SolverState, cl : &mut Clause, lit : Lit
         return PropagateResult::NoConflict;
     }
 
-    // Try to find a new literal to watch
-    for lit_num in 2 .. cl.lit_count() {
-        if solver_state.value_of(cl[lit_num]) == Value::LIFTED_FALSE {
-            // Can't watch something that is already false
-            continue;
-        }
-
+    // Try to find a new literal to watch (which can't be one that is already
+    // false)
+    let watchable = |ix : &usize| solver_state.value_of(cl[*ix]) != Value::LIFTED_FALSE;
+    let mut candidates = 2 .. cl.lit_count();
+    let new_watch = match direction {
+        WatchSearch::Forward => candidates.find(watchable),
+        WatchSearch::Backward => candidates.rev().find(watchable)
+    };
+    if let Some(lit_num) = new_watch {
         let tmp_lit = cl[1];
         cl[1] = cl[lit_num];
         cl[lit_num] = tmp_lit;
@@ -182,18 +844,33 @@ fn propagate_clause(solver_state : &mut SolverState, cl : &mut Clause, lit : Lit
     // Restore the original watch (even though it isn't really useful) to
     // maintain the two-watched literal invariant
     solver_state.watchlist[lit].insert(cl.identifier());
-    enqueue(solver_state, cl[0])
+    solver_state.statistics.propagations += 1;
+    #[cfg(feature = "clause-heat")]
+    {
+        *cl.heat_mut() += 1;
+    }
+    enqueue(solver_state, cl[0], Some(cl.identifier()))
 }
 
+/// Propagate the consequences of the queued literals (and everything they
+/// imply) to a fixpoint, or until a clause is falsified
 fn propagate_units(env : &mut Env) -> PropagateResult {
+    let result = propagate_watches(env);
+    if env.config.verify_propagation {
+        verify_propagation(env, result);
+    }
+    result
+}
+
+fn propagate_watches(env : &mut Env) -> PropagateResult {
     while let Some(lit) = env.solver_state.propagation_queue.pop_front() {
-        let watchers = std::mem::replace(&mut env.solver_state.watchlist[lit], BTreeSet::new());
+        let watchers = ::core::mem::replace(&mut env.solver_state.watchlist[lit], BTreeSet::new());
         let mut watcher_iter = watchers.iter();
         while let Some(idx) = watcher_iter.next() {
-            let cl = &mut env.problem[*idx];
-            match propagate_clause(&mut env.solver_state, cl, lit) {
+            let cl = env.problem.clause_mut(*idx);
+            match propagate_clause(&mut env.solver_state, cl, lit, env.config.watch_search) {
                 PropagateResult::NoConflict => {},
-                PropagateResult::Conflict => {
+                PropagateResult::Conflict(cid) => {
                     // Restore all of the watches that we didn't modify before
                     // we hit a conflict
                     while let Some(idx) = watcher_iter.next() {
@@ -201,7 +878,7 @@ fn propagate_units(env : &mut Env) -> PropagateResult {
                     }
 
                     env.solver_state.propagation_queue.clear();
-                    return PropagateResult::Conflict;
+                    return PropagateResult::Conflict(cid);
                 }
             }
         }
@@ -210,20 +887,69 @@ fn propagate_units(env : &mut Env) -> PropagateResult {
     return PropagateResult::NoConflict;
 }
 
-/// Pick the next literal to set
+/// Check the result of propagation by scanning every clause (see
+/// `SolverConfig::verify_propagation`)
+///
+/// A reported conflict must be falsified.  Otherwise, naive propagation from
+/// the current assignment must not be able to derive anything: propagating to
+/// a fixpoint means that no clause is falsified or unit (i.e., unsatisfied
+/// with a single unassigned literal).
+fn verify_propagation(env : &Env, result : PropagateResult) {
+    let state = &env.solver_state;
+    if let PropagateResult::Conflict(cid) = result {
+        let lits = env.problem.clause(cid).literals();
+        assert!(lits.iter().all(|l| state.value_of(*l) == Value::LIFTED_FALSE),
+                "Propagation reported conflict on clause {:?} ({}), which is not falsified", cid, display_literals(lits));
+        return;
+    }
+
+    for cl in env.problem.iter() {
+        let lits = cl.literals();
+        if lits.iter().any(|l| state.value_of(*l) == Value::LIFTED_TRUE) {
+            continue;
+        }
+        let unassigned : Vec<Literal> = lits.iter().copied().filter(|l| state.value_of(*l).is_unassigned()).collect();
+        assert!(unassigned.len() != 1,
+                "Propagation missed {} implied by clause {:?} ({})", unassigned[0], cl.identifier(), display_literals(lits));
+        assert!(!unassigned.is_empty(),
+                "Propagation missed conflict on clause {:?} ({})", cl.identifier(), display_literals(lits));
+    }
+}
+
+/// Pick the next literal to decide
 ///
-/// This can be either an arbitrary choice or taken from a list of implied
-/// decisions (e.g., due to watched literals)
+/// This is a random variable (see `SolverConfig::random_decision_freq`) or the
+/// most active unassigned variable in the variable order.  Implied literals
+/// never compete with decisions: the search propagates to a fixpoint (emptying
+/// the propagation queue) before every decision, so this must only be called
+/// with an empty propagation queue.
 fn next_decision(env : &mut Env) -> Option<Literal> {
-    // FIXME: Find some way to persist the priority so that we could restore it
-    // if we re-add the variable to the decision queue
-    //
-    // Note: we can just use variable activity for this
+    debug_assert!(env.solver_state.propagation_queue.is_empty(),
+                  "Deciding with {} pending propagations", env.solver_state.propagation_queue.len());
+    let num_vars = env.next_var.as_index();
+    if env.config.random_decision_freq > 0.0
+        && num_vars > 0
+        && env.solver_state.rng.next_f64() < env.config.random_decision_freq
+    {
+        let v = Variable::from_index(env.solver_state.rng.below(num_vars));
+        if env.solver_state.assignment.value_of(v) == Value::UNASSIGNED {
+            return Some(decision_literal(&env.solver_state, v));
+        }
+    }
+
+    // Note that variables popped here are restored (with their activity as
+    // their priority) when they are unassigned during backtracking
     loop {
         match env.solver_state.variable_order.pop() {
             Some((v, _)) => {
-                if env.solver_state.assignment[v] == Value::UNASSIGNED {
-                    return Some(v.to_positive_literal());
+                env.solver_state.order_pops += 1;
+                if env.solver_state.assignment.value_of(v) == Value::UNASSIGNED {
+                    return Some(decision_literal(&env.solver_state, v));
+                }
+
+                env.solver_state.order_skips += 1;
+                if has_stale_order(&env.solver_state) {
+                    rebuild_variable_order(&mut env.solver_state);
                 }
             }
             None => {
@@ -233,152 +959,4309 @@ fn next_decision(env : &mut Env) -> Option<Literal> {
     }
 }
 
-/// Look at the last decision we made and undo it
+/// Find an unassigned variable that occurs in only one polarity among the
+/// problem clauses that are not yet satisfied, returning the literal that
+/// satisfies all of its occurrences
 ///
-/// This involves removing the assignment and undoing any relevant modifications
-/// made during unit propagation
-fn undo_last_decision(env : &mut Env) -> () {
-    print!("Backtracking\n");
-    match env.solver_state.decision_stack.pop() {
-        None => {}
-        Some(l) => {
-            env.solver_state.assignment[l.variable()] = Value::UNASSIGNED;
-            // FIXME: Choose a new priority (likely based on variable activity)
-            env.solver_state.variable_order.push(l.variable(), OrderedFloat(0.0));
+/// Variables that do not occur in any such clause are left to the variable
+/// order.
+fn find_pure_literal(env : &Env) -> Option<Literal> {
+    let state = &env.solver_state;
+    (0..env.next_var.as_index()).map(Variable::from_index).find_map(|v| {
+        if state.assignment.value_of(v) != Value::UNASSIGNED {
+            return None;
         }
-    }
+
+        let mut polarities = env.occurrences[v].iter()
+            .map(|cid| env.problem.clause(*cid))
+            .filter(|cl| !cl.is_learned() && !cl.literals().iter().any(|l| state.value_of(*l) == Value::LIFTED_TRUE))
+            .filter_map(|cl| cl.literals().iter().find(|l| l.variable() == v).copied());
+        let first = polarities.next()?;
+        if polarities.all(|l| l == first) { Some(first) } else { None }
+    })
 }
 
-/// Assign a trivial and not particularly useful priority to each variable
+/// True if every problem clause is satisfied by the current (partial)
+/// assignment
 ///
-/// The priority is based just on the order variables are encountered
-fn initial_variable_order(clauses : &Vec<Clause>) -> PriorityQueue<Variable, OrderedFloat<f32>> {
-    let mut priority = 0;
-    let mut q = PriorityQueue::new();
-    let mut seen = BTreeSet::new();
-    for c in clauses {
-        for idx in 0..c.lit_count() {
-            let v = c[idx].variable();
-            if seen.contains(&v) {
-                continue;
-            }
+/// Learned clauses are implied by the problem clauses, so they hold in every
+/// extension of such an assignment.
+fn problem_satisfied(env : &Env) -> bool {
+    let state = &env.solver_state;
+    env.problem.iter()
+        .filter(|cl| !cl.is_learned())
+        .all(|cl| cl.literals().iter().any(|l| state.value_of(*l) == Value::LIFTED_TRUE))
+}
 
-            q.push(v, OrderedFloat(priority as f32));
-            priority += 1;
-            seen.insert(v);
-        }
-    }
+/// The literal to assert when deciding the given variable
+fn decision_literal(state : &SolverState, v : Variable) -> Literal {
+    let polarity = if state.saved_phase[v] == Value::LIFTED_FALSE { Polarity::Negative } else { Polarity::Positive };
+    Literal::with_polarity(v, polarity)
+}
 
-    q
+/// Rebuild the variable order only after at least this many skipped pops...
+const MIN_STALE_SKIPS : usize = 64;
+
+/// ...and only if more than this fraction of pops since the last rebuild were
+/// skipped
+const MAX_STALE_RATIO : f64 = 0.5;
+
+/// True if `next_decision` is spending most of its time skipping over variables
+/// that have already been assigned (typically by unit propagation)
+fn has_stale_order(state : &SolverState) -> bool {
+    state.order_skips >= MIN_STALE_SKIPS
+        && (state.order_skips as f64) > MAX_STALE_RATIO * (state.order_pops as f64)
 }
 
-/// Fill in the watchlist index; this must come after preprocessing, as we
-/// require that all clauses have at least two literals
+/// Drop every assigned variable from the variable order in a single pass
 ///
-/// FIXME: Split preprocessing into its own module and capture that invariant in
-/// a newtype
+/// The dropped variables are re-inserted when they are unassigned during
+/// backtracking, so this does not lose any candidates.
+fn rebuild_variable_order(state : &mut SolverState) {
+    let assignment = &state.assignment;
+    let old_order = ::core::mem::take(&mut state.variable_order);
+    state.variable_order = old_order.into_iter()
+        .filter(|(v, _)| assignment.value_of(*v) == Value::UNASSIGNED)
+        .collect();
+    state.order_pops = 0;
+    state.order_skips = 0;
+    state.statistics.order_rebuilds += 1;
+}
+
+/// Open a new decision level and assign `lit` at that level
+fn decide(env : &mut Env, lit : Literal, flipped : bool) {
+    let start = env.solver_state.decision_stack.len();
+    env.solver_state.decision_levels.push(DecisionLevel { start, flipped });
+    // The literal is always unassigned here, so this cannot conflict
+    let res = enqueue(&mut env.solver_state, lit, None);
+    debug_assert!(res == PropagateResult::NoConflict);
+}
+
+/// Undo every assignment made above the given decision level
 ///
-/// The convention is that the first two literals of each clause are watched, so
-/// build the reverse index based on the current literal ordering.
-fn initialize_watchlist(next_var : &Variable,
-                        clauses : &TaggedVec<ClauseId, Clause>,
-                        watch_index : &mut TaggedVec<Literal, BTreeSet<ClauseId>>)
-{
-    // First initialize empty watchlists for each literal, then fill in the
-    // active ones.
-    let max_lit = std::cmp::max(next_var.to_positive_literal(), next_var.to_negative_literal());
-    watch_index.ensure_index(&max_lit, BTreeSet::new());
-    let mut clause_iter = clauses.iter();
-    while let Some(cl) = clause_iter.next() {
-        let cid = cl.identifier();
-        watch_index[cl[0]].insert(cid);
-        watch_index[cl[1]].insert(cid);
+/// This involves removing the assignments and returning the variables to the
+/// variable order so that they can be decided again
+fn cancel_until(env : &mut Env, level : usize) {
+    if env.solver_state.decision_level() <= level {
+        return;
     }
+
+    let start = env.solver_state.decision_levels[level].start;
+    undo_trail_to(&mut env.solver_state, start);
+    env.solver_state.decision_levels.truncate(level);
+    compact_stacks(&mut env.solver_state);
 }
 
-/// Put our clauses into a `TaggedVec` with safer types
+/// Unassign the most recent assignments until the decision stack has the given
+/// length, returning the variables to the variable order
 ///
-/// This allocates all of the `ClauseId`s, ensuring that each clause's
-/// `ClauseId` matches its index in the `TaggedVec`.
-fn intern_clauses(clauses : Vec<Clause>) -> TaggedVec<ClauseId, Clause> {
-    // Ensure that the index of each clause matches its ClauseId (so that we can
-    // maintain the watchlist index)
-    let mut numbered_clauses = TaggedVec::new();
-    let mut clause_num = 0;
-    let mut clause_iter = clauses.into_iter();
-    while let Some(mut cl) = clause_iter.next() {
-        let clause_id = ClauseId(clause_num);
-        clause_num += 1;
-        *cl.identifier_mut() = clause_id;
-        numbered_clauses.push(cl);
+/// This does not touch the decision levels or the propagation queue.
+fn undo_trail_to(state : &mut SolverState, len : usize) {
+    while state.decision_stack.len() > len {
+        if let Some(l) = state.decision_stack.pop() {
+            state.assignment.set(l.variable(), Value::UNASSIGNED);
+            let v = l.variable();
+            state.levels[v] = NO_LEVEL;
+            state.variable_order.push(v, state.variable_activity[v]);
+        }
     }
-
-    numbered_clauses
 }
 
-pub fn solve(mut clauses : Vec<Clause>, next_var : Variable) -> core::Result {
-    // If there is an obvious syntactic conflict, return early
-    //
-    // Those can arise if there are conflicting unit clauses, so propagate units
-    let pp_result = preprocess(&mut clauses, &next_var);
-    if pp_result.conflict_vars.len() > 0 {
-        return core::Result::Unsat;
+/// Stacks with less capacity than this are never shrunk
+const MIN_COMPACT_CAPACITY : usize = 1024;
+
+/// Release excess capacity held by the decision stack and propagation queue
+///
+/// A deep search followed by a large backtrack would otherwise hold onto the
+/// peak allocation forever.  To avoid thrashing (repeatedly shrinking and
+/// regrowing as the search oscillates), we only shrink a stack once it is using
+/// less than a quarter of its capacity, and leave it room to double.
+fn compact_stacks(state : &mut SolverState) {
+    let cap = state.decision_stack.capacity();
+    let len = state.decision_stack.len();
+    if cap > MIN_COMPACT_CAPACITY && len < cap / 4 {
+        state.decision_stack.shrink_to(::core::cmp::max(len * 2, MIN_COMPACT_CAPACITY));
     }
 
-    // This is computed early just because we can't borrow it multiple times
-    // while constructing `Env`
-    let init_var_order = initial_variable_order(&clauses);
-    let numbered_clauses = intern_clauses(clauses);
+    let cap = state.propagation_queue.capacity();
+    let len = state.propagation_queue.len();
+    if cap > MIN_COMPACT_CAPACITY && len < cap / 4 {
+        state.propagation_queue.shrink_to(::core::cmp::max(len * 2, MIN_COMPACT_CAPACITY));
+    }
+}
 
-    // NOTE: This must come after preprocessing since we require all clauses to
-    // have at least two literals
-    let mut watch_index = TaggedVec::new();
-    initialize_watchlist(&next_var, &numbered_clauses, &mut watch_index);
+/// Chronologically backtrack after a conflict
+///
+/// Undo decisions until we find one whose negation has not been tried yet, then
+/// assert that negation in its place.  Returns false if every decision has
+/// already been flipped, which means that the formula is unsatisfiable (under
+/// the assumptions, which are never flipped).
+fn backtrack(env : &mut Env) -> bool {
+    loop {
+        let (start, flipped) = match env.solver_state.decision_levels.last() {
+            None => return false,
+            Some(dl) => (dl.start, dl.flipped)
+        };
 
-    let mut env = Env {
-        problem : numbered_clauses,
-        solver_state : SolverState {
-            decision_stack : Vec::new(),
-            assignment : pp_result.initial_assignment,
-            watchlist : watch_index,
-            variable_order : init_var_order,
-            propagation_queue : VecDeque::new(),
-            statistics : empty_statistics()
+        let level = env.solver_state.decision_level() - 1;
+        if flipped {
+            cancel_until(env, level);
+        } else {
+            let decision = env.solver_state.decision_stack[start];
+            cancel_until(env, level);
+            decide(env, decision.negate(), true);
+            return true;
         }
-    };
+    }
+}
 
+/// Make the next assumption that is not yet decided, if any
+///
+/// Each assumption gets its own decision level (empty if the assumption is
+/// already implied), so the assumptions occupy exactly the first levels.
+/// Assumptions are marked as flipped so that chronological backtracking never
+/// tries their negations.  Returns false if an assumption is already false.
+fn assume(env : &mut Env) -> Option<bool> {
+    let level = env.solver_state.decision_level();
+    let lit = *env.assumptions.get(level)?;
+    let val = env.solver_state.value_of(lit);
+    if val == Value::LIFTED_FALSE {
+        return Some(false);
+    }
 
-    loop {
-        match propagate_units(&mut env) {
-            PropagateResult::Conflict => {
-                if env.solver_state.decision_level() == 0 {
-                    return core::Result::Unsat;
-                }
+    trace_event(env, format_args!("assume {}", lit));
+    if val.is_unassigned() {
+        decide(env, lit, true);
+    } else {
+        let start = env.solver_state.decision_stack.len();
+        env.solver_state.decision_levels.push(DecisionLevel { start, flipped : true });
+    }
 
-                undo_last_decision(&mut env);
-            },
-            PropagateResult::NoConflict => {
+    Some(true)
+}
 
-            }
+/// Variable activities (and the increment) are rescaled before any would
+/// exceed this value (to keep them well within the range of an `f32`)
+const VARIABLE_ACTIVITY_LIMIT : f32 = 1e20;
+
+/// The factor by which variable activities decay after each conflict
+const VARIABLE_DECAY : f32 = 0.95;
+
+/// Scale every variable activity (and the increment) down, preserving their
+/// order
+fn rescale_variable_activity(state : &mut SolverState) {
+    for ix in 0..state.variable_activity.len() {
+        let var = Variable::from_index(ix);
+        state.variable_activity[var] = OrderedFloat(state.variable_activity[var].0 / VARIABLE_ACTIVITY_LIMIT);
+        if state.variable_order.get_priority(&var).is_some() {
+            state.variable_order.change_priority(&var, state.variable_activity[var]);
         }
     }
-    // Next, decide and propagate units until we have completed the assignment
-    // or exhausted our possible assignments
-    while let Some(next_lit) = next_decision(&mut env) {
-        print!("Deciding {:?}\n", next_lit);
-        match propagate_units(&mut env) {
-            PropagateResult::NoConflict => {
-                // No special action - decide an assignment for the next
-                // variable
-            }
-            PropagateResult::Conflict => {
-                undo_last_decision(&mut env);
-                let next_lit = next_lit.negate();
-                // env.decision_queue.push_back(next_lit);
-            }
-        }
+    state.variable_increment /= VARIABLE_ACTIVITY_LIMIT;
+}
+
+/// Bump the activity of a variable involved in a conflict (VSIDS)
+fn bump_variable_activity(state : &mut SolverState, v : Variable) {
+    bump_variable_activity_by(state, v, 1.0);
+}
+
+/// Bump the activity of a variable by the given multiple of the increment
+fn bump_variable_activity_by(state : &mut SolverState, v : Variable, factor : f32) {
+    // Rescale before adding so that the sum can never overflow
+    if state.variable_activity[v].0 > VARIABLE_ACTIVITY_LIMIT - factor * state.variable_increment {
+        rescale_variable_activity(state);
+    }
+
+    let OrderedFloat(activity) = state.variable_activity[v];
+    state.variable_activity[v] = OrderedFloat(activity + factor * state.variable_increment);
+    debug_assert!(state.variable_activity[v].0.is_finite());
+    if state.variable_order.get_priority(&v).is_some() {
+        state.variable_order.change_priority(&v, state.variable_activity[v]);
+    }
+}
+
+/// Decay all variable activities
+///
+/// Rather than touching every variable, this grows the increment used for
+/// future bumps
+fn decay_variable_activity(state : &mut SolverState) {
+    // Conflicts need not bump anything that would trigger a rescale, so the
+    // increment has to be checked on its own
+    if state.variable_increment > VARIABLE_ACTIVITY_LIMIT * VARIABLE_DECAY {
+        rescale_variable_activity(state);
     }
 
+    state.variable_increment /= VARIABLE_DECAY;
+}
+
+/// Clause activities (and the increment) are rescaled before any would exceed
+/// this value
+const CLAUSE_ACTIVITY_LIMIT : f64 = 1e20;
+
+/// The factor by which clause activities decay after each conflict
+const CLAUSE_DECAY : f64 = 0.999;
 
-    return core::Result::Sat;
+/// Scale the activity of every learned clause (and the increment) down,
+/// preserving their order
+fn rescale_clause_activity(env : &mut Env) {
+    for mut cl in env.problem.iter_mut().filter(|cl| cl.is_learned()) {
+        cl.scale_activity(1.0 / CLAUSE_ACTIVITY_LIMIT);
+    }
+    env.solver_state.clause_increment /= CLAUSE_ACTIVITY_LIMIT;
+}
+
+/// Bump the activity of a clause involved in a conflict
+///
+/// Only learned clauses have activities, since only they can be deleted
+fn bump_clause_activity(env : &mut Env, cid : ClauseId) {
+    if !env.problem.clause(cid).is_learned() {
+        return;
+    }
+
+    // Rescale before adding so that the sum can never overflow
+    if env.problem.clause(cid).activity() > CLAUSE_ACTIVITY_LIMIT - env.solver_state.clause_increment {
+        rescale_clause_activity(env);
+    }
+
+    let increment = env.solver_state.clause_increment;
+    env.problem.clause_mut(cid).bump_activity(increment);
+}
+
+/// Decay all clause activities (by growing the increment)
+fn decay_clause_activity(env : &mut Env) {
+    if env.solver_state.clause_increment > CLAUSE_ACTIVITY_LIMIT * CLAUSE_DECAY {
+        rescale_clause_activity(env);
+    }
+
+    env.solver_state.clause_increment /= CLAUSE_DECAY;
+}
+
+/// Learn a clause from a conflict by resolving backwards from the conflict
+/// clause until reaching the first unique implication point (UIP)
+///
+/// The first literal of the learned clause is the negation of the UIP, which
+/// becomes true after backjumping.  The second literal (if any) is the one with
+/// the highest decision level among the rest, which is the level to backjump
+/// to (also returned).
+///
+/// Every clause resolved on (the conflict clause and the reason of each literal
+/// resolved away) has its activity bumped, so that learned clauses that keep
+/// taking part in conflicts survive reduction.
+///
+/// This requires that the conflict clause has at least one literal at the
+/// current decision level.
+fn analyze_conflict(env : &mut Env, conflict : ClauseId) -> (Vec<Literal>, usize) {
+    let current_level = env.solver_state.decision_level();
+    let mut rest = Vec::new();
+    let mut path_count = 0;
+    let mut resolving = conflict;
+    let mut index = env.solver_state.decision_stack.len();
+    let mut resolved = Vec::new();
+    let uip = loop {
+        if env.config.record_derivations {
+            resolved.push(resolving);
+        }
+        bump_clause_activity(env, resolving);
+        let problem = &env.problem;
+        let state = &mut env.solver_state;
+        let cl = problem.clause(resolving);
+        for lit_num in 0..cl.lit_count() {
+            let q = cl[lit_num];
+            let v = q.variable();
+            if state.seen[v] || state.levels[v] == 0 || state.value_of(q) != Value::LIFTED_FALSE {
+                // Note that the only true literal in a reason clause is the
+                // literal it implied, which has already been resolved on
+                continue;
+            }
+
+            state.seen[v] = true;
+            bump_variable_activity(state, v);
+            if state.levels[v] >= current_level {
+                path_count += 1;
+            } else {
+                rest.push(q);
+            }
+        }
+
+        // Resolve on the most recently assigned literal that we have seen
+        loop {
+            index -= 1;
+            if state.seen[state.decision_stack[index].variable()] {
+                break;
+            }
+        }
+
+        let p = state.decision_stack[index];
+        state.seen[p.variable()] = false;
+        path_count -= 1;
+        let reason = state.reasons[p.variable()];
+        if path_count == 0 && (env.config.learning_scheme == LearningScheme::FirstUip || reason.is_none()) {
+            break p;
+        }
+
+        // Past the first UIP, a literal implied at the conflict level has a
+        // reason with another literal from that level (which keeps the path
+        // count positive)
+        resolving = reason.expect("Only the first literal at a decision level has no reason");
+    };
+
+    let state = &mut env.solver_state;
+    let mut backjump_level = 0;
+    if !rest.is_empty() {
+        let mut max_ix = 0;
+        for (ix, lit) in rest.iter().enumerate() {
+            if state.levels[lit.variable()] > state.levels[rest[max_ix].variable()] {
+                max_ix = ix;
+            }
+        }
+        rest.swap(0, max_ix);
+        backjump_level = state.levels[rest[0].variable()];
+    }
+
+    for lit in rest.iter() {
+        state.seen[lit.variable()] = false;
+    }
+
+    let mut learned = Vec::with_capacity(rest.len() + 1);
+    learned.push(uip.negate());
+    learned.extend(rest);
+    if env.config.record_derivations {
+        record_derivation(env, &resolved, &learned);
+    }
+    (learned, backjump_level)
+}
+
+/// Save the clauses resolved (the conflict clause first) to learn a clause
+fn record_derivation(env : &mut Env, resolved : &[ClauseId], learned : &[Literal]) {
+    let state = &env.solver_state;
+    let mut clauses = resolved.iter().map(|cid| env.problem.clause(*cid).literals().to_vec());
+    let conflict = clauses.next().expect("Analysis starts from the conflict clause");
+    let reasons : Vec<Vec<Literal>> = clauses.collect();
+    let mut fixed = Vec::new();
+    for lit in conflict.iter().chain(reasons.iter().flatten()) {
+        if state.levels[lit.variable()] == 0 && state.value_of(*lit) == Value::LIFTED_FALSE && !fixed.contains(lit) {
+            fixed.push(*lit);
+        }
+    }
+
+    let mut key = learned.to_vec();
+    key.sort();
+    env.derivations.insert(key, Derivation { conflict, reasons, fixed });
+}
+
+/// The fewest learned clauses the solver keeps before deleting any
+const MIN_LEARNED_LIMIT : f64 = 1000.0;
+
+/// The initial number of learned clauses allowed, relative to the number of
+/// problem clauses
+const LEARNED_LIMIT_FACTOR : f64 = 1.0 / 3.0;
+
+/// The factor by which the learned clause limit grows after each reduction
+const LEARNED_LIMIT_GROWTH : f64 = 1.1;
+
+/// True if the clause is the reason for a current assignment (and thus cannot
+/// be deleted)
+///
+/// Conflict analysis resolves on the reasons of assigned literals, so deleting
+/// a locked clause would leave its literal without a reason.  Every deletion
+/// pass must skip locked clauses (`compact_clauses` checks this).
+fn is_locked(env : &Env, cid : ClauseId) -> bool {
+    let implied = env.problem.clause(cid)[0];
+    env.solver_state.value_of(implied) == Value::LIFTED_TRUE
+        && env.solver_state.reasons[implied.variable()] == Some(cid)
+}
+
+/// Simplify the clause database with the level 0 assignments, which hold
+/// permanently: clauses satisfied at level 0 are deleted, and false literals
+/// are removed from the others
+///
+/// This must only be called at level 0 after propagation has finished without
+/// a conflict.  Then, a clause that is not satisfied cannot be watching a false
+/// literal, so removing false literals keeps its watches (and leaves it with at
+/// least two literals).  Pinned clauses are left alone.
+///
+/// If propagation did miss something, removing the false literals would leave
+/// a clause that cannot be watched, so such clauses are handled here instead:
+/// a clause left with a single literal is deleted and its literal asserted (to
+/// be propagated by the caller), while a clause left with none proves the
+/// formula unsatisfiable (recorded as the final conflict).  Returns false in
+/// that case.
+fn simplify_at_root(env : &mut Env) -> bool {
+    let state = &mut env.solver_state;
+    debug_assert!(state.decision_level() == 0 && state.propagation_queue.is_empty());
+    state.simplified_assignments = state.decision_stack.len();
+
+    let mut deleted = vec![false; env.problem.len()];
+    let mut num_deleted = 0;
+    let mut units = Vec::new();
+    let mut falsified = None;
+    for (ix, mut cl) in env.problem.iter_mut().enumerate() {
+        if cl.is_pinned() {
+            continue;
+        }
+        if cl.literals().iter().any(|l| state.value_of(*l) == Value::LIFTED_TRUE) {
+            deleted[ix] = true;
+            num_deleted += 1;
+            continue;
+        }
+
+        let open : Vec<Literal> = cl.literals().iter().copied().filter(|l| state.value_of(*l) != Value::LIFTED_FALSE).collect();
+        match open[..] {
+            [] => {
+                falsified = Some(cl.literals().to_vec());
+                break;
+            },
+            [unit] => {
+                deleted[ix] = true;
+                units.push((unit, cl.literals().to_vec()));
+            },
+            _ => cl.retain_literals(|l| state.value_of(l) != Value::LIFTED_FALSE)
+        }
+    }
+
+    // Level 0 assignments are never analyzed, so their reasons (which are
+    // satisfied, and thus deleted) are not needed
+    for lit in state.decision_stack.iter() {
+        state.reasons[lit.variable()] = None;
+    }
+    trace_event(env, format_args!("simplify: delete {} satisfied clauses", num_deleted));
+    compact_clauses(env, &deleted);
+
+    for (unit, lits) in units {
+        if falsified.is_none() && env.solver_state.value_of(unit) == Value::LIFTED_FALSE {
+            falsified = Some(lits);
+        } else if env.solver_state.value_of(unit).is_unassigned() {
+            trace_event(env, format_args!("simplify: assert unit {}", unit));
+            enqueue(&mut env.solver_state, unit, None);
+        }
+    }
+    match falsified {
+        None => true,
+        Some(lits) => {
+            trace_event(env, format_args!("simplify: clause {} is false at level 0", display_literals(&lits)));
+            env.final_conflict = Some(lits);
+            false
+        }
+    }
+}
+
+/// Add a clause with the given flags to the clause database, watching its
+/// first two literals
+fn insert_clause(env : &mut Env, lits : Vec<Literal>, flags : u16) -> ClauseId {
+    let cid = env.problem.push(ClauseHeader::new(ClauseId(0), lits.len(), flags), &lits);
+    debug_assert!(lits[0] != lits[1], "Clause {:?} watches {:?} twice", cid, lits[0]);
+    env.solver_state.watchlist[lits[0].negate()].insert(cid);
+    env.solver_state.watchlist[lits[1].negate()].insert(cid);
+    add_occurrences(&mut env.occurrences, env.problem.clause(cid));
+    cid
+}
+
+/// Delete (about) the given fraction of the learned clauses, starting from the
+/// least active
+///
+/// Pinned clauses, binary clauses, and clauses that are the reason for a
+/// current assignment are never deleted.  The remaining clauses are
+/// renumbered, so this rebuilds the indexes that refer to clauses by
+/// `ClauseId`.
+fn reduce_learned_clauses(env : &mut Env, fraction : f64) {
+    let mut candidates : Vec<ClauseId> = (0..env.problem.len())
+        .map(|ix| ClauseId(ix as i64))
+        .filter(|cid| {
+            let cl = env.problem.clause(*cid);
+            cl.is_learned() && !cl.is_pinned() && cl.lit_count() > 2 && !is_locked(env, *cid)
+        })
+        .collect();
+    candidates.sort_by(|c1, c2| env.problem.clause(*c1).activity().total_cmp(&env.problem.clause(*c2).activity()));
+    candidates.truncate((candidates.len() as f64 * fraction) as usize);
+    if candidates.is_empty() {
+        return;
+    }
+
+    trace_event(env, format_args!("delete {} learned clauses", candidates.len()));
+    let mut deleted = vec![false; env.problem.len()];
+    for cid in candidates.iter() {
+        deleted[cid.as_index()] = true;
+    }
+
+    compact_clauses(env, &deleted);
+}
+
+/// Remove the clauses marked in `deleted` (indexed by `ClauseId`) from the
+/// clause database, relocating the survivors into a dense layout
+///
+/// Surviving clauses keep their relative order, but are renumbered; this
+/// rebuilds every index that refers to clauses by `ClauseId` (the reasons, the
+/// watchlists, and the occurrence lists) in a single pass over each.  Clauses
+/// that are the reason for a current assignment must not be deleted.
+fn compact_clauses(env : &mut Env, deleted : &[bool]) {
+    debug_assert!((0..env.problem.len()).all(|ix| !deleted[ix] || !is_locked(env, ClauseId(ix as i64))),
+                  "Deleting the reason for a current assignment");
+    env.num_problem_clauses -= env.problem.iter().zip(deleted).filter(|(cl, d)| **d && !cl.is_learned()).count();
+    let renumbered = env.problem.compact(deleted);
+    env.compactions += 1;
+
+    let state = &mut env.solver_state;
+    for ix in 0..state.reasons.len() {
+        // Reasons are only cleared lazily, so those of unassigned variables
+        // can refer to deleted clauses
+        let v = Variable::from_index(ix);
+        state.reasons[v] = if state.assignment.value_of(v).is_unassigned() {
+            None
+        } else {
+            state.reasons[v].map(|cid| renumbered[cid.as_index()].expect("Reasons are never deleted"))
+        };
+    }
+
+    rebuild_watchlists(env);
+    env.occurrences = initialize_occurrences(&env.next_var, &env.problem);
+}
+
+/// Rebuild every watchlist from the clause database
+///
+/// The watched literals of each clause are its first two literals, so the
+/// watchlists can be rebuilt from the clauses alone.
+fn rebuild_watchlists(env : &mut Env) {
+    let watchlist = &mut env.solver_state.watchlist;
+    for ix in 0..watchlist.len() {
+        watchlist[Literal::from_index(ix)].clear();
+    }
+    for cl in env.problem.iter() {
+        watchlist[cl[0].negate()].insert(cl.identifier());
+        watchlist[cl[1].negate()].insert(cl.identifier());
+    }
+}
+
+/// Add a clause to the clause database (as a learned clause), watching its
+/// first two literals
+fn add_learned_clause(env : &mut Env, lits : Vec<Literal>) -> ClauseId {
+    let cid = insert_clause(env, lits, LEARNED_MASK);
+    bump_clause_activity(env, cid);
+    env.solver_state.statistics.learned_clauses += 1;
+    cid
+}
+
+/// Add an asserting learned clause after backjumping, asserting its first
+/// literal
+///
+/// Unit clauses are not stored; their literal is asserted at level 0
+fn assert_learned_clause(env : &mut Env, learned : Vec<Literal>) {
+    let uip = learned[0];
+    let reason = if learned.len() == 1 {
+        None
+    } else {
+        Some(add_learned_clause(env, learned))
+    };
+    let res = enqueue(&mut env.solver_state, uip, reason);
+    debug_assert!(res == PropagateResult::NoConflict);
+}
+
+/// Add a clause (which need not be falsified) during search
+///
+/// The literals are reordered so that the watched literals are the best
+/// candidates: true or unassigned literals first, then false literals from the
+/// highest decision level.  If the clause is unit under the current
+/// assignment, its remaining literal is asserted; if it is falsified, this
+/// reports a conflict.
+fn attach_clause(env : &mut Env, mut lits : Vec<Literal>) -> PropagateResult {
+    let state = &env.solver_state;
+    sort_for_watching(state, &mut lits);
+
+    let first = lits[0];
+    let second_false = state.value_of(lits[1]) == Value::LIFTED_FALSE;
+    let cid = add_learned_clause(env, lits);
+    let first_val = env.solver_state.value_of(first);
+    if first_val == Value::LIFTED_FALSE {
+        PropagateResult::Conflict(cid)
+    } else if second_false && first_val.is_unassigned() {
+        enqueue(&mut env.solver_state, first, Some(cid))
+    } else {
+        PropagateResult::NoConflict
+    }
+}
+
+/// Recover from a conflict by learning a clause and backjumping
+///
+/// Returns false if the conflict proves that the formula is unsatisfiable
+fn resolve_conflict(env : &mut Env, mut conflict : ClauseId) -> bool {
+    loop {
+        env.solver_state.statistics.conflicts += 1;
+        env.solver_state.conflicts_since_restart += 1;
+        sample_trail_size(&mut env.solver_state);
+        let level = env.solver_state.decision_level();
+        trace_event(env, format_args!("conflict at level {}", level));
+
+        // A conflict on a clause added during search might not involve the
+        // current decision level; analysis requires that it does
+        let cl = env.problem.clause(conflict);
+        let conflict_level = (0..cl.lit_count())
+            .map(|ix| env.solver_state.levels[cl[ix].variable()])
+            .max()
+            .unwrap_or(0);
+        if conflict_level == 0 {
+            env.final_conflict = Some(cl.literals().to_vec());
+            return false;
+        }
+        cancel_until(env, conflict_level);
+
+        let (learned, backjump_level) = analyze_conflict(env, conflict);
+        if env.config.recency_bias > 0.0 {
+            for lit in learned.iter() {
+                bump_variable_activity_by(&mut env.solver_state, lit.variable(), env.config.recency_bias);
+            }
+        }
+        decay_variable_activity(&mut env.solver_state);
+        decay_clause_activity(env);
+        if env.config.max_learned_length.is_some_and(|max_len| learned.len() > max_len) {
+            trace_event(env, format_args!("discard learned clause of length {}", learned.len()));
+            return backtrack(env);
+        }
+
+        let action = match env.on_learn.as_mut() {
+            None => LearnAction::Keep,
+            Some(on_learn) => on_learn(&learned)
+        };
+
+        match action {
+            LearnAction::Keep => {
+                env.solver_state.learned_since_restart += 1;
+                trace_event(env, format_args!("learn {}", display_literals(&learned)));
+                env.add_to_proof(&learned);
+                cancel_until(env, backjump_level);
+                assert_learned_clause(env, learned);
+                return true;
+            },
+            LearnAction::Drop => {
+                return backtrack(env);
+            },
+            LearnAction::Replace(lits) => {
+                env.solver_state.learned_since_restart += 1;
+                env.add_to_proof(&lits);
+                if lits.is_empty() {
+                    return false;
+                } else if lits.len() == 1 {
+                    cancel_until(env, 0);
+                    let val = env.solver_state.value_of(lits[0]);
+                    if val == Value::LIFTED_FALSE {
+                        return false;
+                    } else if val.is_unassigned() {
+                        enqueue(&mut env.solver_state, lits[0], None);
+                    }
+                    return true;
+                }
+
+                if !backtrack(env) {
+                    return false;
+                }
+
+                match attach_clause(env, lits) {
+                    PropagateResult::NoConflict => return true,
+                    PropagateResult::Conflict(cid) => conflict = cid
+                }
+            }
+        }
+    }
+}
+
+/// A priority queue of variables, keyed by their activity
+type VariableOrder = PriorityQueue<Variable, OrderedFloat<f32>, BuildHasherDefault<VariableHasher>>;
+
+/// The hasher of the `VariableOrder`, which (unlike the default hasher of
+/// `std`) needs no source of randomness
+///
+/// Variables are small, dense integers, so this only spreads their bits out
+/// (with the multiplicative step of FxHash).
+#[derive(Default)]
+struct VariableHasher(u64);
+
+impl Hasher for VariableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes : &[u8]) {
+        for b in bytes {
+            self.write_u64(*b as u64);
+        }
+    }
+
+    fn write_u32(&mut self, i : u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i : u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+/// Assign a trivial and not particularly useful priority to each variable
+///
+/// The priority is based just on the order variables are encountered
+fn initial_variable_order(clauses : &ClauseArena) -> VariableOrder {
+    let mut priority = 0;
+    let mut q = VariableOrder::default();
+    let mut seen = BTreeSet::new();
+    for c in clauses.iter() {
+        for idx in 0..c.lit_count() {
+            let v = c[idx].variable();
+            if seen.contains(&v) {
+                continue;
+            }
+
+            q.push(v, OrderedFloat(priority as f32));
+            priority += 1;
+            seen.insert(v);
+        }
+    }
+
+    q
+}
+
+/// Prioritize each variable by the number of clauses it occurs in
+fn occurrence_variable_order(clauses : &ClauseArena) -> VariableOrder {
+    let mut q = VariableOrder::default();
+    for c in clauses.iter() {
+        for idx in 0..c.lit_count() {
+            let v = c[idx].variable();
+            let count = q.get_priority(&v).map_or(0.0, |p : &OrderedFloat<f32>| p.0);
+            q.push(v, OrderedFloat(count + 1.0));
+        }
+    }
+
+    q
+}
+
+/// Compute the Jeroslow-Wang score of each literal: the sum of `2^-|C|` over
+/// the clauses `C` containing it
+fn jeroslow_wang_scores(clauses : &ClauseArena, next_var : &Variable) -> TaggedVec<Literal, f64> {
+    let max_lit = Literal::from_index(Variable::max_literal_index(next_var));
+    let mut scores = TaggedVec::new();
+    scores.ensure_index(&max_lit, 0.0);
+    for c in clauses.iter() {
+        let weight = 2f64.powi(-(c.lit_count() as i32));
+        for idx in 0..c.lit_count() {
+            scores[c[idx]] += weight;
+        }
+    }
+
+    scores
+}
+
+/// Prioritize each variable by the combined Jeroslow-Wang score of its two
+/// literals, setting its phase to the polarity of the higher-scoring literal
+fn jeroslow_wang_variable_order(clauses : &ClauseArena,
+                                next_var : &Variable,
+                                saved_phase : &mut TaggedVec<Variable, Value>) -> VariableOrder
+{
+    let scores = jeroslow_wang_scores(clauses, next_var);
+    let mut q = VariableOrder::default();
+    for c in clauses.iter() {
+        for idx in 0..c.lit_count() {
+            let v = c[idx].variable();
+            let pos = scores[v.to_positive_literal()];
+            let neg = scores[v.to_negative_literal()];
+            q.push(v, OrderedFloat((pos + neg) as f32));
+            let polarity = if pos >= neg { Polarity::Positive } else { Polarity::Negative };
+            saved_phase[v] = Literal::with_polarity(v, polarity).satisfy();
+        }
+    }
+
+    q
+}
+
+/// Record the clauses that each variable occurs in
+fn initialize_occurrences(next_var : &Variable,
+                          clauses : &ClauseArena) -> TaggedVec<Variable, Vec<ClauseId>>
+{
+    let mut occurrences = TaggedVec::new();
+    occurrences.ensure_index(next_var, Vec::new());
+    for cl in clauses.iter() {
+        add_occurrences(&mut occurrences, cl);
+    }
+
+    occurrences
+}
+
+fn add_occurrences(occurrences : &mut TaggedVec<Variable, Vec<ClauseId>>, cl : ClauseRef) {
+    for lit in cl.literals() {
+        // A variable can appear more than once in a clause (e.g., in a
+        // tautology), but each clause is only recorded once
+        let occs = &mut occurrences[lit.variable()];
+        if occs.last() != Some(&cl.identifier()) {
+            occs.push(cl.identifier());
+        }
+    }
+}
+
+/// Fill in the watchlist index; this must come after preprocessing, as we
+/// require that all clauses have at least two literals
+///
+/// FIXME: Split preprocessing into its own module and capture that invariant in
+/// a newtype
+///
+/// The convention is that the first two literals of each clause are watched, so
+/// build the reverse index based on the current literal ordering.  A clause
+/// watching literal `l` is stored in the watchlist of `¬l`, since that is the
+/// literal whose assertion requires us to revisit the clause.
+fn initialize_watchlist(next_var : &Variable,
+                        clauses : &ClauseArena,
+                        watch_index : &mut TaggedVec<Literal, BTreeSet<ClauseId>>)
+{
+    // First initialize empty watchlists for each literal, then fill in the
+    // active ones.
+    let max_lit = Literal::from_index(Variable::max_literal_index(next_var));
+    watch_index.ensure_index(&max_lit, BTreeSet::new());
+    let mut clause_iter = clauses.iter();
+    while let Some(cl) = clause_iter.next() {
+        debug_assert!(cl[0] != cl[1], "Clause {:?} watches {:?} twice", cl.identifier(), cl[0]);
+        let cid = cl.identifier();
+        watch_index[cl[0].negate()].insert(cid);
+        watch_index[cl[1].negate()].insert(cid);
+    }
+}
+
+/// Copy our clauses into a `ClauseArena`
+///
+/// This allocates all of the `ClauseId`s, ensuring that each clause's
+/// `ClauseId` matches its index in the arena (so that we can maintain the
+/// watchlist index).
+fn intern_clauses(clauses : Vec<Clause>) -> ClauseArena {
+    let num_literals = clauses.iter().map(|cl| cl.lit_count()).sum();
+    let mut arena = ClauseArena::with_capacity(clauses.len(), num_literals);
+    for cl in clauses.iter() {
+        arena.push_clause(cl);
+    }
+    arena
+}
+
+pub fn solve(clauses : Vec<Clause>, next_var : Variable) -> core::Result {
+    Solver::new(clauses, next_var, SolverConfig::default()).solve()
+}
+
+/// Decide a formula using only preprocessing and unit propagation at level 0
+///
+/// This is a cheap filter: it returns `Some(Unsat)` if propagating the unit
+/// clauses leads to a conflict, `Some(Sat)` if the implied literals satisfy
+/// every clause, and `None` if deciding the formula would require search.
+pub fn quick_check(clauses : Vec<Clause>, next_var : Variable) -> Option<core::Result> {
+    let mut solver = Solver::new(clauses, next_var, SolverConfig::default());
+    if solver.known_unsat {
+        return Some(core::Result::Unsat);
+    }
+
+    match propagate_units(&mut solver.env) {
+        PropagateResult::Conflict(_) => Some(core::Result::Unsat),
+        PropagateResult::NoConflict if problem_satisfied(&solver.env) => Some(core::Result::Sat),
+        PropagateResult::NoConflict => None
+    }
+}
+
+/// Solve a formula, reporting the statistics, model, and time taken along with
+/// the result
+#[cfg(feature = "std")]
+pub fn solve_full(clauses : Vec<Clause>, next_var : Variable, config : SolverConfig) -> SolveOutcome {
+    let start = std::time::Instant::now();
+    let solver = Solver::new(clauses, next_var, config);
+    finish_outcome(solver, start)
+}
+
+/// Solve a formula, returning only the result and the statistics of the run
+pub fn solve_with_stats(clauses : Vec<Clause>, next_var : Variable, config : SolverConfig) -> (core::Result, Stats) {
+    let mut solver = Solver::new(clauses, next_var, config);
+    let result = solver.solve();
+    (result, solver.stats())
+}
+
+/// Solve a formula starting from a hinted assignment (e.g., a model of a
+/// slightly different formula), reporting like `solve_full`
+///
+/// Each variable is first decided with its value in the hint.  The hint is
+/// only advice: it changes the order of the search, but never the answer.
+/// Variables beyond the end of the hint are decided with the default phase,
+/// and hinted variables beyond the formula are ignored.
+#[cfg(feature = "std")]
+pub fn solve_warm(clauses : Vec<Clause>, next_var : Variable, hint : &Model) -> SolveOutcome {
+    let start = std::time::Instant::now();
+    let mut solver = Solver::new(clauses, next_var, SolverConfig::default());
+    for lit in hint.literals().take(next_var.as_index()) {
+        solver.set_phase(lit.variable(), lit.polarity());
+    }
+    finish_outcome(solver, start)
+}
+
+/// Run the solver, reporting the time since `start`
+#[cfg(feature = "std")]
+fn finish_outcome(mut solver : Solver, start : std::time::Instant) -> SolveOutcome {
+    let result = solver.solve();
+    SolveOutcome {
+        result,
+        stats : solver.stats(),
+        model : solver.model(),
+        elapsed : start.elapsed()
+    }
+}
+
+/// Solve a formula whose clauses are produced lazily by an iterator
+///
+/// Unlike `solve`, this never materializes the full set of input clauses: each
+/// clause is preprocessed and interned as soon as it is produced, so unit
+/// clauses are never allocated at all.  If preprocessing finds a conflict, the
+/// rest of the iterator is not consumed.
+pub fn solve_from_iter<I>(clauses : I, next_var : Variable) -> core::Result
+where
+    I : Iterator<Item = Vec<Literal>>
+{
+    let mut pp_result = PreprocessResult::new(&next_var);
+    let mut numbered_clauses = ClauseArena::new();
+    for mut lits in clauses {
+        let mut seen = BTreeSet::new();
+        lits.retain(|l| seen.insert(*l));
+        if lits.iter().any(|l| seen.contains(&l.negate())) {
+            continue;
+        }
+
+        if lits.is_empty() {
+            return core::Result::Unsat;
+        } else if lits.len() == 1 {
+            if !pp_result.record_unit(lits[0]) {
+                return core::Result::Unsat;
+            }
+        } else {
+            numbered_clauses.push(ClauseHeader::new(ClauseId(0), lits.len(), 0), &lits);
+        }
+    }
+
+    let mut env = initialize_env(numbered_clauses, pp_result, next_var, SolverConfig::default());
+    search(&mut env)
+}
+
+/// Set up the solver state for preprocessed and interned clauses
+fn initialize_env(mut numbered_clauses : ClauseArena,
+                  pp_result : PreprocessResult,
+                  next_var : Variable,
+                  config : SolverConfig) -> Env
+{
+    if config.normalize_clauses {
+        for mut cl in numbered_clauses.iter_mut() {
+            cl.literals_mut().sort();
+        }
+    }
+
+    let mut saved_phase = TaggedVec::new();
+    saved_phase.ensure_index(&next_var, Literal::with_polarity(Variable::FIRST_VARIABLE, config.default_phase).satisfy());
+    let init_var_order = match config.order_heuristic {
+        OrderHeuristic::EncounterOrder => initial_variable_order(&numbered_clauses),
+        OrderHeuristic::OccurrenceCount => occurrence_variable_order(&numbered_clauses),
+        OrderHeuristic::JeroslowWang => jeroslow_wang_variable_order(&numbered_clauses, &next_var, &mut saved_phase)
+    };
+    let mut variable_activity = TaggedVec::new();
+    variable_activity.ensure_index(&next_var, OrderedFloat(0.0));
+    for (v, priority) in init_var_order.iter() {
+        variable_activity[*v] = *priority;
+    }
+
+    // NOTE: This must come after preprocessing since we require all clauses to
+    // have at least two literals
+    let mut watch_index = TaggedVec::new();
+    initialize_watchlist(&next_var, &numbered_clauses, &mut watch_index);
+    let occurrences = initialize_occurrences(&next_var, &numbered_clauses);
+
+    let mut levels = TaggedVec::new();
+    levels.ensure_index(&next_var, NO_LEVEL);
+    let mut reasons = TaggedVec::new();
+    reasons.ensure_index(&next_var, None);
+    let mut seen = TaggedVec::new();
+    seen.ensure_index(&next_var, false);
+
+    let seed = effective_seed(&config);
+    let next_pure_check = config.pure_literal_interval.unwrap_or(0);
+    let assignment = AssignmentStorage::new(pp_result.initial_assignment, config.packed_assignment);
+    let num_problem_clauses = numbered_clauses.len();
+    let mut env = Env {
+        problem : numbered_clauses,
+        num_problem_clauses,
+        assumptions : Vec::new(),
+        interrupted : Arc::new(AtomicBool::new(false)),
+        occurrences,
+        on_learn : None,
+        final_conflict : None,
+        derivations : BTreeMap::new(),
+        #[cfg(feature = "std")]
+        proof : None,
+        compactions : 0,
+        next_var,
+        config,
+        solver_state : SolverState {
+            // Every variable can be assigned at once
+            decision_stack : Vec::with_capacity(next_var.as_index()),
+            decision_levels : Vec::new(),
+            assignment,
+            levels,
+            reasons,
+            seen,
+            watchlist : watch_index,
+            variable_order : init_var_order,
+            variable_activity,
+            variable_increment : 1.0,
+            clause_increment : 1.0,
+            max_learned : f64::max(num_problem_clauses as f64 * LEARNED_LIMIT_FACTOR, MIN_LEARNED_LIMIT),
+            conflicts_since_restart : 0,
+            learned_since_restart : 0,
+            restart_intervals : 0,
+            trail_average : 0.0,
+            trail_samples : 0,
+            conflict_trail : 0,
+            simplified_assignments : 0,
+            next_pure_check,
+            saved_phase,
+            propagation_queue : VecDeque::new(),
+            order_pops : 0,
+            order_skips : 0,
+            statistics : empty_statistics(seed),
+            rng : Rng::new(seed),
+            trace : String::new()
+        }
+    };
+
+    // The units found during preprocessing are already assigned (at level 0),
+    // but their consequences have not been propagated yet
+    for lit in pp_result.units {
+        env.solver_state.levels[lit.variable()] = 0;
+        env.solver_state.decision_stack.push(lit);
+        env.solver_state.propagation_queue.push_back(lit);
+    }
+
+    env
+}
+
+/// Literals in DIMACS form, separated by spaces (for the search trace)
+fn display_literals(lits : &[Literal]) -> String {
+    lits.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Append an event to the search trace (if tracing is enabled)
+fn trace_event(env : &mut Env, event : ::core::fmt::Arguments) {
+    if env.config.trace {
+        env.solver_state.trace.push_str(&event.to_string());
+        env.solver_state.trace.push('\n');
+    }
+}
+
+/// Search for a satisfying assignment
+///
+/// Propagate units to a fixpoint, then either learn from a conflict and
+/// backjump or make a new decision, until we have completed the assignment or
+/// derived a conflict at decision level 0
+fn search(env : &mut Env) -> core::Result {
+    loop {
+        // The interrupt is consumed so that later calls to solve can proceed
+        if env.interrupted.swap(false, Ordering::Relaxed) {
+            trace_event(env, format_args!("interrupted"));
+            return core::Result::Unknown;
+        }
+
+        if let Some(max_depth) = env.config.max_decision_depth {
+            let level = env.solver_state.decision_level();
+            if level > max_depth {
+                let diagnostic = format!("decision level {} exceeds max_decision_depth {} ({} variables, {} assumptions)",
+                                         level, max_depth, env.next_var.as_index(), env.assumptions.len());
+                if cfg!(test) {
+                    panic!("{}", diagnostic);
+                }
+                trace_event(env, format_args!("{}", diagnostic));
+                return core::Result::Unknown;
+            }
+        }
+
+        match propagate_units(env) {
+            PropagateResult::Conflict(cid) => {
+                if !resolve_conflict(env, cid) {
+                    return core::Result::Unsat;
+                }
+            },
+            PropagateResult::NoConflict => {
+                let state = &env.solver_state;
+                if state.decision_level() == 0 && state.decision_stack.len() > state.simplified_assignments {
+                    if !simplify_at_root(env) {
+                        return core::Result::Unsat;
+                    }
+                    if !env.solver_state.propagation_queue.is_empty() {
+                        continue;
+                    }
+                }
+
+                if env.num_learned_clauses() as f64 >= env.solver_state.max_learned {
+                    reduce_learned_clauses(env, 0.5);
+                    env.solver_state.max_learned *= LEARNED_LIMIT_GROWTH;
+                }
+
+                if maybe_restart(env) {
+                    // Level 0 assignments may still need to be propagated
+                    continue;
+                }
+
+                match assume(env) {
+                    Some(false) => return core::Result::Unsat,
+                    Some(true) => continue,
+                    None => {}
+                }
+
+                // If the assumptions (and their consequences) already satisfy
+                // the formula, there is nothing left to decide
+                if !env.assumptions.is_empty()
+                    && env.solver_state.decision_level() == env.assumptions.len()
+                    && problem_satisfied(env) {
+                    trace_event(env, format_args!("satisfied by assumptions"));
+                    return core::Result::Sat;
+                }
+
+                if let Some(interval) = env.config.pure_literal_interval {
+                    let state = &env.solver_state;
+                    if state.statistics.decisions >= state.next_pure_check
+                        && state.decision_level() >= env.assumptions.len() {
+                        match find_pure_literal(env) {
+                            Some(lit) => {
+                                env.solver_state.statistics.decisions += 1;
+                                trace_event(env, format_args!("pure {}", lit));
+                                decide(env, lit, false);
+                                continue;
+                            }
+                            None => {
+                                let state = &mut env.solver_state;
+                                state.next_pure_check = state.statistics.decisions + interval;
+                            }
+                        }
+                    }
+                }
+
+                match next_decision(env) {
+                    None => return core::Result::Sat,
+                    Some(next_lit) => {
+                        env.solver_state.statistics.decisions += 1;
+                        trace_event(env, format_args!("decide {}", next_lit));
+                        decide(env, next_lit, false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The Luby sequence: 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+///
+/// `RestartPolicy::Luby` restarts after `unit` times each term in turn.
+pub fn luby() -> impl Iterator<Item = u64> {
+    (0..).map(luby_term)
+}
+
+/// The `i`th term (from 0) of the Luby sequence: 1, 1, 2, 1, 1, 2, 4, 1, ...
+fn luby_term(mut i : u64) -> u64 {
+    // Find the smallest complete subsequence (of length 2^k - 1) containing
+    // the term, then descend into the copy of the previous subsequence that it
+    // falls in
+    let mut size = 1;
+    let mut exponent = 0;
+    while size < i + 1 {
+        size = 2 * size + 1;
+        exponent += 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        exponent -= 1;
+        i %= size;
+    }
+    1 << exponent
+}
+
+/// The number of conflicts allowed in the given restart interval (or `None` if
+/// the solver never restarts)
+fn restart_limit(policy : &RestartPolicy, interval : u64) -> Option<u64> {
+    match policy {
+        RestartPolicy::Never => None,
+        RestartPolicy::Luby { unit } => Some(unit * luby_term(interval)),
+        RestartPolicy::Geometric { first, factor } => Some((*first as f64 * factor.powi(interval as i32)) as u64)
+    }
+}
+
+/// The number of conflicts over which the trail size is averaged
+///
+/// Until there are this many samples, the average is their mean; after that,
+/// each new sample has this share of the weight.
+const TRAIL_AVERAGE_WINDOW : u64 = 5000;
+
+/// Add the current trail size to its moving average
+fn sample_trail_size(state : &mut SolverState) {
+    state.trail_samples = ::core::cmp::min(state.trail_samples + 1, TRAIL_AVERAGE_WINDOW);
+    state.conflict_trail = state.decision_stack.len();
+    state.trail_average += (state.conflict_trail as f64 - state.trail_average) / state.trail_samples as f64;
+}
+
+/// Restart if the current restart interval is over, returning true if the
+/// solver restarted
+///
+/// If nothing has been learned since the last restart, the restart is skipped:
+/// the only progress since then is in the decisions that chronological
+/// backtracking has flipped, which a restart would throw away (allowing the
+/// solver to repeat the same decisions forever).  The restart can also be
+/// blocked by the trail size (see `SolverConfig::restart_blocking`).
+fn maybe_restart(env : &mut Env) -> bool {
+    let state = &env.solver_state;
+    match restart_limit(&env.config.restart_policy, state.restart_intervals) {
+        Some(limit) if state.conflicts_since_restart >= limit => {},
+        _ => return false
+    }
+
+    env.solver_state.restart_intervals += 1;
+    env.solver_state.conflicts_since_restart = 0;
+    let state = &env.solver_state;
+    if env.config.restart_blocking.is_some_and(|factor| state.conflict_trail as f64 > factor * state.trail_average) {
+        trace_event(env, format_args!("restart blocked"));
+        return false;
+    }
+    if env.solver_state.learned_since_restart == 0 {
+        trace_event(env, format_args!("restart skipped"));
+        return false;
+    }
+
+    env.solver_state.learned_since_restart = 0;
+    env.solver_state.statistics.restarts += 1;
+    trace_event(env, format_args!("restart"));
+    backtrack_to_root(env);
+    if env.config.order_gc_interval.is_some_and(|n| (env.solver_state.statistics.restarts as u64).is_multiple_of(n)) {
+        rebuild_variable_order(&mut env.solver_state);
+    }
+    if env.config.reset_phases_on_restart {
+        let default = Literal::with_polarity(Variable::FIRST_VARIABLE, env.config.default_phase).satisfy();
+        for phase in env.solver_state.saved_phase.iter_mut() {
+            *phase = default;
+        }
+    }
+    true
+}
+
+/// Return to decision level 0, keeping pending propagations of the level 0
+/// assignments
+fn backtrack_to_root(env : &mut Env) {
+    backtrack_to_level(env, 0);
+}
+
+/// Return to the given decision level, keeping pending propagations of the
+/// assignments that remain
+fn backtrack_to_level(env : &mut Env, level : usize) {
+    cancel_until(env, level);
+    let state = &mut env.solver_state;
+    let assignment = &state.assignment;
+    state.propagation_queue.retain(|lit| !assignment.value_of(lit.variable()).is_unassigned());
+}
+
+/// Add a problem clause to the clause database in the middle of a session
+///
+/// The clause is watched by two literals that maintain the watch invariant
+/// under the current assignment: if it is unit, its remaining literal is
+/// enqueued; if it is falsified, the conflict is resolved by backjumping until
+/// it is not.  Either can require backjumping below the current level, since
+/// the implied literal must be assigned at the level of the last falsified
+/// literal for backtracking to undo them together.  Unit clauses are assigned
+/// at level 0.  Tautologies are dropped.
+///
+/// Returns false if the clause makes the formula unsatisfiable.
+fn add_problem_clause(env : &mut Env, lits : Vec<Literal>) -> bool {
+    add_clause_mid_search(env, lits, 0)
+}
+
+/// Add a clause with the given flags to the clause database in the middle of a
+/// session (see `add_problem_clause`)
+///
+/// Clauses with `LEARNED_MASK` set count as learned clauses (and can later be
+/// deleted); others count as problem clauses.
+fn add_clause_mid_search(env : &mut Env, lits : Vec<Literal>, flags : u16) -> bool {
+    let mut lits = match normalize_clause(lits) {
+        None => return true,
+        Some(lits) => lits
+    };
+
+    if lits.len() < 2 {
+        backtrack_to_root(env);
+        return assert_unit_clause(env, &lits);
+    }
+
+    loop {
+        let state = &env.solver_state;
+        sort_for_watching(state, &mut lits);
+
+        let first_val = state.value_of(lits[0]);
+        if state.value_of(lits[1]) != Value::LIFTED_FALSE {
+            break;
+        }
+
+        // The second literal is the most recently falsified of the others
+        let first_level = state.levels[lits[0].variable()];
+        let second_level = state.levels[lits[1].variable()];
+        let target = if first_val == Value::LIFTED_FALSE {
+            if first_level == 0 {
+                return false;
+            }
+            if first_level == second_level { first_level - 1 } else { second_level }
+        } else if first_val == Value::LIFTED_TRUE {
+            if first_level <= second_level {
+                break;
+            }
+            second_level
+        } else {
+            if state.decision_level() == second_level {
+                let first = lits[0];
+                let cid = insert_counted_clause(env, lits, flags);
+                enqueue(&mut env.solver_state, first, Some(cid));
+                return true;
+            }
+            second_level
+        };
+        backtrack_to_level(env, target);
+    }
+
+    insert_counted_clause(env, lits, flags);
+    true
+}
+
+/// Insert a clause added mid-search, counting it as a problem clause unless
+/// it is learned
+fn insert_counted_clause(env : &mut Env, lits : Vec<Literal>, flags : u16) -> ClauseId {
+    if flags & LEARNED_MASK == 0 {
+        env.num_problem_clauses += 1;
+    }
+    insert_clause(env, lits, flags)
+}
+
+/// Sort the literals of a clause and remove duplicates, returning `None` for
+/// tautologies
+fn normalize_clause(mut lits : Vec<Literal>) -> Option<Vec<Literal>> {
+    lits.sort();
+    lits.dedup();
+    // Complementary literals are adjacent once sorted
+    if lits.windows(2).any(|w| w[0].variable() == w[1].variable()) {
+        None
+    } else {
+        Some(lits)
+    }
+}
+
+/// Assert the literal of a unit clause at level 0 (which must be the current
+/// level), returning false if the clause is empty or its literal is false
+fn assert_unit_clause(env : &mut Env, lits : &[Literal]) -> bool {
+    match lits.first() {
+        None => false,
+        Some(lit) => {
+            let val = env.solver_state.value_of(*lit);
+            if val.is_unassigned() {
+                enqueue(&mut env.solver_state, *lit, None);
+            }
+            val != Value::LIFTED_FALSE
+        }
+    }
+}
+
+/// Order the literals of a clause so that the best candidates for watching
+/// come first: true literals, then unassigned literals, then false literals
+/// from the highest decision level
+fn sort_for_watching(state : &SolverState, lits : &mut [Literal]) {
+    lits.sort_by_key(|l| {
+        let val = state.value_of(*l);
+        if val == Value::LIFTED_TRUE {
+            (0, Reverse(0))
+        } else if val.is_unassigned() {
+            (1, Reverse(0))
+        } else {
+            (2, Reverse(state.levels[l.variable()]))
+        }
+    });
+}
+
+/// Add a problem clause at level 0 without watching it (see `add_clauses`)
+///
+/// This behaves like `add_problem_clause` at level 0, which never needs to
+/// backjump.  Returns false if the clause makes the formula unsatisfiable.
+fn add_root_clause(env : &mut Env, lits : Vec<Literal>) -> bool {
+    debug_assert!(env.solver_state.decision_level() == 0);
+    let mut lits = match normalize_clause(lits) {
+        None => return true,
+        Some(lits) => lits
+    };
+    if lits.len() < 2 {
+        return assert_unit_clause(env, &lits);
+    }
+
+    sort_for_watching(&env.solver_state, &mut lits);
+    let state = &env.solver_state;
+    let (first, first_val) = (lits[0], state.value_of(lits[0]));
+    if first_val == Value::LIFTED_FALSE {
+        return false;
+    }
+    let implies_first = first_val.is_unassigned() && state.value_of(lits[1]) == Value::LIFTED_FALSE;
+
+    let cid = env.problem.push(ClauseHeader::new(ClauseId(0), lits.len(), 0), &lits);
+    add_occurrences(&mut env.occurrences, env.problem.clause(cid));
+    env.num_problem_clauses += 1;
+    if implies_first {
+        enqueue(&mut env.solver_state, first, Some(cid));
+    }
+    true
+}
+
+/// Add a batch of problem clauses, watching them all in a single pass over
+/// the clause database at the end rather than one at a time
+///
+/// The solver returns to level 0 first.  Returns false if the clauses make
+/// the formula unsatisfiable (the rest of the batch is then skipped).
+fn add_problem_clauses<I>(env : &mut Env, clauses : I) -> bool
+where
+    I : IntoIterator<Item = Vec<Literal>>
+{
+    backtrack_to_root(env);
+    let mut sat = true;
+    for lits in clauses {
+        assert!(lits.iter().all(|l| l.variable() < env.next_var), "Clauses cannot introduce new variables");
+        if !add_root_clause(env, lits) {
+            sat = false;
+            break;
+        }
+    }
+
+    rebuild_watchlists(env);
+    sat
+}
+
+/// A solver for a single problem
+pub struct Solver {
+    env : Env,
+    /// Set once the problem is known to be unsatisfiable (either by
+    /// preprocessing or by search)
+    known_unsat : bool,
+    /// The DIMACS variable number of each variable, used when writing models
+    dimacs_variables : Option<TaggedVec<Variable, u32>>,
+    /// The inverse of `dimacs_variables`
+    variables_by_dimacs : BTreeMap<u32, Variable>,
+    /// True if the last call to solve found a model (which is still the
+    /// current assignment)
+    has_model : bool
+}
+
+/// The maximum width of a `v` line written by `Solver::write_model`
+const MODEL_LINE_WIDTH : usize = 80;
+
+/// The incremental solving interface, as a trait so that callers can choose a
+/// solver (or configuration) at runtime and hold it as a `Box<dyn SatSolver>`
+///
+/// The methods mirror those of `Solver`, which documents them.
+pub trait SatSolver {
+    fn add_clause(&mut self, lits : &[Literal]);
+
+    fn solve(&mut self) -> core::Result;
+
+    fn solve_under_assumptions(&mut self, assumptions : &[Literal]) -> core::Result;
+
+    fn model(&self) -> Option<Model>;
+
+    fn stats(&self) -> Stats;
+}
+
+impl SatSolver for Solver {
+    fn add_clause(&mut self, lits : &[Literal]) {
+        Solver::add_clause(self, lits)
+    }
+
+    fn solve(&mut self) -> core::Result {
+        Solver::solve(self)
+    }
+
+    fn solve_under_assumptions(&mut self, assumptions : &[Literal]) -> core::Result {
+        Solver::solve_under_assumptions(self, assumptions)
+    }
+
+    fn model(&self) -> Option<Model> {
+        Solver::model(self)
+    }
+
+    fn stats(&self) -> Stats {
+        Solver::stats(self)
+    }
+}
+
+impl Solver {
+    pub fn new(mut clauses : Vec<Clause>, next_var : Variable, config : SolverConfig) -> Self {
+        let pp_result = preprocess(&mut clauses, &next_var);
+        let known_unsat = pp_result.is_unsat();
+        let final_conflict = pp_result.conflict_clause();
+        let numbered_clauses = intern_clauses(clauses);
+        let mut env = initialize_env(numbered_clauses, pp_result, next_var, config);
+        env.final_conflict = final_conflict;
+        Solver {
+            env,
+            known_unsat,
+            dimacs_variables : None,
+            variables_by_dimacs : BTreeMap::new(),
+            has_model : false
+        }
+    }
+
+    pub fn solve(&mut self) -> core::Result {
+        self.solve_under_assumptions(&[])
+    }
+
+    /// Solve with the given literals assumed to be true
+    ///
+    /// Unlike adding the literals as unit clauses, assumptions only hold for
+    /// this call: learned clauses are kept between calls, but an `Unsat` result
+    /// only means that the formula is unsatisfiable under these assumptions.
+    /// The solver can be called again (with different assumptions) after
+    /// either result.
+    pub fn solve_under_assumptions(&mut self, assumptions : &[Literal]) -> core::Result {
+        if self.known_unsat {
+            self.finish_refutation();
+            return core::Result::Unsat;
+        }
+
+        backtrack_to_root(&mut self.env);
+        self.env.assumptions = assumptions.to_vec();
+        self.env.final_conflict = None;
+        let res = search(&mut self.env);
+        self.known_unsat = res == core::Result::Unsat && assumptions.is_empty();
+        self.has_model = res == core::Result::Sat;
+        if self.known_unsat {
+            self.finish_refutation();
+        }
+        res
+    }
+
+    /// End the proof (if any) with the empty clause
+    fn finish_refutation(&mut self) {
+        self.env.add_to_proof(&[]);
+    }
+
+    /// The literals that are true in every model of the formula
+    ///
+    /// This solves the formula (if that has not been done yet), then tests
+    /// each literal of the model by solving under the assumption of its
+    /// negation.  Every model found along the way rules out the candidates it
+    /// falsifies.  This is empty if the formula is unsatisfiable.  If the
+    /// solver is interrupted, this returns the backbone literals found so far.
+    pub fn backbone(&mut self) -> Vec<Literal> {
+        if self.solve() != core::Result::Sat {
+            return Vec::new();
+        }
+
+        let mut candidates : Vec<Literal> = (0..self.env.next_var.as_index()).map(|ix| {
+            let v = Variable::from_index(ix);
+            let polarity = if self.env.solver_state.assignment.value_of(v) == Value::LIFTED_TRUE { Polarity::Positive } else { Polarity::Negative };
+            Literal::with_polarity(v, polarity)
+        }).collect();
+        let mut backbone = Vec::new();
+        while let Some(lit) = candidates.pop() {
+            if self.env.solver_state.levels[lit.variable()] == 0 && self.env.solver_state.value_of(lit) == Value::LIFTED_TRUE {
+                // Implied by the formula alone
+                backbone.push(lit);
+            } else {
+                match self.solve_under_assumptions(&[lit.negate()]) {
+                    core::Result::Unsat => {
+                        backbone.push(lit);
+                        // Record the literal as a unit so later checks are cheaper
+                        backtrack_to_root(&mut self.env);
+                        self.has_model = false;
+                        enqueue(&mut self.env.solver_state, lit, None);
+                    },
+                    core::Result::Sat => {
+                        let state = &self.env.solver_state;
+                        candidates.retain(|l| state.value_of(*l) == Value::LIFTED_TRUE);
+                    },
+                    core::Result::Unknown => break
+                }
+            }
+        }
+
+        backbone.sort();
+        backbone
+    }
+
+    /// Undo every decision (and the assignments that followed from them),
+    /// returning to decision level 0
+    ///
+    /// Assignments at level 0 and learned clauses are kept.  Pending
+    /// propagations of undone assignments are discarded, while those of level 0
+    /// assignments are kept so that their consequences are still found.
+    pub fn restart(&mut self) {
+        trace_event(&mut self.env, format_args!("restart"));
+        backtrack_to_root(&mut self.env);
+        self.has_model = false;
+    }
+
+    /// Save the current search state: the decisions, the assignment (with the
+    /// level and reason of each assigned variable), and the pending
+    /// propagations
+    ///
+    /// See `restore`.  Learned clauses and heuristic state (e.g., activities)
+    /// are not part of the snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        let state = &self.env.solver_state;
+        Snapshot {
+            trail : state.decision_stack.iter().map(|l| (*l, state.levels[l.variable()], state.reasons[l.variable()])).collect(),
+            decision_levels : state.decision_levels.clone(),
+            propagation_queue : state.propagation_queue.iter().copied().collect(),
+            has_model : self.has_model,
+            compactions : self.env.compactions
+        }
+    }
+
+    /// Return to the search state saved by `snapshot`
+    ///
+    /// If the current decision stack extends the saved one (e.g., after making
+    /// more decisions), this just undoes the extra assignments.  Otherwise,
+    /// the assignments after the common prefix are undone and the saved ones
+    /// are replayed; since the watched literals may have moved in the
+    /// meantime, the replayed literals are also queued for propagation, which
+    /// can only find what the saved state would have.
+    ///
+    /// The snapshot must be restored before the clause database is next
+    /// compacted (which happens when learned clauses are reduced during
+    /// solving), since that renumbers the reasons; this panics otherwise.
+    pub fn restore(&mut self, snapshot : Snapshot) {
+        assert_eq!(snapshot.compactions, self.env.compactions, "The clause database was compacted after the snapshot");
+        let state = &mut self.env.solver_state;
+        let common = state.decision_stack.iter().zip(snapshot.trail.iter())
+            .take_while(|(l, (saved, level, reason))| {
+                **l == *saved && state.levels[l.variable()] == *level && state.reasons[l.variable()] == *reason
+            })
+            .count();
+
+        undo_trail_to(state, common);
+        // Pending propagations of kept assignments must stay pending, since
+        // the watches of their clauses have not been visited
+        let mut pending : BTreeSet<Literal> = state.propagation_queue.iter().copied().filter(|l| {
+            state.value_of(*l) == Value::LIFTED_TRUE
+        }).collect();
+        pending.extend(snapshot.propagation_queue.iter().copied());
+        for (lit, level, reason) in snapshot.trail[common..].iter() {
+            let v = lit.variable();
+            state.decision_stack.push(*lit);
+            state.assignment.set(v, lit.satisfy());
+            state.levels[v] = *level;
+            state.reasons[v] = *reason;
+            pending.insert(*lit);
+        }
+        state.decision_levels = snapshot.decision_levels;
+        state.propagation_queue = state.decision_stack.iter().copied().filter(|l| pending.contains(l)).collect();
+        self.has_model = snapshot.has_model;
+    }
+
+    /// Add a clause to the problem
+    ///
+    /// The clause is kept for every later call to solve.  Adding a clause
+    /// discards the current model, and can undo some of the current
+    /// assignment (see `add_problem_clause`).  Clauses can only mention
+    /// variables that the solver was created with.
+    pub fn add_clause(&mut self, lits : &[Literal]) {
+        assert!(lits.iter().all(|l| l.variable() < self.env.next_var), "Clauses cannot introduce new variables");
+        self.has_model = false;
+        if !self.known_unsat && !add_problem_clause(&mut self.env, lits.to_vec()) {
+            self.known_unsat = true;
+        }
+    }
+
+    /// Add a clause learned elsewhere (e.g., by another solver working on the
+    /// same problem) to the clause database
+    ///
+    /// The clause must be implied by the problem: it is treated as a learned
+    /// clause, so it can later be deleted when the learned clauses are reduced.
+    /// Like `add_clause`, it is watched under the current assignment, which
+    /// can assert its remaining literal if it is unit, or backjump if it is
+    /// falsified.  Imported clauses are not written to the proof.
+    pub fn import_clause(&mut self, lits : &[Literal]) {
+        assert!(lits.iter().all(|l| l.variable() < self.env.next_var), "Clauses cannot introduce new variables");
+        self.has_model = false;
+        if !self.known_unsat && !add_clause_mid_search(&mut self.env, lits.to_vec(), LEARNED_MASK) {
+            self.known_unsat = true;
+        }
+    }
+
+    /// Add many clauses to the problem at once
+    ///
+    /// This is equivalent to adding each clause with `add_clause` in turn
+    /// (after returning to decision level 0), but much faster for large
+    /// batches: the clauses are stored first, and the watchlists are then
+    /// rebuilt in a single pass.
+    pub fn add_clauses<I>(&mut self, clauses : I)
+    where
+        I : IntoIterator<Item = Vec<Literal>>
+    {
+        self.has_model = false;
+        if !self.known_unsat && !add_problem_clauses(&mut self.env, clauses) {
+            self.known_unsat = true;
+        }
+    }
+
+    /// The number of clauses from the original problem in the clause database
+    ///
+    /// Note that this does not include clauses removed by preprocessing (e.g.,
+    /// unit clauses, which are folded into the initial assignment) or clauses
+    /// satisfied at decision level 0, which are removed during search
+    pub fn num_problem_clauses(&self) -> usize {
+        self.env.num_problem_clauses
+    }
+
+    /// The number of learned clauses in the clause database
+    ///
+    /// Note that learned unit clauses are not stored (they are assigned at
+    /// decision level 0 instead)
+    pub fn num_learned_clauses(&self) -> usize {
+        self.env.num_learned_clauses()
+    }
+
+    /// The clauses (problem or learned) that mention the given variable, in
+    /// either polarity
+    ///
+    /// Each clause is produced once, in the order the clauses were added.
+    /// Clauses removed by preprocessing (e.g., units) or satisfied at decision
+    /// level 0 are not included.
+    pub fn clauses_with(&self, var : Variable) -> impl Iterator<Item = ClauseId> + '_ {
+        self.env.occurrences[var].iter().copied()
+    }
+
+    /// True if the clause has a literal that is true under the current
+    /// assignment (at any decision level)
+    pub fn is_satisfied(&self, id : ClauseId) -> bool {
+        let state = &self.env.solver_state;
+        let lits = self.env.problem.clause(id).literals();
+        lits.iter().any(|l| state.value_of(*l) == Value::LIFTED_TRUE)
+    }
+
+    /// The literals of the clause as signed DIMACS literals, with variables
+    /// numbered as in `write_model` (see `set_dimacs_variables`)
+    ///
+    /// The literals are in their current order in the clause database, which
+    /// propagation changes (the first two are the watched literals).
+    pub fn clause_dimacs(&self, id : ClauseId) -> Vec<i32> {
+        self.env.problem.clause(id).literals().iter().map(|l| {
+            let var = self.dimacs_variable(l.variable()) as i32;
+            if l.is_negated() { -var } else { var }
+        }).collect()
+    }
+
+    /// The learned clauses with their activities, from the most to the least
+    /// active (ties in order of `ClauseId`)
+    ///
+    /// Reducing the clause database deletes from the end of this order (among
+    /// the clauses that can be deleted).
+    pub fn clauses_by_activity(&self) -> impl Iterator<Item = (ClauseId, f64)> {
+        let mut learned : Vec<(ClauseId, f64)> = self.env.problem.iter()
+            .filter(|cl| cl.is_learned())
+            .map(|cl| (cl.identifier(), cl.activity()))
+            .collect();
+        learned.sort_by(|(c1, a1), (c2, a2)| a2.total_cmp(a1).then(c1.cmp(c2)));
+        learned.into_iter()
+    }
+
+    /// The clauses that have propagated, with the number of times each has,
+    /// from the most to the least often
+    ///
+    /// Learned clauses count too.  The counts of deleted clauses are lost,
+    /// and the remaining clauses may have been renumbered since they
+    /// propagated.
+    #[cfg(feature = "clause-heat")]
+    pub fn clause_heat(&self) -> Vec<(ClauseId, u64)> {
+        let mut heat : Vec<(ClauseId, u64)> = self.env.problem.iter()
+            .filter(|cl| cl.heat() > 0)
+            .map(|cl| (cl.identifier(), cl.heat()))
+            .collect();
+        heat.sort_by_key(|(cid, h)| (::core::cmp::Reverse(*h), *cid));
+        heat
+    }
+
+    /// Set the polarity that the variable is decided with (until the search
+    /// changes it), e.g., from a `c phase` directive (see `DIMACS::phases`)
+    pub fn set_phase(&mut self, var : Variable, polarity : Polarity) {
+        self.env.solver_state.saved_phase[var] = Literal::with_polarity(var, polarity).satisfy();
+    }
+
+    /// The statistics accumulated by all calls to solve so far
+    ///
+    /// Solving never resets the statistics, so to measure a single query of an
+    /// incremental solver, call `reset_statistics` before it.
+    pub fn stats(&self) -> Stats {
+        self.env.solver_state.statistics.clone()
+    }
+
+    /// Zero every counter in the statistics (keeping the seed), without
+    /// changing the clauses (problem or learned) or the search state
+    pub fn reset_statistics(&mut self) {
+        let seed = self.env.solver_state.statistics.seed;
+        self.env.solver_state.statistics = empty_statistics(seed);
+    }
+
+    /// The model found by the last call to solve, if it returned `Sat`
+    ///
+    /// Variables left unassigned (e.g., when the assumptions alone satisfy the
+    /// formula, so that nothing was decided) are false in the model; any value
+    /// would do.
+    pub fn model(&self) -> Option<Model> {
+        if !self.has_model {
+            return None;
+        }
+
+        let assignment = &self.env.solver_state.assignment;
+        let values = (0..self.env.next_var.as_index()).map(|ix| {
+            assignment.value_of(Variable::from_index(ix)) == Value::LIFTED_TRUE
+        }).collect();
+        Some(Model { values })
+    }
+
+    /// The literals of the model found by the last call to solve that are
+    /// needed to satisfy the problem, if it returned `Sat`
+    ///
+    /// Starting from the assigned literals of the model, this greedily drops
+    /// (in variable order) each literal that every problem clause containing
+    /// its variable can do without, because the clause has another true
+    /// literal that is kept.  The variables of the dropped literals are
+    /// don't-cares: any extension of the result to a full assignment satisfies
+    /// the problem.  The result is minimal in that no single literal can be
+    /// dropped from it, but another order could drop more.  Literals assigned
+    /// at decision level 0 are always kept, since clauses satisfied by them
+    /// may have been removed from the clause database.
+    pub fn minimal_model(&self) -> Option<Vec<Literal>> {
+        if !self.has_model {
+            return None;
+        }
+
+        let state = &self.env.solver_state;
+        let lit_of = |v : Variable| {
+            if state.assignment.value_of(v) == Value::LIFTED_TRUE { v.to_positive_literal() } else { v.to_negative_literal() }
+        };
+        let mut kept : TaggedVec<Variable, bool> = (0..self.env.next_var.as_index()).map(|ix| {
+            !state.assignment.value_of(Variable::from_index(ix)).is_unassigned()
+        }).collect();
+        for ix in 0..kept.len() {
+            let v = Variable::from_index(ix);
+            if !kept[v] || state.levels[v] == 0 {
+                continue;
+            }
+            let droppable = self.env.occurrences[v].iter()
+                .map(|cid| self.env.problem.clause(*cid))
+                .filter(|cl| !cl.is_learned())
+                .all(|cl| cl.literals().iter().any(|l| {
+                    l.variable() != v && kept[l.variable()] && state.value_of(*l) == Value::LIFTED_TRUE
+                }));
+            if droppable {
+                kept[v] = false;
+            }
+        }
+
+        Some((0..kept.len()).map(Variable::from_index).filter(|v| kept[*v]).map(lit_of).collect())
+    }
+
+    /// A handle that can stop this solver (from any thread)
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.env.interrupted.clone())
+    }
+
+    /// Install a callback to inspect each clause learned from a conflict
+    /// before it is added, which may keep, drop, or replace it
+    ///
+    /// Note that dropping (or replacing) learned clauses falls back to
+    /// chronological backtracking, which can make the search much slower.
+    pub fn set_learn_hook(&mut self, on_learn : LearnHook) {
+        self.env.on_learn = Some(on_learn);
+    }
+
+    /// Write a DRUP proof of unsatisfiability to the given output as the
+    /// search proceeds
+    ///
+    /// Each learned clause is written (as a DIMACS clause, with variables named
+    /// as in `write_model`) when it is learned, and the empty clause is written
+    /// once the formula is proved unsatisfiable, so a proof checker (e.g.,
+    /// `drat-trim`) can verify an `Unsat` result against the clauses given to
+    /// the solver.  Learned clauses are never deleted from the proof.  The
+    /// proof is only complete if every learned clause is kept: clauses
+    /// discarded by `SolverConfig::max_learned_length` or a learn hook lead to
+    /// chronological backtracking, which the proof does not justify.  Call
+    /// `finish_proof` to flush the output and find out whether writing failed.
+    #[cfg(feature = "std")]
+    pub fn set_proof_writer(&mut self, out : Box<dyn std::io::Write + Send>) {
+        self.env.proof = Some(ProofLog {
+            out,
+            dimacs_variables : self.dimacs_variables.as_ref().map(|names| names.iter().copied().collect()),
+            error : None
+        });
+    }
+
+    /// Stop writing the proof, flushing the output
+    ///
+    /// This reports the first error encountered while writing the proof (if
+    /// any); it is a no-op if there is no proof.
+    #[cfg(feature = "std")]
+    pub fn finish_proof(&mut self) -> std::io::Result<()> {
+        match self.env.proof.take() {
+            None => Ok(()),
+            Some(ProofLog { error : Some(e), .. }) => Err(e),
+            Some(mut proof) => proof.out.flush()
+        }
+    }
+
+    /// Explain how conflict analysis derived a learned clause, as a sequence of
+    /// resolution steps starting from the conflict clause
+    ///
+    /// Each step resolves the current clause with the reason for one of its
+    /// literals; literals false at level 0 are dropped at the end, and the
+    /// last line is the learned clause.  This requires
+    /// `SolverConfig::record_derivations`; the clause can be given in any
+    /// order, but must be exactly as learned (e.g., as passed to a learn hook).
+    pub fn explain_learned(&self, clause : &[Literal]) -> String {
+        let mut key = clause.to_vec();
+        key.sort();
+        match self.env.derivations.get(&key) {
+            None => format!("no derivation recorded for {}\n", display_literals(clause)),
+            Some(derivation) => derivation.explain()
+        }
+    }
+
+    /// The clause that was falsified at decision level 0 when the formula was
+    /// proved unsatisfiable, if that is how it was proved
+    ///
+    /// This is a copy of the literals, since the clause itself may be deleted
+    /// (or renumbered) later.  For formulas proved unsatisfiable by
+    /// preprocessing, it is the empty clause or a unit clause that contradicts
+    /// another unit.  It is `None` if the last call to solve did not return
+    /// `Unsat`, or returned it for another reason (e.g., a failed assumption).
+    pub fn final_conflict(&self) -> Option<&[Literal]> {
+        self.env.final_conflict.as_deref()
+    }
+
+    /// The number of variables currently assigned (at any decision level)
+    pub fn num_assigned(&self) -> usize {
+        self.env.solver_state.decision_stack.len()
+    }
+
+    /// The other literals of the clause that implied the given (true) literal,
+    /// all of which are false
+    ///
+    /// This is empty if the literal is not true, or was not implied by a clause
+    /// (decisions, assumptions, units, and level 0 assignments once the clause
+    /// database has been simplified).
+    pub fn antecedents(&self, lit : Literal) -> Vec<Literal> {
+        let state = &self.env.solver_state;
+        if state.value_of(lit) != Value::LIFTED_TRUE {
+            return Vec::new();
+        }
+
+        match state.reasons[lit.variable()] {
+            None => Vec::new(),
+            Some(cid) => self.env.problem.clause(cid).literals().iter().copied().filter(|l| *l != lit).collect()
+        }
+    }
+
+    /// The decision level at which the variable was assigned, or `NO_LEVEL` if
+    /// it is unassigned
+    pub fn level_of(&self, var : Variable) -> usize {
+        self.env.solver_state.levels[var]
+    }
+
+    /// The number of variables assigned at each decision level, indexed by level
+    /// (from 0 through the current level)
+    ///
+    /// Every level but 0 has at least its decision (unless it was opened for an
+    /// assumption that already held).  This counts the level recorded for each
+    /// variable, which is not always the level in effect when it was assigned
+    /// (e.g., clauses added mid-search imply literals at earlier levels).
+    pub fn level_histogram(&self) -> Vec<usize> {
+        let state = &self.env.solver_state;
+        let mut histogram = vec![0; state.decision_level() + 1];
+        for lit in state.decision_stack.iter() {
+            histogram[state.levels[lit.variable()]] += 1;
+        }
+        histogram
+    }
+
+    /// The variables that are currently unassigned (in order)
+    ///
+    /// Unlike the variable order (which is lazily cleaned, and so can contain
+    /// assigned variables), this is exactly the variables without a value.
+    pub fn unassigned_variables(&self) -> impl Iterator<Item = Variable> + '_ {
+        let assignment = &self.env.solver_state.assignment;
+        (0..self.env.next_var.as_index()).map(Variable::from_index).filter(move |v| assignment.value_of(*v) == Value::UNASSIGNED)
+    }
+
+    /// Find watchlist entries that should not exist
+    ///
+    /// Each clause must appear in exactly the watchlists for the negations of
+    /// its two watched literals (its first two literals).  A watchlist cannot
+    /// hold the same clause twice (it is a set), so the symptom of a clause
+    /// being re-watched when it is already watched (e.g., by
+    /// `propagate_clause`) is an entry in a third watchlist.  This returns every
+    /// such entry, which should never happen in a correct solver.
+    pub fn audit_watches(&self) -> Vec<(Literal, ClauseId)> {
+        let mut bad_watches = Vec::new();
+        let watchlist = &self.env.solver_state.watchlist;
+        for lit_idx in 0..watchlist.len() {
+            let lit = Literal::from_index(lit_idx);
+            for cid in watchlist[lit].iter() {
+                let cl = self.env.problem.clause(*cid);
+                if cl[0].negate() != lit && cl[1].negate() != lit {
+                    bad_watches.push((lit, *cid));
+                }
+            }
+        }
+
+        bad_watches
+    }
+
+    /// The trace of the search so far (one event per line)
+    ///
+    /// Literals are shown in DIMACS form (by internal variable number, which
+    /// agrees with the input whenever it numbers its variables densely).
+    ///
+    /// This is empty unless tracing is enabled in the `SolverConfig`
+    pub fn trace(&self) -> &str {
+        &self.env.solver_state.trace
+    }
+
+    /// Name variables by their DIMACS numbers (see
+    /// `DIMACS::dimacs_variables`) when writing models (and proofs)
+    ///
+    /// Without this, variables are numbered from 1 in internal order
+    pub fn set_dimacs_variables(&mut self, dimacs_variables : TaggedVec<Variable, u32>) {
+        self.variables_by_dimacs = (0..self.env.next_var.as_index()).map(|ix| {
+            let v = Variable::from_index(ix);
+            (dimacs_variables[v], v)
+        }).collect();
+        #[cfg(feature = "std")]
+        if let Some(proof) = self.env.proof.as_mut() {
+            proof.dimacs_variables = Some(dimacs_variables.iter().copied().collect());
+        }
+        self.dimacs_variables = Some(dimacs_variables);
+    }
+
+    /// The value of a signed DIMACS literal in the model found by the last
+    /// call to solve
+    ///
+    /// Variables are named as in `write_model`.  This is `None` if there is no
+    /// model (see `model`) or the variable is not in the formula.
+    pub fn value_of_dimacs(&self, lit : i32) -> Option<bool> {
+        if !self.has_model {
+            return None;
+        }
+
+        let dimacs_var = lit.unsigned_abs();
+        let var = match &self.dimacs_variables {
+            Some(_) => self.variables_by_dimacs.get(&dimacs_var).copied(),
+            None => Variable::from_dimacs(dimacs_var).filter(|v| *v < self.env.next_var)
+        }?;
+        let value = self.env.solver_state.assignment.value_of(var) == Value::LIFTED_TRUE;
+        Some(if lit < 0 { !value } else { value })
+    }
+
+    /// Write the current assignment as DIMACS `v` lines (terminated by `0`),
+    /// wrapping lines at 80 columns
+    ///
+    /// This is only a model after `solve` returns `Sat`.  Unassigned variables
+    /// are written as false.  Variables appear in ascending order of their
+    /// DIMACS numbers.  Only one line is buffered at a time, so this is
+    /// suitable for very large models.
+    #[cfg(feature = "std")]
+    pub fn write_model<W : std::io::Write>(&self, w : &mut W) -> std::io::Result<()> {
+        let assignment = &self.env.solver_state.assignment;
+        let mut line = String::with_capacity(MODEL_LINE_WIDTH);
+        line.push('v');
+        // Note that the assignment has a slot for `next_var`, which is not a
+        // real variable
+        let lits = self.variables_in_dimacs_order().into_iter().map(|v| {
+            let dimacs_var = self.dimacs_variable(v) as i64;
+            if assignment.value_of(v) == Value::LIFTED_TRUE { dimacs_var } else { -dimacs_var }
+        });
+        for lit in lits.chain(::core::iter::once(0)) {
+            let token = format!(" {}", lit);
+            if line.len() + token.len() > MODEL_LINE_WIDTH {
+                writeln!(w, "{}", line)?;
+                line.truncate(1);
+            }
+            line.push_str(&token);
+        }
+
+        writeln!(w, "{}", line)
+    }
+
+    /// Write the current assignment as a JSON object mapping DIMACS variable
+    /// numbers (as strings) to their values, e.g., `{"1": true, "2": false}`
+    ///
+    /// Variables are named (and ordered) as in `write_model`.
+    #[cfg(feature = "std")]
+    pub fn write_model_json<W : std::io::Write>(&self, w : &mut W) -> std::io::Result<()> {
+        let assignment = &self.env.solver_state.assignment;
+        write!(w, "{{")?;
+        for (ix, v) in self.variables_in_dimacs_order().into_iter().enumerate() {
+            let sep = if ix == 0 { "" } else { ", " };
+            write!(w, "{}\"{}\": {}", sep, self.dimacs_variable(v), assignment.value_of(v) == Value::LIFTED_TRUE)?;
+        }
+        write!(w, "}}")
+    }
+
+    /// Write the current (partial) assignment as `c assign <literal>` DIMACS
+    /// comment lines, with literals named as in `write_model`
+    ///
+    /// Assignments are written in the order they were made, so level 0
+    /// assignments come first, then those of each decision (starting with the
+    /// decided literal).
+    #[cfg(feature = "std")]
+    pub fn dump_partial<W : std::io::Write>(&self, w : &mut W) -> std::io::Result<()> {
+        for lit in self.env.solver_state.decision_stack.iter() {
+            let dimacs_var = self.dimacs_variable(lit.variable()) as i64;
+            writeln!(w, "c assign {}", if lit.is_negated() { -dimacs_var } else { dimacs_var })?;
+        }
+
+        Ok(())
+    }
+
+    /// The variables in ascending order of their DIMACS numbers (see
+    /// `dimacs_variable`), so that models are written in a stable order that
+    /// does not depend on how the variables were numbered internally
+    fn variables_in_dimacs_order(&self) -> Vec<Variable> {
+        match &self.dimacs_variables {
+            Some(_) => self.variables_by_dimacs.values().copied().collect(),
+            None => (0..self.env.next_var.as_index()).map(Variable::from_index).collect()
+        }
+    }
+
+    /// The DIMACS number of a variable: its name from `set_dimacs_variables`
+    /// if there are names, or the standard numbering otherwise
+    fn dimacs_variable(&self, v : Variable) -> u32 {
+        match &self.dimacs_variables {
+            Some(names) => names[v],
+            None => v.to_dimacs()
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_variables(count : usize) -> Vec<Variable> {
+    let mut vars = Vec::new();
+    let mut v = Variable::FIRST_VARIABLE;
+    for _ in 0..count {
+        vars.push(v);
+        v = v.next_variable();
+    }
+
+    vars
+}
+
+#[cfg(test)]
+fn solve_dimacs(input : &str) -> core::Result {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    solve(dimacs.clauses, dimacs.next_var)
+}
+
+#[test]
+fn test_solve_sat() {
+    let result = solve_dimacs("p cnf 3 3\n1 2 0\n-1 3 0\n-3 -2 0\n");
+    assert_eq!(result, core::Result::Sat);
+}
+
+#[test]
+fn test_solve_unsat() {
+    let result = solve_dimacs("p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n");
+    assert_eq!(result, core::Result::Unsat);
+}
+
+#[test]
+fn test_solve_conflicting_units() {
+    let result = solve_dimacs("p cnf 1 2\n1 0\n-1 0\n");
+    assert_eq!(result, core::Result::Unsat);
+}
+
+#[test]
+fn test_solve_units_propagate() {
+    let result = solve_dimacs("p cnf 3 4\n1 0\n-1 2 0\n-2 3 0\n-3 -1 0\n");
+    assert_eq!(result, core::Result::Unsat);
+}
+
+/// Lazily generate the pigeonhole problem: `pigeons` pigeons must each be in
+/// one of `holes` holes, and no two pigeons can share a hole
+#[cfg(test)]
+fn pigeonhole(pigeons : usize, holes : usize) -> (impl Iterator<Item = Vec<Literal>>, Variable) {
+    let next_var = test_variables(pigeons * holes + 1)[pigeons * holes];
+    let var = move |p : usize, h : usize| test_variables(p * holes + h + 1)[p * holes + h];
+    let at_least_one = (0..pigeons).map(move |p| (0..holes).map(|h| var(p, h).to_positive_literal()).collect());
+    let at_most_one = (0..holes).flat_map(move |h| {
+        (0..pigeons).flat_map(move |p1| {
+            (p1 + 1..pigeons).map(move |p2| vec![var(p1, h).to_negative_literal(), var(p2, h).to_negative_literal()])
+        })
+    });
+
+    (at_least_one.chain(at_most_one), next_var)
+}
+
+#[test]
+fn test_solve_from_iter_unsat() {
+    let (clauses, next_var) = pigeonhole(4, 3);
+    assert_eq!(solve_from_iter(clauses, next_var), core::Result::Unsat);
+}
+
+#[test]
+fn test_solve_from_iter_sat() {
+    let (clauses, next_var) = pigeonhole(3, 3);
+    assert_eq!(solve_from_iter(clauses, next_var), core::Result::Sat);
+}
+
+#[test]
+fn test_solve_from_iter_stops_at_conflict() {
+    // The conflicting units are found before the rest of the (infinite) stream
+    // is consumed
+    let vars = test_variables(1);
+    let units = vec![vec![vars[0].to_positive_literal()], vec![vars[0].to_negative_literal()]];
+    let clauses = units.into_iter().chain(std::iter::repeat(vec![vars[0].to_positive_literal()]));
+    assert_eq!(solve_from_iter(clauses, vars[0].next_variable()), core::Result::Unsat);
+}
+
+#[test]
+fn test_deterministic_traces_match() {
+    let config = SolverConfig {
+        deterministic : true,
+        random_decision_freq : 0.5,
+        trace : true,
+        ..SolverConfig::default()
+    };
+
+    let mut traces = Vec::new();
+    for _ in 0..2 {
+        let (clauses, next_var) = pigeonhole(5, 4);
+        let clauses = to_clauses(clauses);
+        let mut solver = Solver::new(clauses, next_var, config.clone());
+        assert_eq!(solver.solve(), core::Result::Unsat);
+        traces.push(solver.trace().to_string());
+    }
+
+    assert!(!traces[0].is_empty());
+    assert_eq!(traces[0], traces[1]);
+}
+
+#[test]
+fn test_deterministic_ignores_seed() {
+    let base = SolverConfig { deterministic : true, seed : Some(1), ..SolverConfig::default() };
+    let other = SolverConfig { seed : Some(2), ..base.clone() };
+    assert_eq!(effective_seed(&base), effective_seed(&other));
+}
+
+#[test]
+fn test_reported_seed_reproduces_run() {
+    let run = |seed : Option<u64>| {
+        let (clauses, next_var) = pigeonhole(5, 4);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { seed, random_decision_freq : 0.5, trace : true, ..SolverConfig::default() };
+        let mut solver = Solver::new(clauses, next_var, config);
+        assert_eq!(solver.solve(), core::Result::Unsat);
+        (solver.stats().seed, solver.trace().to_string())
+    };
+
+    // No seed is given, so one is generated
+    let (seed, trace) = run(None);
+    let (replayed_seed, replayed_trace) = run(Some(seed));
+    assert_eq!(replayed_seed, seed);
+    assert_eq!(replayed_trace, trace);
+
+    let config = SolverConfig { deterministic : true, seed : Some(7), ..SolverConfig::default() };
+    assert_eq!(solve_full(Vec::new(), Variable::FIRST_VARIABLE, config).stats.seed, DETERMINISTIC_SEED);
+}
+
+#[test]
+fn test_compact_after_deep_search() {
+    // A long chain of implications lets us build a deep decision stack (one
+    // level per variable) without conflicts
+    let num_vars = 10 * MIN_COMPACT_CAPACITY;
+    let vars = test_variables(num_vars + 1);
+    let clauses = to_clauses((0..num_vars - 1).map(|i| vec![vars[i].to_positive_literal(), vars[i + 1].to_negative_literal()]));
+    let mut solver = Solver::new(clauses, vars[num_vars], SolverConfig::default());
+    let env = &mut solver.env;
+    while let Some(v) = env.solver_state.variable_order.pop().map(|(v, _)| v) {
+        if env.solver_state.assignment.value_of(v) == Value::UNASSIGNED {
+            decide(env, v.to_negative_literal(), false);
+            assert!(propagate_units(env) == PropagateResult::NoConflict);
+        }
+    }
+
+    let deep_capacity = env.solver_state.decision_stack.capacity();
+    assert!(env.solver_state.decision_stack.len() == num_vars);
+
+    // A small backtrack keeps the allocation...
+    let level = env.solver_state.decision_level() - 1;
+    cancel_until(env, level);
+    assert_eq!(env.solver_state.decision_stack.capacity(), deep_capacity);
+
+    // ...while returning to a shallow search releases it
+    cancel_until(env, 1);
+    assert!(env.solver_state.decision_stack.capacity() < deep_capacity / 4);
+    assert!(env.solver_state.decision_stack.capacity() >= env.solver_state.decision_stack.len());
+}
+
+#[test]
+fn test_stale_variable_order_is_rebuilt() {
+    // Deciding the hub variable propagates every other variable, leaving the
+    // variable order full of stale entries
+    let num_vars = 20 * MIN_STALE_SKIPS;
+    let vars = test_variables(num_vars + 1);
+    let hub = vars[0];
+    let clauses = to_clauses((1..num_vars).map(|i| vec![hub.to_negative_literal(), vars[i].to_positive_literal()]));
+    let mut solver = Solver::new(clauses, vars[num_vars], SolverConfig::default());
+    solver.env.solver_state.variable_order.push(hub, OrderedFloat(f32::MAX));
+
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let state = &solver.env.solver_state;
+    assert_eq!(state.statistics.decisions, 1);
+    assert_eq!(state.statistics.order_rebuilds, 1);
+    // The rebuild happened as soon as the threshold was reached, rather than
+    // popping every stale variable individually
+    assert!(state.variable_order.is_empty());
+    assert_eq!(state.order_pops, 0);
+}
+
+#[test]
+fn test_backtrack_restores_priority() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 3 2\n1 2 0\n-1 3 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    let env = &mut solver.env;
+    let hot = Variable::FIRST_VARIABLE;
+    let high = OrderedFloat(1000.0);
+    env.solver_state.variable_activity[hot] = high;
+    env.solver_state.variable_order.push(hot, high);
+
+    let lit = next_decision(env).unwrap();
+    assert_eq!(lit.variable(), hot);
+    decide(env, lit, false);
+    assert!(propagate_units(env) == PropagateResult::NoConflict);
+    assert_eq!(env.solver_state.variable_order.get_priority(&hot), None);
+
+    cancel_until(env, 0);
+    assert_eq!(env.solver_state.variable_order.get_priority(&hot), Some(&high));
+    assert_eq!(next_decision(env).map(|l| l.variable()), Some(hot));
+}
+
+#[test]
+fn test_solver_counts() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 4\n1 0\n-1 2 0\n3 4 0\n-3 -4 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    // The unit clause is folded into the initial assignment
+    assert_eq!(solver.num_problem_clauses(), 3);
+    assert_eq!(solver.num_learned_clauses(), 0);
+    assert_eq!(solver.num_assigned(), 1);
+
+    assert_eq!(solver.solve(), core::Result::Sat);
+    // -1 2 is satisfied at level 0, and so removed by simplification
+    assert_eq!(solver.num_problem_clauses(), 2);
+    assert_eq!(solver.num_assigned(), 4);
+}
+
+#[test]
+fn test_jeroslow_wang_first_decision() {
+    // Variable 3 occurs negatively in the two shortest clauses, so it has the
+    // highest score (despite variable 1 occurring in more clauses)
+    let input = "p cnf 5 5\n-3 1 0\n-3 2 0\n1 2 4 5 0\n1 -2 4 -5 0\n1 3 4 5 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v3 = dimacs.variable(3).unwrap();
+    let v1 = dimacs.variable(1).unwrap();
+
+    let config = SolverConfig { order_heuristic : OrderHeuristic::JeroslowWang, ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    assert_eq!(next_decision(&mut solver.env), Some(v3.to_negative_literal()));
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let config = SolverConfig { order_heuristic : OrderHeuristic::OccurrenceCount, ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    assert_eq!(next_decision(&mut solver.env), Some(v1.to_positive_literal()));
+}
+
+#[test]
+fn test_order_heuristics_agree() {
+    for heuristic in &[OrderHeuristic::EncounterOrder, OrderHeuristic::OccurrenceCount, OrderHeuristic::JeroslowWang] {
+        let (clauses, next_var) = pigeonhole(4, 3);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { order_heuristic : *heuristic, ..SolverConfig::default() };
+        assert_eq!(Solver::new(clauses, next_var, config).solve(), core::Result::Unsat);
+    }
+}
+
+#[test]
+fn test_audit_watches() {
+    let (clauses, next_var) = pigeonhole(4, 3);
+    let clauses = to_clauses(clauses);
+    let mut solver = Solver::new(clauses, next_var, SolverConfig::default());
+    assert!(solver.audit_watches().is_empty());
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(solver.audit_watches().is_empty());
+
+    // Simulate a clause being watched a third time (before solving, which may
+    // shorten the clause)
+    let mut solver = pigeonhole_solver(4, 3);
+    let cid = ClauseId(0);
+    let cl = solver.env.problem.clause(cid);
+    let unwatched = cl[2].negate();
+    solver.env.solver_state.watchlist[unwatched].insert(cid);
+    assert_eq!(solver.audit_watches(), vec![(unwatched, cid)]);
+}
+
+#[test]
+fn test_intern_reserves_capacity() {
+    let input = "p cnf 100 4\n1 2 0\n-1 3 0\n-3 -2 0\n50 100 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    assert_eq!(dimacs.clauses.capacity(), dimacs.num_declared_clauses as usize);
+
+    let numbered_clauses = intern_clauses(dimacs.clauses);
+    assert_eq!(numbered_clauses.capacity(), numbered_clauses.len());
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert!(solver.env.solver_state.decision_stack.capacity() >= dimacs.next_var.as_index());
+}
+
+#[cfg(test)]
+fn pigeonhole_solver(pigeons : usize, holes : usize) -> Solver {
+    let (clauses, next_var) = pigeonhole(pigeons, holes);
+    Solver::new(to_clauses(clauses), next_var, SolverConfig::default())
+}
+
+/// Make a problem clause of each list of literals
+#[cfg(test)]
+fn to_clauses(clauses : impl Iterator<Item = Vec<Literal>>) -> Vec<Clause> {
+    clauses.map(|lits| Clause::new(ClauseHeader::new(ClauseId(0), lits.len(), 0), lits)).collect()
+}
+
+/// The watchlists that `initialize_watchlist` would build from scratch for the
+/// current clause database
+#[cfg(test)]
+fn rebuild_watchlist(env : &Env) -> TaggedVec<Literal, BTreeSet<ClauseId>> {
+    let mut watchlist = TaggedVec::new();
+    initialize_watchlist(&env.next_var, &env.problem, &mut watchlist);
+    watchlist
+}
+
+/// Check that the incrementally maintained watchlists are exactly those of a
+/// rebuild from scratch (unlike `Solver::audit_watches`, this also catches
+/// missing watches)
+#[cfg(test)]
+fn assert_watchlist_consistent(env : &Env) {
+    let expected = rebuild_watchlist(env);
+    let actual = &env.solver_state.watchlist;
+    assert_eq!(actual.len(), expected.len());
+    for ix in 0..expected.len() {
+        let lit = Literal::from_index(ix);
+        assert_eq!(actual[lit], expected[lit], "Watchlist of {} differs from a rebuild", lit);
+    }
+}
+
+#[test]
+fn test_learned_clauses_are_kept() {
+    let mut solver = pigeonhole_solver(4, 3);
+    let num_problem_clauses = solver.num_problem_clauses();
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    // Only simplification at level 0 can remove problem clauses, and it does
+    // not reorder the rest
+    assert!(solver.num_problem_clauses() <= num_problem_clauses);
+    assert!(solver.num_learned_clauses() > 0);
+    assert!(solver.num_learned_clauses() <= solver.env.solver_state.statistics.learned_clauses);
+    for (ix, cl) in solver.env.problem.iter().enumerate() {
+        assert_eq!(cl.is_learned(), ix >= solver.num_problem_clauses());
+    }
+}
+
+#[test]
+fn test_learn_hook_drop() {
+    for (pigeons, expected) in &[(4, core::Result::Unsat), (3, core::Result::Sat)] {
+        let mut solver = pigeonhole_solver(*pigeons, 3);
+        solver.set_learn_hook(Box::new(|_| LearnAction::Drop));
+        assert_eq!(solver.solve(), *expected);
+        assert_eq!(solver.num_learned_clauses(), 0);
+    }
+}
+
+#[test]
+fn test_learn_hook_inspects_clauses() {
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut solver = pigeonhole_solver(4, 3);
+    let hook_seen = seen.clone();
+    solver.set_learn_hook(Box::new(move |lits| {
+        hook_seen.lock().unwrap().push(lits.len());
+        LearnAction::Replace(lits.to_vec())
+    }));
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    let seen = seen.lock().unwrap();
+    assert!(!seen.is_empty());
+    assert!(seen.iter().all(|len| *len > 0));
+}
+
+#[test]
+fn test_write_model_wraps_and_checks() {
+    // A chain of implications over sparse (and out of order) DIMACS variables,
+    // so that the model spans several lines and internal numbering differs
+    let mut input = String::from("p cnf 2000 100\n");
+    for i in (1..=100).rev() {
+        input.push_str(&format!("-{} {} 0\n", i * 20, (i + 1) * 19));
+    }
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(&input).unwrap();
+    let dimacs_variables = dimacs.dimacs_variables();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    solver.set_dimacs_variables(dimacs_variables);
+
+    let mut out = Vec::new();
+    solver.write_model(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.lines().count() > 1);
+    assert!(out.lines().all(|line| line.starts_with("v ") && line.len() <= MODEL_LINE_WIDTH));
+    assert!(out.ends_with(" 0\n"));
+
+    let model = crate::satir::parse::model::parse_model(&out).unwrap();
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(&input).unwrap();
+    assert_eq!(model.len(), dimacs.next_var.as_index());
+    let assignment = dimacs.assignment_from_model(&model);
+    assert!(crate::satir::clause::evaluate(&dimacs.clauses, &assignment));
+}
+
+#[test]
+fn test_write_model_dimacs_numbering() {
+    // Without names, the writer uses the numbering of the parser for formulas
+    // that mention every variable
+    let input = "p cnf 3 3\n-3 0\n3 1 0\n-1 -2 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let mut out = Vec::new();
+    solver.write_model(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "v 1 -2 -3 0\n");
+    let lits : Vec<i32> = solver.model().unwrap().literals().map(|l| l.to_dimacs()).collect();
+    assert_eq!(lits, vec![1, -2, -3]);
+}
+
+#[test]
+fn test_restart_keeps_level_zero() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 3\n1 0\n-1 2 0\n3 4 -2 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    let vars = test_variables(4);
+    let env = &mut solver.env;
+    assert!(propagate_units(env) == PropagateResult::NoConflict);
+    add_learned_clause(env, vec![vars[2].to_positive_literal(), vars[3].to_positive_literal()]);
+    decide(env, vars[2].to_negative_literal(), false);
+    assert!(propagate_units(env) == PropagateResult::NoConflict);
+    assert_eq!(env.solver_state.assignment.value_of(vars[3]), Value::LIFTED_TRUE);
+
+    solver.restart();
+    let state = &solver.env.solver_state;
+    assert_eq!(state.decision_level(), 0);
+    assert!(state.propagation_queue.is_empty());
+    assert_eq!(state.assignment.value_of(vars[0]), Value::LIFTED_TRUE);
+    assert_eq!(state.assignment.value_of(vars[1]), Value::LIFTED_TRUE);
+    assert!(state.assignment.value_of(vars[2]).is_unassigned());
+    assert!(state.assignment.value_of(vars[3]).is_unassigned());
+    assert_eq!(solver.num_learned_clauses(), 1);
+    assert!(solver.audit_watches().is_empty());
+    assert_eq!(solver.solve(), core::Result::Sat);
+}
+
+#[test]
+fn test_restart_before_solve() {
+    let mut solver = pigeonhole_solver(4, 3);
+    solver.restart();
+    assert_eq!(solver.solve(), core::Result::Unsat);
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 2 2\n1 0\n-1 -2 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    solver.restart();
+    assert_eq!(solver.env.solver_state.propagation_queue.len(), 1);
+}
+
+#[test]
+fn test_solve_empty_problem() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 0 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let mut out = Vec::new();
+    solver.write_model(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "v 0\n");
+}
+
+#[test]
+fn test_max_learned_length() {
+    for max_len in 1..4 {
+        let (clauses, next_var) = pigeonhole(5, 4);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { max_learned_length : Some(max_len), ..SolverConfig::default() };
+        let mut solver = Solver::new(clauses, next_var, config);
+        assert_eq!(solver.solve(), core::Result::Unsat);
+        let problem = &solver.env.problem;
+        for ix in solver.num_problem_clauses()..problem.len() {
+            assert!(problem.clause(ClauseId(ix as i64)).lit_count() <= max_len);
+        }
+    }
+}
+
+#[test]
+fn test_clauses_with() {
+    let input = "p cnf 4 5\n1 -2 0\n2 3 0\n-1 -3 4 0\n4 0\n3 -3 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v1 = dimacs.variable(1).unwrap();
+    let v2 = dimacs.variable(2).unwrap();
+    let v3 = dimacs.variable(3).unwrap();
+    let v4 = dimacs.variable(4).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.clauses_with(v1).collect::<Vec<_>>(), vec![ClauseId(0), ClauseId(2)]);
+    // The unit clause is folded into the initial assignment
+    assert_eq!(solver.clauses_with(v4).collect::<Vec<_>>(), vec![ClauseId(2)]);
+
+    let cid = add_learned_clause(&mut solver.env, vec![v3.to_positive_literal(), v1.to_negative_literal()]);
+    assert_eq!(solver.clauses_with(v1).collect::<Vec<_>>(), vec![ClauseId(0), ClauseId(2), cid]);
+    assert_eq!(solver.clauses_with(v2).collect::<Vec<_>>(), vec![ClauseId(0), ClauseId(1)]);
+    // The tautology is removed by preprocessing
+    assert_eq!(solver.clauses_with(v3).collect::<Vec<_>>(), vec![ClauseId(1), ClauseId(2), cid]);
+}
+
+#[test]
+fn test_activity_bumping_stays_finite() {
+    let mut solver = pigeonhole_solver(4, 3);
+    let vars = test_variables(3);
+    let env = &mut solver.env;
+    let low = add_learned_clause(env, vec![vars[0].to_positive_literal(), vars[1].to_positive_literal()]);
+    let high = add_learned_clause(env, vec![vars[1].to_positive_literal(), vars[2].to_positive_literal()]);
+
+    // Push everything to the brink of the limits, where a naive bump (or
+    // decay) would overflow
+    env.solver_state.clause_increment = CLAUSE_ACTIVITY_LIMIT * 0.9999;
+    for (cid, activity) in &[(low, CLAUSE_ACTIVITY_LIMIT * 0.5), (high, CLAUSE_ACTIVITY_LIMIT * 0.9)] {
+        let mut cl = env.problem.clause_mut(*cid);
+        cl.scale_activity(0.0);
+        cl.bump_activity(*activity);
+    }
+    env.solver_state.variable_increment = VARIABLE_ACTIVITY_LIMIT * 0.9999;
+    env.solver_state.variable_activity[vars[0]] = OrderedFloat(VARIABLE_ACTIVITY_LIMIT * 0.5);
+    env.solver_state.variable_activity[vars[1]] = OrderedFloat(VARIABLE_ACTIVITY_LIMIT * 0.9);
+    for _ in 0..100 {
+        bump_clause_activity(env, high);
+        decay_clause_activity(env);
+        bump_variable_activity(&mut env.solver_state, vars[1]);
+        decay_variable_activity(&mut env.solver_state);
+    }
+
+    assert!(env.solver_state.clause_increment.is_finite());
+    assert!(env.problem.clause(high).activity().is_finite());
+    assert!(env.problem.clause(low).activity() < env.problem.clause(high).activity());
+    assert!(env.solver_state.variable_increment.is_finite());
+    let activity = &env.solver_state.variable_activity;
+    assert!(activity.iter().all(|a| a.0.is_finite()));
+    assert!(activity[vars[0]] < activity[vars[1]]);
+    assert!(activity[vars[2]] <= activity[vars[0]]);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+}
+
+#[test]
+fn test_pinned_clause_survives_deletion() {
+    let mut solver = pigeonhole_solver(4, 3);
+    let vars = test_variables(5);
+    let env = &mut solver.env;
+    let lits : Vec<Vec<Literal>> = (0..3).map(|ix| {
+        vec![vars[ix].to_positive_literal(), vars[ix + 1].to_positive_literal(), vars[ix + 2].to_negative_literal()]
+    }).collect();
+    for clause_lits in lits.iter() {
+        add_learned_clause(env, clause_lits.clone());
+    }
+    let pinned = ClauseId(env.num_problem_clauses as i64 + 1);
+    env.problem.clause_mut(pinned).pin();
+
+    reduce_learned_clauses(env, 1.0);
+    assert_eq!(env.num_learned_clauses(), 1);
+    let survivor = ClauseId(env.num_problem_clauses as i64);
+    assert!(env.problem.clause(survivor).is_pinned());
+    assert_eq!(env.problem.clause(survivor).literals(), &lits[1][..]);
+    assert_eq!(env.problem.clause(survivor).identifier(), survivor);
+    assert!(solver.audit_watches().is_empty());
+    assert!(solver.clauses_with(vars[3]).any(|cid| cid == survivor));
+    assert!(solver.clauses_with(vars[3]).all(|cid| cid.as_index() <= survivor.as_index()));
+    assert_eq!(solver.solve(), core::Result::Unsat);
+}
+
+#[test]
+fn test_reason_clause_survives_deletion() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 7 1\n1 2 3 4 5 6 7 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    let lit = |l| Literal::from_dimacs(l).unwrap();
+    let env = &mut solver.env;
+    let reason = add_learned_clause(env, vec![lit(1), lit(-2), lit(-3)]);
+    let idle = add_learned_clause(env, vec![lit(4), lit(5), lit(6)]);
+    let active = add_learned_clause(env, vec![lit(-4), lit(-5), lit(6)]);
+    for (cid, activity) in &[(reason, 0.0), (idle, 1.0), (active, 100.0)] {
+        let mut cl = env.problem.clause_mut(*cid);
+        cl.scale_activity(0.0);
+        cl.bump_activity(*activity);
+    }
+
+    // Make the least active clause the reason for 1
+    for decision in &[2, 3] {
+        decide(env, lit(*decision), false);
+        assert!(propagate_units(env) == PropagateResult::NoConflict);
+    }
+    assert_eq!(env.solver_state.reasons[lit(1).variable()], Some(reason));
+    assert!(is_locked(env, reason));
+
+    // Half of the unlocked clauses go, starting from the least active
+    reduce_learned_clauses(env, 0.5);
+    let learned : Vec<Vec<Literal>> = env.problem.iter().filter(|cl| cl.is_learned()).map(|cl| cl.literals().to_vec()).collect();
+    assert_eq!(learned.len(), 2);
+    let reason = env.solver_state.reasons[lit(1).variable()].unwrap();
+    assert!(is_locked(env, reason));
+    let mut reason_lits = env.problem.clause(reason).literals().to_vec();
+    reason_lits.sort();
+    assert_eq!(reason_lits, vec![lit(1), lit(-2), lit(-3)]);
+    assert!(learned.contains(&vec![lit(-4), lit(-5), lit(6)]));
+    assert!(solver.audit_watches().is_empty());
+    assert_eq!(solver.solve(), core::Result::Sat);
+}
+
+#[test]
+fn test_reduce_during_search() {
+    let mut solver = pigeonhole_solver(6, 5);
+    solver.env.solver_state.max_learned = 10.0;
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(solver.env.solver_state.max_learned > 10.0);
+    assert!(solver.audit_watches().is_empty());
+}
+
+#[test]
+fn test_solve_under_assumptions() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 3 3\n1 2 0\n-1 3 0\n-3 -2 0\n").unwrap();
+    let v1 = dimacs.variable(1).unwrap();
+    let v2 = dimacs.variable(2).unwrap();
+    let v3 = dimacs.variable(3).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve_under_assumptions(&[v2.to_positive_literal(), v3.to_positive_literal()]), core::Result::Unsat);
+    assert_eq!(solver.solve_under_assumptions(&[v1.to_negative_literal()]), core::Result::Sat);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v2), Value::LIFTED_TRUE);
+    // Assumptions do not persist, and unsatisfiable assumptions do not make
+    // the formula unsatisfiable
+    assert_eq!(solver.solve_under_assumptions(&[v1.to_positive_literal()]), core::Result::Sat);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v3), Value::LIFTED_TRUE);
+    assert_eq!(solver.solve(), core::Result::Sat);
+}
+
+#[test]
+fn test_assumptions_with_chronological_backtracking() {
+    let mut solver = pigeonhole_solver(3, 3);
+    solver.set_learn_hook(Box::new(|_| LearnAction::Drop));
+    let vars = test_variables(4);
+    // Pigeons 0 and 1 both in hole 0
+    let assumptions = [vars[0].to_positive_literal(), vars[3].to_positive_literal()];
+    assert_eq!(solver.solve_under_assumptions(&assumptions), core::Result::Unsat);
+    assert_eq!(solver.solve_under_assumptions(&assumptions[..1]), core::Result::Sat);
+}
+
+#[test]
+fn test_backbone() {
+    // 1 and -3 are forced (without unit clauses); 5 and 6 are not
+    let input = "p cnf 6 5\n1 2 0\n1 -2 0\n-3 4 0\n-3 -4 0\n5 6 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let expected = vec![dimacs.variable(1).unwrap().to_positive_literal(),
+                        dimacs.variable(3).unwrap().to_negative_literal()];
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.backbone(), expected);
+    // The backbone is recorded at level 0
+    assert_eq!(solver.backbone(), expected);
+
+    let mut solver = pigeonhole_solver(4, 3);
+    assert!(solver.backbone().is_empty());
+}
+
+#[test]
+fn test_phase_directive() {
+    for (directive, polarity) in &[("", Polarity::Positive), ("c phase 4 0\n", Polarity::Negative), ("c phase 4 1\n", Polarity::Positive)] {
+        let input = format!("{}p cnf 4 2\nc phase 3 0\n4 -3 0\n3 -4 0\n", directive);
+        let dimacs = crate::satir::parse::dimacs::parse_dimacs(&input).unwrap();
+        let v4 = dimacs.variable(4).unwrap();
+        let phases = dimacs.phases.clone();
+        let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+        for (v, phase) in phases {
+            solver.set_phase(v, phase);
+        }
+
+        // Make the named variable the first decision
+        solver.env.solver_state.variable_order.push(v4, OrderedFloat(1000.0));
+        let first = next_decision(&mut solver.env).unwrap();
+        assert_eq!(first.variable(), v4);
+        assert_eq!(first.polarity(), *polarity);
+    }
+}
+
+#[test]
+fn test_default_phase() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 2 1\n1 2 0\n").unwrap();
+    let config = SolverConfig { default_phase : Polarity::Negative, ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    assert_eq!(next_decision(&mut solver.env).unwrap().polarity(), Polarity::Negative);
+}
+
+#[test]
+fn test_luby_term() {
+    let terms : Vec<u64> = (0..15).map(luby_term).collect();
+    assert_eq!(terms, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+}
+
+#[test]
+fn test_luby() {
+    let terms : Vec<u64> = luby().take(15).collect();
+    assert_eq!(terms, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    // Each complete subsequence of length 2^k - 1 ends with 2^(k - 1)
+    for k in 1..12 {
+        assert_eq!(luby().nth((1 << k) - 2), Some(1 << (k - 1)));
+    }
+}
+
+#[test]
+fn test_restarts_without_learning_terminate() {
+    // Restarting after every conflict without learning anything would repeat
+    // the same decisions forever
+    let config = SolverConfig {
+        restart_policy : RestartPolicy::Geometric { first : 1, factor : 1.0 },
+        trace : true,
+        ..SolverConfig::default()
+    };
+    for (pigeons, expected) in &[(4, core::Result::Unsat), (3, core::Result::Sat)] {
+        let (clauses, next_var) = pigeonhole(*pigeons, 3);
+        let clauses = to_clauses(clauses);
+        let mut solver = Solver::new(clauses, next_var, config.clone());
+        solver.set_learn_hook(Box::new(|_| LearnAction::Drop));
+        assert_eq!(solver.solve(), *expected);
+        assert!(!solver.trace().lines().any(|line| line == "restart"));
+    }
+}
+
+#[test]
+fn test_restarts() {
+    let (clauses, next_var) = pigeonhole(6, 5);
+    let clauses = to_clauses(clauses);
+    let config = SolverConfig { restart_policy : RestartPolicy::Luby { unit : 1 }, trace : true, ..SolverConfig::default() };
+    let mut solver = Solver::new(clauses, next_var, config);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(solver.trace().lines().any(|line| line == "restart"));
+}
+
+#[test]
+fn test_restart_count() {
+    // Restart after every conflict (unless nothing was learned since the last
+    // restart)
+    let policy = RestartPolicy::Geometric { first : 1, factor : 1.0 };
+    let (clauses, next_var) = pigeonhole(6, 5);
+    let clauses = to_clauses(clauses);
+    let config = SolverConfig { restart_policy : policy, seed : Some(7), trace : true, ..SolverConfig::default() };
+    let mut solver = Solver::new(clauses.clone(), next_var, config);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    let stats = solver.stats();
+    let traced = solver.trace().lines().filter(|line| *line == "restart").count();
+    let skipped = solver.trace().lines().filter(|line| *line == "restart skipped").count();
+    assert!(stats.restarts > 0);
+    assert_eq!(stats.restarts, traced);
+    // Every conflict ends a restart interval
+    assert!(stats.conflicts >= stats.restarts + skipped);
+
+    let config = SolverConfig { restart_policy : policy, seed : Some(7), ..SolverConfig::default() };
+    assert_eq!(solve_with_stats(clauses.clone(), next_var, config), (core::Result::Unsat, stats));
+    let config = SolverConfig { restart_policy : RestartPolicy::Never, ..SolverConfig::default() };
+    assert_eq!(solve_with_stats(clauses, next_var, config).1.restarts, 0);
+}
+
+#[test]
+fn test_reset_statistics() {
+    let vars = test_variables(16);
+    let assumptions = [[vars[0].to_positive_literal()], [vars[0].to_negative_literal()]];
+    let solver = || {
+        let (clauses, next_var) = pigeonhole(4, 4);
+        let clauses = to_clauses(clauses);
+        Solver::new(clauses, next_var, SolverConfig { seed : Some(3), ..SolverConfig::default() })
+    };
+
+    let mut cumulative = solver();
+    for query in assumptions.iter() {
+        assert_eq!(cumulative.solve_under_assumptions(query), core::Result::Sat);
+    }
+    let total = cumulative.stats();
+
+    let mut isolated = solver();
+    assert_eq!(isolated.solve_under_assumptions(&assumptions[0]), core::Result::Sat);
+    let first = isolated.stats();
+    isolated.reset_statistics();
+    assert_eq!(isolated.stats(), empty_statistics(3));
+    assert_eq!(isolated.solve_under_assumptions(&assumptions[1]), core::Result::Sat);
+    let second = isolated.stats();
+    assert!(first.decisions > 0 && second.decisions > 0);
+    assert_eq!(first.decisions + second.decisions, total.decisions);
+    assert_eq!(first.propagations + second.propagations, total.propagations);
+    assert_eq!(first.conflicts + second.conflicts, total.conflicts);
+}
+
+#[test]
+fn test_stats_to_json() {
+    let mut solver = pigeonhole_solver(4, 3);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    let stats = solver.stats();
+    let json = stats.to_json();
+    let fields : BTreeMap<String, u64> = json.strip_prefix('{').unwrap().strip_suffix('}').unwrap().split(", ").map(|field| {
+        let (name, value) = field.split_once(": ").unwrap();
+        (name.strip_prefix('"').unwrap().strip_suffix('"').unwrap().to_string(), value.parse().unwrap())
+    }).collect();
+    let keys : Vec<&str> = fields.keys().map(|k| k.as_str()).collect();
+    assert_eq!(keys, vec!["conflicts", "decisions", "learned_clauses", "order_rebuilds", "propagations", "restarts", "seed"]);
+    assert_eq!(fields["conflicts"], stats.conflicts as u64);
+    assert_eq!(fields["seed"], stats.seed);
+}
+
+#[test]
+fn test_restart_blocking() {
+    let policy = RestartPolicy::Geometric { first : 1, factor : 1.0 };
+    let run = |restart_blocking| {
+        let (clauses, next_var) = pigeonhole(6, 5);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { restart_policy : policy, restart_blocking, seed : Some(5), trace : true, ..SolverConfig::default() };
+        let mut solver = Solver::new(clauses, next_var, config);
+        assert_eq!(solver.solve(), core::Result::Unsat);
+        let blocked = solver.trace().lines().filter(|line| *line == "restart blocked").count();
+        (solver.stats().restarts, blocked)
+    };
+
+    let (unblocked, none_blocked) = run(None);
+    assert!(unblocked > 0);
+    assert_eq!(none_blocked, 0);
+
+    // Any trail is longer than no trail at all, so every restart is blocked
+    assert_eq!(run(Some(0.0)).0, 0);
+
+    // Restarts are only blocked when the trail is longer than usual
+    let (restarts, blocked) = run(Some(1.0));
+    assert!(restarts > 0 && blocked > 0);
+
+    // A large factor never blocks
+    assert_eq!(run(Some(1000.0)), (unblocked, 0));
+}
+
+#[test]
+fn test_interrupt() {
+    let mut solver = pigeonhole_solver(4, 3);
+    let handle = solver.interrupt_handle();
+    handle.interrupt();
+    assert_eq!(solver.solve(), core::Result::Unknown);
+    // The interrupt only applies once
+    assert_eq!(solver.solve(), core::Result::Unsat);
+}
+
+#[test]
+fn test_normalized_watchlists() {
+    let vars = test_variables(4);
+    let lits = |ixs : &[usize]| -> Vec<Literal> { ixs.iter().map(|ix| vars[*ix].to_positive_literal()).collect() };
+    let watchlists = |clause_lits : Vec<Vec<Literal>>, normalize_clauses : bool| {
+        let clauses = to_clauses(clause_lits.into_iter());
+        let config = SolverConfig { normalize_clauses, ..SolverConfig::default() };
+        let solver = Solver::new(clauses, vars[3].next_variable(), config);
+        solver.env.solver_state.watchlist.iter().cloned().collect::<Vec<_>>()
+    };
+
+    let input1 = vec![lits(&[0, 1, 2]), lits(&[3, 2, 1])];
+    let input2 = vec![lits(&[2, 1, 0]), lits(&[1, 3, 2])];
+    assert_eq!(watchlists(input1.clone(), true), watchlists(input2.clone(), true));
+    assert_ne!(watchlists(input1, false), watchlists(input2, false));
+}
+
+#[test]
+fn test_solve_full() {
+    let input = "p cnf 4 4\n1 2 0\n-1 3 0\n-3 -2 0\n2 4 -3 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let outcome = solve_full(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(outcome.result, core::Result::Sat);
+    let model = outcome.model.unwrap();
+    assert_eq!(model.len(), dimacs.next_var.as_index());
+    assert_eq!(model.literals().count(), model.len());
+    for lit in model.literals() {
+        assert_eq!(model.value(lit.variable()), lit.polarity() == Polarity::Positive);
+    }
+    let mut assignment = TaggedVec::new();
+    assignment.ensure_index(&dimacs.next_var, Value::UNASSIGNED);
+    for lit in model.literals() {
+        assignment[lit.variable()] = lit.satisfy();
+    }
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    assert!(crate::satir::clause::evaluate(&dimacs.clauses, &assignment));
+    assert!(outcome.stats.decisions > 0);
+    assert!(outcome.stats.propagations > 0);
+    assert!(outcome.elapsed > std::time::Duration::from_secs(0));
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 1 2\n1 0\n-1 0\n").unwrap();
+    let outcome = solve_full(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(outcome.result, core::Result::Unsat);
+    assert!(outcome.model.is_none());
+}
+
+#[test]
+fn test_model_only_after_sat() {
+    let mut solver = pigeonhole_solver(3, 3);
+    assert!(solver.model().is_none());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert!(solver.model().is_some());
+    solver.restart();
+    assert!(solver.model().is_none());
+    let vars = test_variables(4);
+    assert_eq!(solver.solve_under_assumptions(&[vars[0].to_positive_literal(), vars[3].to_positive_literal()]), core::Result::Unsat);
+    assert!(solver.model().is_none());
+}
+
+#[cfg(test)]
+fn watch_count(solver : &Solver, cid : ClauseId) -> usize {
+    solver.env.solver_state.watchlist.iter().filter(|cids| cids.contains(&cid)).count()
+}
+
+#[test]
+fn test_add_blocking_clauses() {
+    // Every model of three pigeons in three holes is a permutation
+    let mut solver = pigeonhole_solver(3, 3);
+    let mut num_models = 0;
+    while solver.solve() == core::Result::Sat {
+        num_models += 1;
+        // Falsified by the current assignment
+        let block : Vec<Literal> = solver.model().unwrap().literals().map(|l| l.negate()).collect();
+        let cid = ClauseId(solver.env.problem.len() as i64);
+        solver.add_clause(&block);
+        assert!(solver.model().is_none());
+        // Blocking the last model can falsify the clause at level 0, in which
+        // case it is not stored at all
+        assert_eq!(watch_count(&solver, cid), if solver.known_unsat { 0 } else { 2 });
+        assert!(solver.audit_watches().is_empty());
+    }
+    assert_eq!(num_models, 6);
+}
+
+#[test]
+fn test_add_unit_and_empty_clauses() {
+    let mut solver = pigeonhole_solver(3, 3);
+    let vars = test_variables(1);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let lit = if solver.model().unwrap().value(vars[0]) { vars[0].to_negative_literal() } else { vars[0].to_positive_literal() };
+    solver.add_clause(&[lit]);
+    assert_eq!(solver.env.solver_state.decision_level(), 0);
+    assert_eq!(solver.env.solver_state.value_of(lit), Value::LIFTED_TRUE);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert_eq!(solver.env.solver_state.value_of(lit), Value::LIFTED_TRUE);
+
+    solver.add_clause(&[]);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+}
+
+#[test]
+fn test_add_clause_mid_search() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 1\n1 2 3 4 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let (v1, v2, v3, v4) = (v(1), v(2), v(3), v(4));
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    decide(&mut solver.env, v1.to_negative_literal(), false);
+    decide(&mut solver.env, v2.to_negative_literal(), false);
+
+    // Unit: the implied literal belongs at the level of the falsified literal
+    let unit = ClauseId(solver.env.problem.len() as i64);
+    solver.add_clause(&[v1.to_positive_literal(), v3.to_positive_literal()]);
+    assert_eq!(solver.env.solver_state.decision_level(), 1);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v3), Value::LIFTED_TRUE);
+    assert_eq!(solver.env.solver_state.reasons[v3], Some(unit));
+    assert_eq!(watch_count(&solver, unit), 2);
+
+    // Satisfied: nothing is undone
+    let satisfied = ClauseId(solver.env.problem.len() as i64);
+    solver.add_clause(&[v1.to_negative_literal(), v4.to_positive_literal()]);
+    assert_eq!(solver.env.solver_state.decision_level(), 1);
+    assert_eq!(watch_count(&solver, satisfied), 2);
+
+    // Tautologies are dropped
+    let num_clauses = solver.env.problem.len();
+    solver.add_clause(&[v4.to_negative_literal(), v4.to_positive_literal()]);
+    assert_eq!(solver.env.problem.len(), num_clauses);
+
+    assert!(solver.audit_watches().is_empty());
+    assert_watchlist_consistent(&solver.env);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let model = solver.model().unwrap();
+    assert!(model.value(v1) || model.value(v3));
+}
+
+#[test]
+fn test_import_clause() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 2\n1 2 3 4 0\n-3 4 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let (v1, v2, v3, v4) = (v(1), v(2), v(3), v(4));
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    decide(&mut solver.env, v1.to_positive_literal(), false);
+    decide(&mut solver.env, v2.to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+
+    // Unit under the current assignment: 3 is implied at the level of 2, and
+    // propagating it implies 4
+    let imported = ClauseId(solver.env.problem.len() as i64);
+    let num_problem_clauses = solver.num_problem_clauses();
+    solver.import_clause(&[v1.to_negative_literal(), v2.to_negative_literal(), v3.to_positive_literal()]);
+    assert!(solver.env.problem.clause(imported).is_learned());
+    assert_eq!((solver.num_learned_clauses(), solver.num_problem_clauses()), (1, num_problem_clauses));
+    assert_eq!(solver.env.solver_state.reasons[v3], Some(imported));
+    assert_eq!(solver.level_of(v3), 2);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v4), Value::LIFTED_TRUE);
+    assert_watchlist_consistent(&solver.env);
+
+    // Falsified under the current assignment (with both literals at level 2):
+    // the solver backjumps below them
+    solver.import_clause(&[v2.to_negative_literal(), v4.to_negative_literal()]);
+    assert_eq!(solver.env.solver_state.decision_level(), 1);
+    assert!(solver.env.solver_state.assignment.value_of(v2).is_unassigned());
+    assert_watchlist_consistent(&solver.env);
+
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let model = solver.model().unwrap();
+    assert!(!model.value(v2) || !model.value(v4));
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let vars = test_variables(16);
+    let mut solver = pigeonhole_solver(4, 4);
+    decide(&mut solver.env, vars[0].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    let saved = solver.snapshot();
+    let values : Vec<Value> = vars.iter().map(|v| solver.env.solver_state.assignment.value_of(*v)).collect();
+    let levels : Vec<usize> = vars.iter().map(|v| solver.level_of(*v)).collect();
+
+    // Deciding (and propagating) more is undone exactly
+    decide(&mut solver.env, vars[5].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    decide(&mut solver.env, vars[10].to_positive_literal(), false);
+    solver.restore(saved.clone());
+    assert_eq!(solver.snapshot(), saved);
+    assert_eq!(vars.iter().map(|v| solver.env.solver_state.assignment.value_of(*v)).collect::<Vec<_>>(), values);
+    assert_eq!(vars.iter().map(|v| solver.level_of(*v)).collect::<Vec<_>>(), levels);
+    assert_eq!(solver.env.solver_state.decision_level(), 1);
+    assert_watchlist_consistent(&solver.env);
+
+    // After taking another branch, the saved assignment is replayed (and
+    // queued for propagation, which finds nothing new)
+    cancel_until(&mut solver.env, 0);
+    decide(&mut solver.env, vars[0].to_negative_literal(), false);
+    decide(&mut solver.env, vars[1].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    solver.restore(saved.clone());
+    assert_eq!(vars.iter().map(|v| solver.env.solver_state.assignment.value_of(*v)).collect::<Vec<_>>(), values);
+    assert!(!solver.env.solver_state.propagation_queue.is_empty());
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_eq!(solver.snapshot(), saved);
+    assert_watchlist_consistent(&solver.env);
+    assert_eq!(solver.solve(), core::Result::Sat);
+}
+
+#[test]
+fn test_dyn_sat_solver() {
+    let configs = [SolverConfig::default(),
+                   SolverConfig { packed_assignment : true, learning_scheme : LearningScheme::LastUip, ..SolverConfig::default() }];
+    let vars = test_variables(2);
+    for config in configs.iter() {
+        let clauses = vec![vec![vars[0].to_positive_literal(), vars[1].to_positive_literal()]];
+        let clauses = to_clauses(clauses.into_iter());
+        let mut solver : Box<dyn SatSolver> = Box::new(Solver::new(clauses, Variable::from_index(2), config.clone()));
+        assert_eq!(solver.solve_under_assumptions(&[vars[0].to_negative_literal()]), core::Result::Sat);
+        assert!(solver.model().unwrap().value(vars[1]));
+
+        solver.add_clause(&[vars[1].to_negative_literal()]);
+        assert_eq!(solver.solve(), core::Result::Sat);
+        assert!(solver.model().unwrap().value(vars[0]));
+        assert_eq!(solver.solve_under_assumptions(&[vars[0].to_negative_literal()]), core::Result::Unsat);
+        assert!(solver.model().is_none());
+        assert!(solver.stats().propagations > 0);
+    }
+}
+
+#[test]
+fn test_minimal_model() {
+    // 1 is a unit, which implies 5 and satisfies the clauses of 2, 3, and 4,
+    // leaving them as don't-cares
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 5 4\n1 0\n1 2 3 0\n1 -4 0\n-1 5 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.minimal_model(), None);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert_eq!(solver.minimal_model(), Some(vec![v(1).to_positive_literal(), v(5).to_positive_literal()]));
+
+    // Without units, the result still satisfies every clause, and every
+    // literal in it is needed by some clause
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 6 4\n1 2 0\n-1 3 0\n3 4 5 0\n-6 2 3 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let model : Vec<Literal> = solver.model().unwrap().literals().collect();
+    let minimal = solver.minimal_model().unwrap();
+    assert!(minimal.len() < dimacs.next_var.as_index());
+    assert!(minimal.iter().all(|l| model.contains(l)));
+    let satisfies = |lits : &[Literal]| dimacs.clauses.iter().all(|cl| cl.literals().iter().any(|l| lits.contains(l)));
+    assert!(satisfies(&minimal));
+    for ix in 0..minimal.len() {
+        let mut fewer = minimal.clone();
+        fewer.remove(ix);
+        assert!(!satisfies(&fewer), "{} is not needed", minimal[ix]);
+    }
+}
+
+#[test]
+fn test_clause_dimacs() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 40 2\n-30 7 12 0\n12 -40 0\n").unwrap();
+    let dimacs_variables = dimacs.dimacs_variables();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    // Without names, variables are numbered densely
+    assert_eq!(solver.clause_dimacs(ClauseId(0)), vec![-3, 1, 2]);
+    solver.set_dimacs_variables(dimacs_variables);
+    assert_eq!(solver.clause_dimacs(ClauseId(0)), vec![-30, 7, 12]);
+    assert_eq!(solver.clause_dimacs(ClauseId(1)), vec![12, -40]);
+}
+
+#[test]
+fn test_incremental_watchlist() {
+    let vars = test_variables(16);
+    let mut solver = pigeonhole_solver(4, 4);
+    assert_watchlist_consistent(&solver.env);
+
+    // Propagation moves watches
+    decide(&mut solver.env, vars[0].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    decide(&mut solver.env, vars[5].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_watchlist_consistent(&solver.env);
+
+    // Clauses added one at a time are watched as they are added
+    add_learned_clause(&mut solver.env, vec![vars[10].to_positive_literal(), vars[11].to_positive_literal(), vars[14].to_positive_literal()]);
+    assert_watchlist_consistent(&solver.env);
+    solver.add_clause(&[vars[15].to_negative_literal(), vars[14].to_negative_literal()]);
+    assert_watchlist_consistent(&solver.env);
+
+    // Deleting clauses renumbers the rest
+    let num_clauses = solver.env.problem.len();
+    reduce_learned_clauses(&mut solver.env, 1.0);
+    assert_eq!(solver.env.problem.len(), num_clauses - 1);
+    assert_watchlist_consistent(&solver.env);
+
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert_watchlist_consistent(&solver.env);
+
+    solver.add_clauses(vec![
+        vec![vars[0].to_negative_literal(), vars[5].to_negative_literal()],
+        vec![vars[3].to_positive_literal(), vars[7].to_positive_literal(), vars[11].to_positive_literal()]
+    ]);
+    assert_watchlist_consistent(&solver.env);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert_watchlist_consistent(&solver.env);
+}
+
+#[test]
+fn test_max_decision_depth_not_hit() {
+    let vars = test_variables(12);
+    for seed in 0..10 {
+        let config = SolverConfig {
+            seed : Some(seed),
+            random_decision_freq : 0.2,
+            max_decision_depth : Some(12 + 2),
+            ..SolverConfig::default()
+        };
+        for (pigeons, expected) in &[(4, core::Result::Unsat), (3, core::Result::Sat)] {
+            let (clauses, next_var) = pigeonhole(*pigeons, 3);
+            let clauses = to_clauses(clauses);
+            let mut solver = Solver::new(clauses, next_var, config.clone());
+            assert_eq!(solver.solve(), *expected);
+            // Repeating an assumption opens an empty level
+            let assumptions = [vars[0].to_positive_literal(), vars[0].to_positive_literal()];
+            assert_ne!(solver.solve_under_assumptions(&assumptions), core::Result::Unknown);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "decision level 2 exceeds max_decision_depth 1")]
+fn test_max_decision_depth_diagnostic() {
+    let mut solver = pigeonhole_solver(4, 3);
+    solver.env.config.max_decision_depth = Some(1);
+    solver.solve();
+}
+
+#[test]
+fn test_compact_clauses() {
+    let mut solver = pigeonhole_solver(4, 3);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    solver.restart();
+    let env = &mut solver.env;
+    assert!(env.num_learned_clauses() > 0);
+    let deleted : Vec<bool> = (0..env.problem.len()).map(|ix| ix % 2 == 1 && !is_locked(env, ClauseId(ix as i64))).collect();
+    let survivors : Vec<Vec<Literal>> = env.problem.iter().zip(deleted.iter())
+        .filter(|(_, d)| !**d)
+        .map(|(cl, _)| cl.literals().to_vec())
+        .collect();
+    let num_learned = env.problem.iter().zip(deleted.iter()).filter(|(cl, d)| !**d && cl.is_learned()).count();
+    compact_clauses(env, &deleted);
+
+    assert_eq!(env.num_learned_clauses(), num_learned);
+    let lits : Vec<Vec<Literal>> = env.problem.iter().map(|cl| cl.literals().to_vec()).collect();
+    assert_eq!(lits, survivors);
+    for (ix, cl) in env.problem.iter().enumerate() {
+        let cid = ClauseId(ix as i64);
+        assert_eq!(cl.identifier(), cid);
+        for lit in cl.literals() {
+            assert!(env.occurrences[lit.variable()].contains(&cid));
+        }
+    }
+    assert!(env.occurrences.iter().flatten().all(|cid| cid.as_index() < env.problem.len()));
+    for ix in 0..env.problem.len() {
+        assert_eq!(watch_count(&solver, ClauseId(ix as i64)), 2);
+    }
+    assert!(solver.audit_watches().is_empty());
+    assert_ne!(solver.solve(), core::Result::Unknown);
+}
+
+/// Check watched-literal propagation against a naive fixpoint computation
+///
+/// The decisions (signed DIMACS literals) are made in order, skipping any that
+/// are already assigned.  After each, the assignment produced by propagation
+/// must match the one computed by repeatedly scanning every clause for units,
+/// and a conflict must be found exactly when some clause is falsified.
+#[cfg(test)]
+fn check_propagation(input : &str, decisions : &[i32]) {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let clauses = dimacs.clauses.clone();
+    let next_var = dimacs.next_var;
+    let to_lit = |dimacs_lit : i32| {
+        let polarity = if dimacs_lit < 0 { Polarity::Negative } else { Polarity::Positive };
+        Literal::with_polarity(dimacs.variable(dimacs_lit.unsigned_abs()).unwrap(), polarity)
+    };
+    let mut solver = Solver::new(dimacs.clauses.clone(), next_var, SolverConfig::default());
+
+    let mut decided : Vec<Literal> = Vec::new();
+    let mut pending = decisions.iter();
+    loop {
+        let conflict = solver.known_unsat || propagate_units(&mut solver.env) != PropagateResult::NoConflict;
+
+        let mut expected = TaggedVec::new();
+        expected.ensure_index(&next_var, Value::UNASSIGNED);
+        for lit in decided.iter() {
+            expected[lit.variable()] = lit.satisfy();
+        }
+        let expected_conflict = loop {
+            let mut changed = false;
+            let mut falsified = false;
+            for cl in clauses.iter() {
+                let open : Vec<Literal> = cl.literals().iter().copied()
+                    .filter(|l| l.under_value(expected[l.variable()]) != Value::LIFTED_FALSE)
+                    .collect();
+                if open.is_empty() {
+                    falsified = true;
+                } else if open.len() == 1 && expected[open[0].variable()].is_unassigned() {
+                    expected[open[0].variable()] = open[0].satisfy();
+                    changed = true;
+                }
+            }
+            if falsified || !changed {
+                break falsified;
+            }
+        };
+
+        assert_eq!(conflict, expected_conflict, "conflict after deciding {:?} in\n{}", decided, input);
+        if conflict {
+            return;
+        }
+        for ix in 0..next_var.as_index() {
+            let v = Variable::from_index(ix);
+            assert_eq!(solver.env.solver_state.assignment.value_of(v), expected[v], "{:?} after deciding {:?} in\n{}", v, decided, input);
+        }
+        assert!(solver.audit_watches().is_empty());
+
+        match pending.find(|l| solver.env.solver_state.value_of(to_lit(**l)).is_unassigned()) {
+            None => return,
+            Some(dimacs_lit) => {
+                decided.push(to_lit(*dimacs_lit));
+                decide(&mut solver.env, to_lit(*dimacs_lit), false);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_propagation_harness() {
+    let instances = [
+        // An implication chain
+        "p cnf 5 4\n-1 2 0\n-2 3 0\n-3 4 0\n-4 5 0\n",
+        // Ternary clauses, which need their watches moved
+        "p cnf 5 5\n1 2 3 0\n-1 -2 4 0\n-3 -4 5 0\n-5 1 0\n2 -4 -5 0\n",
+        // Level 0 units feeding longer clauses
+        "p cnf 4 4\n1 0\n-1 2 3 0\n-2 4 0\n-3 -4 0\n",
+        // Three pigeons in two holes
+        "p cnf 6 9\n1 2 0\n3 4 0\n5 6 0\n-1 -3 0\n-1 -5 0\n-3 -5 0\n-2 -4 0\n-2 -6 0\n-4 -6 0\n",
+        // A long clause whose watches move repeatedly
+        "p cnf 6 3\n1 2 3 4 5 6 0\n-6 -1 0\n-5 -2 0\n",
+    ];
+    let literals : [i32; 12] = [1, -1, 2, -2, 3, -3, 4, -4, 5, -5, 6, -6];
+    for input in instances.iter() {
+        let num_vars = crate::satir::parse::dimacs::parse_dimacs(input).unwrap().num_declared_variables;
+        let usable : Vec<i32> = literals.iter().copied().filter(|l| l.unsigned_abs() <= num_vars).collect();
+        // Decide in many orders and polarities
+        for start in 0..usable.len() {
+            for stride in 1..usable.len() {
+                let decisions : Vec<i32> = (0..usable.len()).map(|k| usable[(start + k * stride) % usable.len()]).collect();
+                check_propagation(input, &decisions);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "clause-heat")]
+#[test]
+fn test_clause_heat() {
+    // The first clause propagates 2 every time 1 is decided true, which the
+    // assumptions force on every call
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 3\n-1 2 0\n3 4 0\n-3 -4 1 0\n").unwrap();
+    let v1 = dimacs.variable(1).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    for _ in 0..10 {
+        assert_eq!(solver.solve_under_assumptions(&[v1.to_positive_literal()]), core::Result::Sat);
+    }
+    let heat = solver.clause_heat();
+    assert_eq!(heat[0], (ClauseId(0), 10));
+    assert!(heat.iter().skip(1).all(|(_, h)| *h < 10));
+}
+
+#[test]
+fn test_dump_partial() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 5 3\n2 0\n-2 -5 4 0\n5 3 1 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let (v1, v5) = (v(1), v(5));
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    decide(&mut solver.env, v5.to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    decide(&mut solver.env, v1.to_negative_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+
+    let mut out = Vec::new();
+    solver.dump_partial(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "c assign 2\nc assign 5\nc assign 4\nc assign -1\n");
+}
+
+#[test]
+fn test_duplicate_literals_are_removed_before_watching() {
+    // Without deduplication, the first clause would watch 1 twice, and the
+    // second would be a unit in disguise
+    let input = "p cnf 3 4\n1 1 2 0\n-2 -2 0\n-1 3 0\n3 -3 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let (v1, v2) = (v(1), v(2));
+    let solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    // The unit and the tautology are removed
+    assert_eq!(solver.num_problem_clauses(), 2);
+    assert_eq!(solver.env.problem.clause(ClauseId(0)).literals(), &[v1.to_positive_literal(), v2.to_positive_literal()]);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v2), Value::LIFTED_FALSE);
+    assert!(solver.audit_watches().is_empty());
+    assert_eq!(solve_dimacs(input), core::Result::Sat);
+
+    let clauses = vec![vec![v1.to_positive_literal(), v1.to_positive_literal()], vec![v1.to_negative_literal(), v2.to_negative_literal(), v1.to_negative_literal()], vec![v2.to_positive_literal()]];
+    assert_eq!(solve_from_iter(clauses.into_iter(), dimacs.next_var), core::Result::Unsat);
+}
+
+#[test]
+fn test_recency_bias() {
+    // Deciding 1 and then 2 leads to a conflict on 6, which is involved in the
+    // conflict but not in the learned clause (-5 -1)
+    let input = "p cnf 6 5\n-2 3 0\n-3 4 0\n-4 5 0\n-5 -1 6 0\n-5 -1 -6 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    for recency_bias in &[0.0, 2.0] {
+        let config = SolverConfig { recency_bias : *recency_bias, ..SolverConfig::default() };
+        let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, config);
+        let env = &mut solver.env;
+        decide(env, v(1).to_positive_literal(), false);
+        assert!(propagate_units(env) == PropagateResult::NoConflict);
+        decide(env, v(2).to_positive_literal(), false);
+        let conflict = match propagate_units(env) {
+            PropagateResult::Conflict(cid) => cid,
+            PropagateResult::NoConflict => panic!("Expected a conflict")
+        };
+        let before : Vec<f32> = env.solver_state.variable_activity.iter().map(|a| a.0).collect();
+        assert!(resolve_conflict(env, conflict));
+        let bump = |n| env.solver_state.variable_activity[v(n)].0 - before[v(n).as_index()];
+        if *recency_bias > 0.0 {
+            assert!(bump(5) > bump(6));
+            assert!(bump(1) > bump(6));
+            // The learned clause's variables are decided first after a restart
+            backtrack_to_root(env);
+            let next = next_decision(env).unwrap().variable();
+            assert!(next == v(1) || next == v(5));
+        } else {
+            assert_eq!(bump(5), bump(6));
+            assert_eq!(bump(1), bump(6));
+        }
+    }
+}
+
+#[test]
+fn test_value_of_dimacs() {
+    // Sparse variables, so that DIMACS and internal numbering differ
+    let input = "p cnf 9 3\n3 9 0\n-3 -9 0\n-5 9 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let dimacs_variables = dimacs.dimacs_variables();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    solver.set_dimacs_variables(dimacs_variables);
+    assert_eq!(solver.value_of_dimacs(3), None);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    for var in &[3, 5, 9] {
+        let pos = solver.value_of_dimacs(*var).unwrap();
+        assert_eq!(solver.value_of_dimacs(-var), Some(!pos));
+    }
+    assert_ne!(solver.value_of_dimacs(3), solver.value_of_dimacs(9));
+    assert_eq!(solver.value_of_dimacs(4), None);
+    assert_eq!(solver.value_of_dimacs(0), None);
+
+    let model = solver.model().unwrap();
+    let v = Variable::from_index(1);
+    assert_eq!(solver.value_of_dimacs(5), Some(model.value(v)));
+}
+
+#[test]
+fn test_simplify_at_root() {
+    // Deciding 1 false first leads to a conflict, learning the unit 1
+    let input = "p cnf 4 4\n1 2 0\n1 -2 0\n1 3 4 0\n-1 3 -4 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let original = dimacs.clauses.clone();
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    let env = &mut solver.env;
+    decide(env, v(1).to_negative_literal(), false);
+    match propagate_units(env) {
+        PropagateResult::Conflict(cid) => assert!(resolve_conflict(env, cid)),
+        PropagateResult::NoConflict => panic!("Expected a conflict")
+    }
+    assert_eq!(solver.num_problem_clauses(), 4);
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert_eq!(solver.env.solver_state.levels[v(1)], 0);
+    assert_eq!(solver.env.solver_state.assignment.value_of(v(1)), Value::LIFTED_TRUE);
+
+    // Only the last clause is left, without its false literal
+    assert_eq!(solver.num_problem_clauses(), 1);
+    assert_eq!(solver.env.problem.clause(ClauseId(0)).literals(), &[v(3).to_positive_literal(), v(4).to_negative_literal()]);
+    assert!(solver.audit_watches().is_empty());
+    let assignment : TaggedVec<Variable, Value> = solver.model().unwrap().literals().map(|l| l.satisfy()).collect();
+    assert!(crate::satir::clause::evaluate(&original, &assignment));
+}
+
+/// Assign literals at level 0 without propagating them, as if propagation had
+/// missed their consequences
+#[cfg(test)]
+fn assign_unpropagated(env : &mut Env, lits : &[Literal]) {
+    for lit in lits {
+        enqueue(&mut env.solver_state, *lit, None);
+    }
+    env.solver_state.propagation_queue.clear();
+}
+
+#[test]
+fn test_simplify_derives_empty_clause() {
+    let input = "p cnf 4 2\n1 2 3 0\n-1 3 4 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assign_unpropagated(&mut solver.env, &[v(1).to_negative_literal(), v(2).to_negative_literal(), v(3).to_negative_literal()]);
+    assert_eq!(search(&mut solver.env), core::Result::Unsat);
+    let first = dimacs.clauses[0].literals().to_vec();
+    assert_eq!(solver.final_conflict(), Some(&first[..]));
+
+    // A clause left with one literal is replaced by its (asserted) literal
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assign_unpropagated(&mut solver.env, &[v(1).to_positive_literal(), v(3).to_negative_literal()]);
+    assert!(simplify_at_root(&mut solver.env));
+    assert_eq!(solver.env.problem.len(), 0);
+    assert_eq!(solver.env.solver_state.value_of(v(4).to_positive_literal()), Value::LIFTED_TRUE);
+    assert_watchlist_consistent(&solver.env);
+    assert_eq!(solver.solve(), core::Result::Sat);
+}
+
+#[test]
+fn test_pure_literal_during_search() {
+    // 1 is pure from the start, but the first check is only after a decision.
+    // 2 occurs negatively only in the last clause, so it becomes pure once 1
+    // is decided (and 3 then occurs in no unsatisfied clause).  In encounter
+    // order, 1 is decided first.
+    let input = "p cnf 3 3\n2 3 0\n2 -3 0\n1 -2 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let v = |n| Variable::from_dimacs(n).unwrap();
+    let config = SolverConfig { trace : true, pure_literal_interval : Some(1), ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    assert_eq!(find_pure_literal(&solver.env), Some(v(1).to_positive_literal()));
+    solver.set_phase(v(2), Polarity::Negative);
+    assert_eq!(solver.solve(), core::Result::Sat);
+
+    let pure = format!("pure {}", v(2).to_positive_literal());
+    let lines : Vec<&str> = solver.trace().lines().collect();
+    // 3 is then decided normally
+    assert_eq!(lines[..2], [format!("decide {}", v(1).to_positive_literal()).as_str(), pure.as_str()]);
+    // The saved phase would have made 2 false
+    assert!(solver.model().unwrap().value(v(2)));
+}
+
+#[test]
+fn test_solve_warm() {
+    let clauses = || {
+        let (clauses, next_var) = pigeonhole(7, 7);
+        let clauses = to_clauses(clauses);
+        (clauses, next_var)
+    };
+    let satisfies = |model : &Model| {
+        clauses().0.iter().all(|cl| cl.literals().iter().any(|l| model.value(l.variable()) != l.is_negated()))
+    };
+
+    let (cls, next_var) = clauses();
+    let cold = solve_full(cls, next_var, SolverConfig::default());
+    assert_eq!(cold.result, core::Result::Sat);
+    let model = cold.model.unwrap();
+    assert!(cold.stats.conflicts > 0);
+
+    // Starting from a model, every decision agrees with it
+    let (cls, next_var) = clauses();
+    let warm = solve_warm(cls, next_var, &model);
+    assert_eq!(warm.result, core::Result::Sat);
+    assert!(satisfies(warm.model.as_ref().unwrap()));
+    assert_eq!(warm.stats.conflicts, 0);
+
+    // A bad (or short) hint is still only a hint
+    let bad = Model { values : vec![true; 3] };
+    let (cls, next_var) = clauses();
+    let outcome = solve_warm(cls, next_var, &bad);
+    assert_eq!(outcome.result, core::Result::Sat);
+    assert!(satisfies(outcome.model.as_ref().unwrap()));
+}
+
+#[test]
+fn test_order_gc_on_restart() {
+    // Asserting the hub at level 0 assigns every implied variable, but they
+    // all stay in the variable order; only the last two are unassigned
+    let num_vars = 12;
+    let vars = test_variables(num_vars + 1);
+    let free = vec![vars[num_vars - 2].to_positive_literal(), vars[num_vars - 1].to_positive_literal()];
+    let clauses = to_clauses((1..num_vars - 2)
+        .map(|i| vec![vars[0].to_negative_literal(), vars[i].to_positive_literal()])
+        .chain(std::iter::once(free)));
+
+    for order_gc_interval in &[None, Some(2)] {
+        let config = SolverConfig { order_gc_interval : *order_gc_interval, ..SolverConfig::default() };
+        let mut solver = Solver::new(clauses.clone(), vars[num_vars], config);
+        let env = &mut solver.env;
+        enqueue(&mut env.solver_state, vars[0].to_positive_literal(), None);
+        assert!(propagate_units(env) == PropagateResult::NoConflict);
+
+        let mut order_sizes = Vec::new();
+        for _ in 0..4 {
+            env.solver_state.conflicts_since_restart = u64::MAX;
+            env.solver_state.learned_since_restart = 1;
+            assert!(maybe_restart(env));
+            order_sizes.push(env.solver_state.variable_order.len());
+        }
+
+        let state = &env.solver_state;
+        let unassigned = (0..num_vars).filter(|ix| state.assignment.value_of(vars[*ix]).is_unassigned()).count();
+        assert_eq!(unassigned, 2);
+        if order_gc_interval.is_some() {
+            assert_eq!(order_sizes, vec![num_vars, unassigned, unassigned, unassigned]);
+            assert!(state.variable_order.iter().all(|(v, p)| *p == state.variable_activity[*v]));
+        } else {
+            assert_eq!(order_sizes, vec![num_vars; 4]);
+        }
+    }
+}
+
+#[test]
+fn test_add_clauses_matches_add_clause() {
+    let num_vars = 3000;
+    let vars = test_variables(num_vars + 1);
+    let mut rng = Rng::new(17);
+    let batch : Vec<Vec<Literal>> = (0..10_000).map(|ix| {
+        // Some units (which can make later clauses unit), duplicates, and
+        // tautologies
+        let len = if ix % 1000 == 0 { 1 } else { 2 + rng.below(3) };
+        (0..len).map(|_| {
+            let v = vars[rng.below(num_vars)];
+            if rng.below(2) == 0 { v.to_positive_literal() } else { v.to_negative_literal() }
+        }).collect()
+    }).collect();
+
+    let new_solver = || {
+        let lits : Vec<Literal> = vars[..num_vars].iter().map(|v| v.to_positive_literal()).collect();
+        Solver::new(to_clauses(std::iter::once(lits)), vars[num_vars], SolverConfig::default())
+    };
+    let mut one_by_one = new_solver();
+    for lits in batch.iter() {
+        one_by_one.add_clause(lits);
+    }
+    let mut bulk = new_solver();
+    bulk.add_clauses(batch.iter().cloned());
+
+    assert!(!bulk.known_unsat && !one_by_one.known_unsat);
+    assert_eq!(bulk.num_problem_clauses(), one_by_one.num_problem_clauses());
+    let clauses = |s : &Solver| s.env.problem.iter().map(|cl| cl.literals().to_vec()).collect::<Vec<_>>();
+    assert_eq!(clauses(&bulk), clauses(&one_by_one));
+    let (s1, s2) = (&bulk.env.solver_state, &one_by_one.env.solver_state);
+    assert!(s1.watchlist.iter().eq(s2.watchlist.iter()));
+    let values = |s : &SolverState| (0..s.assignment.num_variables()).map(|ix| s.assignment.value_of(Variable::from_index(ix))).collect::<Vec<_>>();
+    assert_eq!(values(s1), values(s2));
+    assert!(s1.reasons.iter().eq(s2.reasons.iter()));
+    assert_eq!(s1.decision_stack, s2.decision_stack);
+    assert_eq!(s1.propagation_queue, s2.propagation_queue);
+    assert!(bulk.env.occurrences.iter().eq(one_by_one.env.occurrences.iter()));
+    assert!(bulk.audit_watches().is_empty());
+
+    assert_eq!(bulk.solve(), one_by_one.solve());
+    assert_eq!(bulk.model(), one_by_one.model());
+}
+
+#[test]
+fn test_decisions_follow_propagation() {
+    // `next_decision` checks that the propagation queue is empty (in debug
+    // builds), so this exercises every path to a decision
+    let configs = vec![
+        SolverConfig::default(),
+        SolverConfig { seed : Some(3), random_decision_freq : 0.3, ..SolverConfig::default() },
+        SolverConfig { pure_literal_interval : Some(1), restart_policy : RestartPolicy::Luby { unit : 2 }, ..SolverConfig::default() },
+        SolverConfig { max_learned_length : Some(2), ..SolverConfig::default() }
+    ];
+    for config in configs {
+        for (pigeons, expected) in &[(5, core::Result::Unsat), (4, core::Result::Sat)] {
+            let (clauses, next_var) = pigeonhole(*pigeons, 4);
+            let clauses = to_clauses(clauses);
+            let mut solver = Solver::new(clauses, next_var, config.clone());
+            assert_eq!(solver.solve(), *expected);
+            assert_eq!(solver.solve_under_assumptions(&[test_variables(1)[0].to_negative_literal()]), *expected);
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "Deciding with 1 pending propagations")]
+fn test_decide_with_pending_propagation() {
+    let mut solver = pigeonhole_solver(3, 3);
+    let env = &mut solver.env;
+    enqueue(&mut env.solver_state, test_variables(1)[0].to_positive_literal(), None);
+    next_decision(env);
+}
+
+#[test]
+fn test_unassigned_variables() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 5 3\n1 0\n-1 2 0\n-3 4 5 0\n").unwrap();
+    let vars : Vec<Variable> = (1..=5).map(|v| dimacs.variable(v).unwrap()).collect();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.unassigned_variables().collect::<Vec<_>>(), vars[1..].to_vec());
+
+    let env = &mut solver.env;
+    assert!(propagate_units(env) == PropagateResult::NoConflict);
+    decide(env, vars[2].to_positive_literal(), false);
+    decide(env, vars[3].to_negative_literal(), false);
+    assert!(propagate_units(env) == PropagateResult::NoConflict);
+    // 2 is implied at level 0 and 5 by the two decisions
+    assert_eq!(solver.unassigned_variables().count(), 0);
+
+    cancel_until(&mut solver.env, 1);
+    assert_eq!(solver.unassigned_variables().collect::<Vec<_>>(), vec![vars[3], vars[4]]);
+    assert_eq!(solver.unassigned_variables().count() + solver.num_assigned(), 5);
+}
+
+#[test]
+fn test_is_satisfied() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 3\n1 2 3 0\n1 -4 0\n-2 -3 0\n").unwrap();
+    let vars : Vec<Variable> = (1..=4).map(|v| dimacs.variable(v).unwrap()).collect();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    let ids = [ClauseId(0), ClauseId(1), ClauseId(2)];
+    assert!(ids.iter().all(|id| !solver.is_satisfied(*id)));
+
+    // Partially assigned clauses with no true literal are not satisfied
+    decide(&mut solver.env, vars[0].to_negative_literal(), false);
+    assert!(ids.iter().all(|id| !solver.is_satisfied(*id)));
+
+    // The first clause is satisfied by a literal it does not watch
+    decide(&mut solver.env, vars[2].to_positive_literal(), false);
+    assert!(solver.is_satisfied(ids[0]));
+    assert!(!solver.is_satisfied(ids[2]));
+
+    // Every literal of the second clause is false
+    decide(&mut solver.env, vars[3].to_positive_literal(), false);
+    assert!(!solver.is_satisfied(ids[1]));
+
+    decide(&mut solver.env, vars[1].to_negative_literal(), false);
+    assert!(solver.is_satisfied(ids[2]));
+
+    cancel_until(&mut solver.env, 1);
+    assert!(!solver.is_satisfied(ids[0]));
+}
+
+/// A writer whose output can be read after it is handed to the solver
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf : &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// True if unit propagation on the clauses and the negation of the lemma
+/// reaches a conflict (i.e., the lemma is a reverse unit propagation step)
+#[cfg(test)]
+fn is_rup(clauses : &[Vec<i32>], lemma : &[i32]) -> bool {
+    let mut assigned : BTreeSet<i32> = lemma.iter().map(|l| -l).collect();
+    loop {
+        let mut changed = false;
+        for cl in clauses {
+            if cl.iter().any(|l| assigned.contains(l)) {
+                continue;
+            }
+            let open : Vec<i32> = cl.iter().copied().filter(|l| !assigned.contains(&-l)).collect();
+            match open[..] {
+                [] => return true,
+                [unit] => changed |= assigned.insert(unit),
+                _ => {}
+            }
+        }
+        if !changed {
+            return false;
+        }
+    }
+}
+
+#[test]
+fn test_proof() {
+    let (clauses, next_var) = pigeonhole(5, 4);
+    let clauses : Vec<Vec<Literal>> = clauses.collect();
+    let mut formula : Vec<Vec<i32>> = clauses.iter().map(|lits| lits.iter().map(|l| l.to_dimacs()).collect()).collect();
+    let clauses = to_clauses(clauses.into_iter());
+    let mut solver = Solver::new(clauses, next_var, SolverConfig::default());
+    let proof = SharedBuffer::default();
+    solver.set_proof_writer(Box::new(proof.clone()));
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(solver.finish_proof().is_ok());
+
+    let text = String::from_utf8(proof.0.lock().unwrap().clone()).unwrap();
+    let lemmas : Vec<Vec<i32>> = text.lines().map(|line| {
+        let mut lits : Vec<i32> = line.split(' ').map(|tok| tok.parse().unwrap()).collect();
+        assert_eq!(lits.pop(), Some(0));
+        lits
+    }).collect();
+    assert_eq!(lemmas.last(), Some(&Vec::new()));
+    assert!(lemmas.len() > 1);
+    for lemma in lemmas {
+        assert!(is_rup(&formula, &lemma), "{:?} does not follow by unit propagation", lemma);
+        formula.push(lemma);
+    }
+}
+
+#[test]
+fn test_learning_scheme() {
+    // Deciding 1 and then 2 implies 3, which implies both 4 and 5, which
+    // conflict
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 5 4\n-2 -1 3 0\n-3 4 0\n-3 5 0\n-4 -5 0\n").unwrap();
+    let lit = |l : i32| {
+        let v = dimacs.variable(l.unsigned_abs()).unwrap();
+        if l < 0 { v.to_negative_literal() } else { v.to_positive_literal() }
+    };
+    let learn = |learning_scheme| {
+        let config = SolverConfig { learning_scheme, ..SolverConfig::default() };
+        let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, config);
+        let env = &mut solver.env;
+        decide(env, lit(1), false);
+        assert!(propagate_units(env) == PropagateResult::NoConflict);
+        decide(env, lit(2), false);
+        match propagate_units(env) {
+            PropagateResult::Conflict(cid) => analyze_conflict(env, cid),
+            PropagateResult::NoConflict => panic!("Expected a conflict")
+        }
+    };
+
+    // The first UIP is the implied literal 3, which alone leads to the
+    // conflict; the last is the decision, which needs the earlier decision
+    assert_eq!(learn(LearningScheme::FirstUip), (vec![lit(-3)], 0));
+    assert_eq!(learn(LearningScheme::LastUip), (vec![lit(-2), lit(-1)], 1));
+
+    for (pigeons, expected) in &[(5, core::Result::Unsat), (4, core::Result::Sat)] {
+        let (clauses, next_var) = pigeonhole(*pigeons, 4);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { learning_scheme : LearningScheme::LastUip, ..SolverConfig::default() };
+        assert_eq!(Solver::new(clauses, next_var, config).solve(), *expected);
+    }
+}
+
+#[test]
+fn test_level_histogram() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 6 4\n1 0\n-1 2 0\n-3 4 5 0\n-3 -4 6 0\n").unwrap();
+    let vars : Vec<Variable> = (1..=6).map(|v| dimacs.variable(v).unwrap()).collect();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_eq!(solver.level_histogram(), vec![2]);
+
+    // Deciding 3 implies nothing, but then deciding 4 implies 6
+    decide(&mut solver.env, vars[2].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    decide(&mut solver.env, vars[3].to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_eq!(solver.level_histogram(), vec![2, 1, 2]);
+    assert_eq!(solver.level_histogram().iter().sum::<usize>(), solver.num_assigned());
+
+    cancel_until(&mut solver.env, 1);
+    assert_eq!(solver.level_histogram(), vec![2, 1]);
+}
+
+#[test]
+fn test_antecedents() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 2\n-1 -2 3 0\n-3 4 0\n").unwrap();
+    let lit = |l : i32| {
+        let v = dimacs.variable(l.unsigned_abs()).unwrap();
+        if l < 0 { v.to_negative_literal() } else { v.to_positive_literal() }
+    };
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    decide(&mut solver.env, lit(1), false);
+    decide(&mut solver.env, lit(2), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+
+    for implied in [lit(3), lit(4)] {
+        let cid = solver.env.solver_state.reasons[implied.variable()].unwrap();
+        let falsified : Vec<Literal> = solver.env.problem.clause(cid).literals().iter().copied()
+            .filter(|l| solver.env.solver_state.value_of(*l) == Value::LIFTED_FALSE)
+            .collect();
+        assert_eq!(solver.antecedents(implied), falsified);
+    }
+    let mut antecedents = solver.antecedents(lit(3));
+    antecedents.sort();
+    assert_eq!(antecedents, vec![lit(-1), lit(-2)]);
+    assert_eq!(solver.antecedents(lit(4)), vec![lit(-3)]);
+
+    // Decisions and literals that are not true have no antecedents
+    assert!(solver.antecedents(lit(1)).is_empty());
+    assert!(solver.antecedents(lit(-3)).is_empty());
+}
+
+#[test]
+fn test_watch_search() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 5 1\n1 2 3 4 5 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    // Falsify the given variables, then the first watched literal, returning
+    // the literals watched afterwards
+    let rewatch = |watch_search, falsified : &[u32]| {
+        let config = SolverConfig { watch_search, ..SolverConfig::default() };
+        let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, config);
+        for n in falsified.iter().chain(&[1]) {
+            decide(&mut solver.env, v(*n).to_negative_literal(), false);
+            assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+        }
+        assert_watchlist_consistent(&solver.env);
+        let cl = solver.env.problem.clause(ClauseId(0));
+        let mut watched = vec![cl[0], cl[1]];
+        watched.sort();
+        watched
+    };
+
+    let watching = |a : u32, b : u32| vec![v(a).to_positive_literal(), v(b).to_positive_literal()];
+    assert_eq!(rewatch(WatchSearch::Forward, &[]), watching(2, 3));
+    assert_eq!(rewatch(WatchSearch::Backward, &[]), watching(2, 5));
+    // Only the last literal can be watched
+    assert_eq!(rewatch(WatchSearch::Forward, &[3, 4]), watching(2, 5));
+    assert_eq!(rewatch(WatchSearch::Backward, &[3, 4]), watching(2, 5));
+
+    for (pigeons, expected) in &[(5, core::Result::Unsat), (4, core::Result::Sat)] {
+        let (clauses, next_var) = pigeonhole(*pigeons, 4);
+        let clauses = to_clauses(clauses);
+        let config = SolverConfig { watch_search : WatchSearch::Backward, ..SolverConfig::default() };
+        assert_eq!(Solver::new(clauses, next_var, config).solve(), *expected);
+    }
+}
+
+#[test]
+fn test_no_level() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 3 2\n1 0\n-2 3 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.level_of(v(1)), 0);
+    assert_eq!(solver.level_of(v(2)), NO_LEVEL);
+    assert!(!is_assigned_level(solver.level_of(v(3))));
+
+    decide(&mut solver.env, v(2).to_positive_literal(), false);
+    assert!(propagate_units(&mut solver.env) == PropagateResult::NoConflict);
+    assert_eq!((solver.level_of(v(2)), solver.level_of(v(3))), (1, 1));
+    assert!(is_assigned_level(solver.level_of(v(3))));
+
+    // Backtracking unassigns the variables, and their levels with them
+    cancel_until(&mut solver.env, 0);
+    assert_eq!((solver.level_of(v(2)), solver.level_of(v(3))), (NO_LEVEL, NO_LEVEL));
+    assert_eq!(solver.level_of(v(1)), 0);
+}
+
+#[test]
+fn test_explain_learned() {
+    let (clauses, next_var) = pigeonhole(5, 4);
+    let clauses = to_clauses(clauses);
+    let config = SolverConfig { record_derivations : true, ..SolverConfig::default() };
+    let mut solver = Solver::new(clauses, next_var, config);
+    let learned = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let hook_learned = learned.clone();
+    solver.set_learn_hook(Box::new(move |lits| {
+        hook_learned.lock().unwrap().push(lits.to_vec());
+        LearnAction::Keep
+    }));
+    assert_eq!(solver.solve(), core::Result::Unsat);
+
+    let learned = learned.lock().unwrap();
+    assert!(!learned.is_empty());
+    for clause in learned.iter() {
+        let explanation = solver.explain_learned(clause);
+        let lines : Vec<&str> = explanation.lines().collect();
+        assert!(lines[0].starts_with("conflict: "));
+        assert!(lines[1..lines.len() - 1].iter().all(|l| l.starts_with("resolve on ") || l.starts_with("drop ")));
+        let mut last : Vec<Literal> = lines[lines.len() - 1].strip_prefix("learned:").unwrap().split_whitespace()
+            .map(|l| Literal::from_dimacs(l.parse().unwrap()).unwrap())
+            .collect();
+        let mut expected = clause.clone();
+        last.sort();
+        expected.sort();
+        assert_eq!(last, expected, "{}", explanation);
+    }
+
+    let unknown = [Literal::from_dimacs(1).unwrap()];
+    assert!(solver.explain_learned(&unknown).starts_with("no derivation"));
+}
+
+#[test]
+fn test_verify_propagation() {
+    // Every round of propagation is checked while solving
+    let configs = vec![
+        SolverConfig { verify_propagation : true, ..SolverConfig::default() },
+        SolverConfig { verify_propagation : true, restart_policy : RestartPolicy::Luby { unit : 1 }, ..SolverConfig::default() }
+    ];
+    for config in configs {
+        for (pigeons, expected) in &[(5, core::Result::Unsat), (4, core::Result::Sat)] {
+            let (clauses, next_var) = pigeonhole(*pigeons, 4);
+            let clauses = to_clauses(clauses);
+            let mut solver = Solver::new(clauses, next_var, config.clone());
+            assert_eq!(solver.solve(), *expected);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "Propagation missed")]
+fn test_verify_propagation_catches_missing_watch() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 3 2\n1 2 3 0\n-1 -2 0\n").unwrap();
+    let v1 = dimacs.variable(1).unwrap();
+    let config = SolverConfig { verify_propagation : true, ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    let env = &mut solver.env;
+    // Break the watches of `-1 -2` so that deciding 1 does not imply -2
+    for watchers in env.solver_state.watchlist.iter_mut() {
+        watchers.remove(&ClauseId(1));
+    }
+    decide(env, v1.to_positive_literal(), false);
+    propagate_units(env);
+}
+
+#[test]
+fn test_analysis_bumps_antecedents() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 1\n1 2 3 4 0\n").unwrap();
+    let v = |n| dimacs.variable(n).unwrap();
+    let (a, b, c, d) = (v(1).to_positive_literal(), v(2).to_positive_literal(), v(3).to_positive_literal(), v(4).to_positive_literal());
+    let mut solver = Solver::new(dimacs.clauses.clone(), dimacs.next_var, SolverConfig::default());
+    let env = &mut solver.env;
+    let learned = [vec![b, a.negate()], vec![c, b.negate()], vec![d, c.negate(), b.negate()], vec![c.negate(), d.negate()]];
+    let learned : Vec<ClauseId> = learned.iter().map(|lits| add_learned_clause(env, lits.clone())).collect();
+    let before : Vec<f64> = learned.iter().map(|cid| env.problem.clause(*cid).activity()).collect();
+
+    // a implies b, c, and d, which conflict; resolving back from the conflict
+    // uses the reasons of d and c, reaching b as the first UIP
+    decide(env, a, false);
+    for (lit, reason) in [(b, learned[0]), (c, learned[1]), (d, learned[2])].iter() {
+        assert!(enqueue(&mut env.solver_state, *lit, Some(*reason)) == PropagateResult::NoConflict);
+    }
+    assert_eq!(analyze_conflict(env, learned[3]), (vec![b.negate()], 0));
+
+    let after : Vec<f64> = learned.iter().map(|cid| env.problem.clause(*cid).activity()).collect();
+    assert_eq!(after[0], before[0]);
+    for ix in 1..4 {
+        assert!(after[ix] > before[ix], "clause {} was not bumped", ix);
+    }
+}
+
+#[test]
+fn test_clauses_by_activity() {
+    let mut solver = pigeonhole_solver(4, 3);
+    let vars = test_variables(4);
+    let env = &mut solver.env;
+    let learned : Vec<ClauseId> = (0..3).map(|ix| {
+        add_learned_clause(env, vec![vars[ix].to_positive_literal(), vars[ix + 1].to_negative_literal()])
+    }).collect();
+    for (cid, bumps) in learned.iter().zip(&[1, 3, 2]) {
+        for _ in 0..*bumps {
+            bump_clause_activity(env, *cid);
+            decay_clause_activity(env);
+        }
+    }
+
+    let order : Vec<(ClauseId, f64)> = solver.clauses_by_activity().collect();
+    assert_eq!(order.iter().map(|(cid, _)| *cid).collect::<Vec<_>>(), vec![learned[1], learned[2], learned[0]]);
+    assert!(order.windows(2).all(|w| w[0].1 >= w[1].1));
+    // Problem clauses are not included
+    assert!(order.iter().all(|(cid, _)| solver.env.problem.clause(*cid).is_learned()));
+}
+
+#[test]
+fn test_assumptions_satisfy_formula() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 4 3\n1 2 0\n-1 3 0\n3 4 0\n").unwrap();
+    let config = SolverConfig { trace : true, ..SolverConfig::default() };
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, config);
+    let assumption = Literal::from_dimacs(1).unwrap();
+    assert_eq!(solver.solve_under_assumptions(&[assumption]), core::Result::Sat);
+    assert_eq!(solver.stats().decisions, 0);
+    assert!(solver.trace().ends_with("satisfied by assumptions\n"));
+
+    // The assumption and its consequence, with the free variables defaulted
+    assert_eq!(solver.value_of_dimacs(1), Some(true));
+    assert_eq!(solver.value_of_dimacs(3), Some(true));
+    assert_eq!(solver.value_of_dimacs(2), Some(false));
+    assert_eq!(solver.value_of_dimacs(4), Some(false));
+    assert_eq!(solver.model().unwrap().len(), 4);
+
+    // Without assumptions, the search decides as usual
+    assert_eq!(solver.solve(), core::Result::Sat);
+    assert!(solver.stats().decisions > 0);
+}
+
+#[test]
+fn test_packed_assignment_search() {
+    // The representation of the assignment must not change the search
+    let mut rng = Rng::new(23);
+    for _ in 0..20 {
+        let num_vars = 60;
+        let clauses : Vec<Vec<Literal>> = (0..255).map(|_| {
+            (0..3).map(|_| {
+                let v = Variable::from_index(rng.below(num_vars));
+                if rng.below(2) == 0 { v.to_positive_literal() } else { v.to_negative_literal() }
+            }).collect()
+        }).collect();
+        let solve = |packed_assignment| {
+            let cls = to_clauses(clauses.iter().cloned());
+            let config = SolverConfig { seed : Some(5), packed_assignment, trace : true, ..SolverConfig::default() };
+            let mut solver = Solver::new(cls, Variable::from_index(num_vars), config);
+            let res = solver.solve();
+            (res, solver.model().map(|m| m.literals().collect::<Vec<_>>()), solver.trace().to_string())
+        };
+        assert_eq!(solve(true), solve(false));
+    }
+
+    // The assignment has a slot for `next_var` too
+    let next_var = Variable::from_index(4095);
+    let solver = Solver::new(Vec::new(), next_var, SolverConfig { packed_assignment : true, ..SolverConfig::default() });
+    let unpacked = Solver::new(Vec::new(), next_var, SolverConfig::default());
+    assert_eq!(solver.env.solver_state.assignment.heap_bytes() * 4, unpacked.env.solver_state.assignment.heap_bytes());
+}
+
+#[test]
+fn test_model_in_dimacs_order() {
+    let input = "p cnf 4 3\n1 -2 0\n2 3 0\n-3 -4 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    // Internal order is the reverse of the DIMACS order
+    let names : TaggedVec<Variable, u32> = vec![40, 30, 20, 10].into_iter().collect();
+    solver.set_dimacs_variables(names);
+
+    let mut out = Vec::new();
+    solver.write_model(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let vars : Vec<i64> = out.split_whitespace().skip(1).map(|t| t.parse::<i64>().unwrap().abs()).collect();
+    assert_eq!(vars, vec![10, 20, 30, 40, 0]);
+    assert!(vars[..4].windows(2).all(|w| w[0] < w[1]));
+
+    let mut json = Vec::new();
+    solver.write_model_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    let keys : Vec<&str> = json.split('"').skip(1).step_by(2).collect();
+    assert_eq!(keys, vec!["10", "20", "30", "40"]);
+}
+
+#[test]
+fn test_quick_check() {
+    let check = |input : &str| {
+        let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+        quick_check(dimacs.clauses, dimacs.next_var)
+    };
+    // Contradictory units are caught by preprocessing, and a longer chain by
+    // propagation
+    assert_eq!(check("p cnf 1 2\n1 0\n-1 0\n"), Some(core::Result::Unsat));
+    assert_eq!(check("p cnf 3 4\n1 0\n-1 2 0\n-2 3 0\n-2 -3 0\n"), Some(core::Result::Unsat));
+    // The units imply every clause (leaving 4 free)
+    assert_eq!(check("p cnf 4 3\n1 0\n-1 2 0\n2 3 4 0\n"), Some(core::Result::Sat));
+    // Nothing to propagate
+    assert_eq!(check("p cnf 2 2\n1 2 0\n-1 -2 0\n"), None);
+    assert_eq!(check("p cnf 3 2\n1 0\n-1 2 3 0\n"), None);
+}
+
+#[test]
+fn test_final_conflict() {
+    let falsified = |solver : &Solver| {
+        let lits = solver.final_conflict().expect("Expected a final conflict");
+        lits.iter().all(|l| solver.env.solver_state.value_of(*l) == Value::LIFTED_FALSE)
+    };
+
+    // A conflict in level 0 propagation
+    let input = "p cnf 3 4\n1 0\n-1 2 0\n-2 3 0\n-2 -3 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(falsified(&solver));
+    assert_eq!(solver.final_conflict().unwrap().len(), 2);
+
+    // After learning (the last conflict is always at level 0)
+    let mut solver = pigeonhole_solver(5, 4);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert!(falsified(&solver));
+
+    // Contradictory units are found by preprocessing
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 2 3\n1 2 0\n-2 0\n2 0\n").unwrap();
+    let mut solver = Solver::new(dimacs.clauses, dimacs.next_var, SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Unsat);
+    assert_eq!(solver.final_conflict(), Some(&[Literal::from_dimacs(2).unwrap()][..]));
+    assert!(falsified(&solver));
+
+    // A failed assumption is not a level 0 conflict
+    let mut solver = pigeonhole_solver(3, 3);
+    let v = test_variables(1)[0];
+    solver.add_clause(&[v.to_positive_literal()]);
+    assert_eq!(solver.solve_under_assumptions(&[v.to_negative_literal()]), core::Result::Unsat);
+    assert_eq!(solver.final_conflict(), None);
+}
+
+#[test]
+fn test_reset_phases_on_restart() {
+    let vars = test_variables(3);
+    for reset_phases_on_restart in &[false, true] {
+        let config = SolverConfig { reset_phases_on_restart : *reset_phases_on_restart, ..SolverConfig::default() };
+        let mut solver = pigeonhole_solver(3, 3);
+        solver.env.config = config;
+        solver.set_phase(vars[1], Polarity::Negative);
+        solver.set_phase(vars[2], Polarity::Negative);
+
+        let env = &mut solver.env;
+        env.solver_state.conflicts_since_restart = u64::MAX;
+        env.solver_state.learned_since_restart = 1;
+        assert!(maybe_restart(env));
+
+        let phases : Vec<Polarity> = vars.iter().map(|v| decision_literal(&env.solver_state, *v).polarity()).collect();
+        if *reset_phases_on_restart {
+            assert_eq!(phases, vec![Polarity::Positive; 3]);
+        } else {
+            assert_eq!(phases, vec![Polarity::Positive, Polarity::Negative, Polarity::Negative]);
+        }
+    }
 }
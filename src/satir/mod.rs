@@ -1,6 +1,13 @@
 pub mod tagged;
 pub mod core;
 pub mod clause;
+pub mod arena;
+pub mod assignment;
 pub mod constraint;
+#[cfg(feature = "std")]
 pub mod parse;
 pub mod dpll;
+#[cfg(feature = "std")]
+pub mod portfolio;
+#[cfg(feature = "std")]
+pub mod simplify;
@@ -0,0 +1,188 @@
+use std::io::{BufRead, Read, Write};
+
+use crate::satir::clause::{Clause, ClauseHeader, ClauseId};
+use crate::satir::core::{Literal, Variable};
+use crate::satir::simplify::Cnf;
+use crate::satir::tagged::TaggedIndexable;
+
+/// The first bytes of every binary formula (the last is the format version)
+const MAGIC : &[u8; 5] = b"SATB\x01";
+
+/// Errors that can arise while reading a binary formula
+#[derive(Debug, thiserror::Error)]
+pub enum BinaryError {
+    /// The input could not be read
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// The input does not start with the expected magic number and version
+    #[error("not a binary formula (bad magic number)")]
+    BadMagic,
+    /// The input ended in the middle of the formula
+    #[error("unexpected end of input")]
+    Truncated,
+    /// A number is too large (or a literal refers to a variable outside of
+    /// the formula)
+    #[error("value {0} is out of range")]
+    OutOfRange(u64),
+}
+
+/// Write a number as a LEB128 varint: seven bits per byte, least significant
+/// first, with the high bit set on every byte but the last
+fn write_varint<W : Write>(w : &mut W, mut n : u64) -> std::io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R : BufRead>(r : &mut R) -> Result<u64, BinaryError> {
+    let mut n : u64 = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0u8];
+        if r.read(&mut byte)? == 0 {
+            return Err(BinaryError::Truncated);
+        }
+        let bits = (byte[0] & 0x7f) as u64;
+        if shift >= 64 || (shift > 0 && bits >> (64 - shift) != 0) {
+            return Err(BinaryError::OutOfRange(n));
+        }
+        n |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+        shift += 7;
+    }
+}
+
+impl Cnf {
+    /// Write the formula in a compact binary form, which is much faster to
+    /// load than DIMACS text (see `read_binary`)
+    ///
+    /// The format is the magic number `SATB` and a version byte, then the
+    /// number of variables, the number of clauses, and for each clause its
+    /// length followed by its literals (by index), all as varints.  Clauses and
+    /// their literals are written in order.
+    pub fn write_binary<W : Write>(&self, w : &mut W) -> std::io::Result<()> {
+        w.write_all(MAGIC)?;
+        write_varint(w, self.next_var.as_index() as u64)?;
+        write_varint(w, self.clauses.len() as u64)?;
+        for cl in self.clauses.iter() {
+            write_varint(w, cl.lit_count() as u64)?;
+            for lit in cl.literals() {
+                write_varint(w, lit.as_index() as u64)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read a formula written by `write_binary`
+    ///
+    /// Clauses are numbered in order.
+    pub fn read_binary<R : Read>(reader : R) -> Result<Cnf, BinaryError> {
+        let mut r = std::io::BufReader::new(reader);
+        let mut magic = [0u8; 5];
+        r.read_exact(&mut magic).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => BinaryError::BadMagic,
+            _ => BinaryError::Io(err)
+        })?;
+        if &magic != MAGIC {
+            return Err(BinaryError::BadMagic);
+        }
+
+        let num_vars = read_varint(&mut r)?;
+        if num_vars > i32::MAX as u64 / 2 {
+            return Err(BinaryError::OutOfRange(num_vars));
+        }
+        let next_var = Variable::from_index(num_vars as usize);
+        let max_lit = 2 * num_vars;
+
+        let num_clauses = read_varint(&mut r)?;
+        // The counts are untrusted, so do not preallocate for all of them
+        let mut clauses = Vec::with_capacity(std::cmp::min(num_clauses, 1 << 16) as usize);
+        for ix in 0..num_clauses {
+            // Clauses can repeat literals, so their length is not bounded by
+            // the number of variables
+            let lit_count = read_varint(&mut r)?;
+            let mut lits = Vec::with_capacity(std::cmp::min(lit_count, 1 << 16) as usize);
+            for _ in 0..lit_count {
+                let lit = read_varint(&mut r)?;
+                if lit >= max_lit {
+                    return Err(BinaryError::OutOfRange(lit));
+                }
+                lits.push(Literal::from_index(lit as usize));
+            }
+            clauses.push(Clause::new(ClauseHeader::new(ClauseId(ix as i64), lits.len(), 0), lits));
+        }
+
+        Ok(Cnf { clauses, next_var })
+    }
+}
+
+#[test]
+fn test_varint_round_trip() {
+    for n in &[0, 1, 127, 128, 300, 1 << 35, u64::MAX] {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, *n).unwrap();
+        assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), *n);
+    }
+}
+
+#[test]
+fn test_binary_round_trip() {
+    let input = "p cnf 200 5\n1 -2 0\n-200 3 17 0\n5 0\n0\n2 2 -150 0\n";
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let cnf = Cnf { clauses : dimacs.clauses, next_var : dimacs.next_var };
+    let mut buf = Vec::new();
+    cnf.write_binary(&mut buf).unwrap();
+
+    let loaded = Cnf::read_binary(buf.as_slice()).unwrap();
+    assert_eq!(loaded.next_var, cnf.next_var);
+    let lits = |c : &Cnf| c.clauses.iter().map(|cl| cl.literals().to_vec()).collect::<Vec<_>>();
+    assert_eq!(lits(&loaded), lits(&cnf));
+    for (ix, cl) in loaded.clauses.iter().enumerate() {
+        assert_eq!(cl.identifier(), ClauseId(ix as i64));
+    }
+    assert_eq!(crate::satir::dpll::solve(loaded.clauses, loaded.next_var), crate::satir::core::Result::Unsat);
+}
+
+#[test]
+fn test_binary_repeated_literals() {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 1 1\n1 1 1 0\n").unwrap();
+    let cnf = Cnf { clauses : dimacs.clauses, next_var : dimacs.next_var };
+    let mut buf = Vec::new();
+    cnf.write_binary(&mut buf).unwrap();
+
+    let loaded = Cnf::read_binary(buf.as_slice()).unwrap();
+    let lit = Literal::from_dimacs(1).unwrap();
+    assert_eq!(loaded.clauses[0].literals(), &[lit, lit, lit][..]);
+
+    // A huge (claimed) clause length is not allocated up front
+    let mut huge = MAGIC.to_vec();
+    for n in &[1, 1, 1 << 31] {
+        write_varint(&mut huge, *n).unwrap();
+    }
+    assert!(matches!(Cnf::read_binary(huge.as_slice()), Err(BinaryError::Truncated)));
+}
+
+#[test]
+fn test_binary_errors() {
+    assert!(matches!(Cnf::read_binary(&b"p cnf 1 1\n"[..]), Err(BinaryError::BadMagic)));
+    assert!(matches!(Cnf::read_binary(&b"SAT"[..]), Err(BinaryError::BadMagic)));
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs("p cnf 3 2\n1 -2 0\n2 3 0\n").unwrap();
+    let cnf = Cnf { clauses : dimacs.clauses, next_var : dimacs.next_var };
+    let mut buf = Vec::new();
+    cnf.write_binary(&mut buf).unwrap();
+    assert!(matches!(Cnf::read_binary(&buf[..buf.len() - 1]), Err(BinaryError::Truncated)));
+
+    // A literal of a variable beyond the formula
+    let last = buf.len() - 1;
+    buf[last] = 6;
+    assert!(matches!(Cnf::read_binary(buf.as_slice()), Err(BinaryError::OutOfRange(6))));
+}
@@ -1,9 +1,11 @@
 use std::collections::BTreeMap;
 
 use combine::error::ParseError;
+use combine::stream::easy;
 use combine::stream::position;
 use combine::parser::char;
 use combine::parser::choice;
+use combine::parser::combinator;
 use combine::parser::repeat;
 use combine::parser::token;
 use combine::{Parser,Stream,EasyParser};
@@ -11,8 +13,24 @@ use combine::{Parser,Stream,EasyParser};
 use crate::satir::core;
 use crate::satir::core::Variable;
 use crate::satir::clause;
+use crate::satir::tagged::TaggedVec;
 
-/// A parser for whitespace between tokens
+/// The characters separating tokens within a line
+///
+/// DIMACS is byte-oriented, so this is the ASCII whitespace other than line
+/// endings (rather than `char::space`, which accepts any Unicode whitespace)
+const BLANKS : &str = " \t\x0b\x0c";
+
+/// A parser for whitespace between tokens on the same line
+fn blanks<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input : Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    repeat::skip_many(token::one_of(BLANKS.chars()))
+}
+
+/// A parser for whitespace between tokens, which may include line endings
 ///
 /// This does not cover comments, which are a bit special in DIMACS (they are
 /// handled at the top level)
@@ -21,7 +39,7 @@ where
     Input : Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
-    repeat::skip_many(char::space())
+    repeat::skip_many(token::one_of(BLANKS.chars().chain("\r\n".chars())))
 }
 
 fn line_end<Input>() -> impl Parser<Input, Output = ()>
@@ -31,9 +49,13 @@ where
     choice::or(char::newline(), char::crlf()).map(|_| ())
 }
 
-/// Parse a vector of char into a u32; fails loudly if they are not actually digits
+/// Parse a vector of char into a u32
+///
+/// Values too large for a u32 saturate to `u32::MAX`, which is out of range for
+/// a DIMACS variable and is reported as an overflow when the literal is
+/// interned.
 fn digits_to_u32(digits : Vec<char>) -> u32 {
-    digits.into_iter().collect::<String>().parse().unwrap()
+    digits.into_iter().collect::<String>().parse().unwrap_or(u32::MAX)
 }
 
 fn number<Input>() -> impl Parser<Input, Output = u32>
@@ -41,7 +63,8 @@ where
     Input : Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
-    // As long as the many1 combined succeeds, digitsToU32 cannot fail
+    // As long as the many1 combined succeeds, digitsToU32 cannot fail (it
+    // saturates on overflow)
     repeat::many1::<Vec<_>, _, _>(char::digit()).map(digits_to_u32)
 }
 
@@ -58,24 +81,76 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
     (char::char('p'),
-     whitespace(),
+     blanks(),
      char::string("cnf"),
-     whitespace(),
+     blanks(),
      number(),
-     whitespace(),
+     blanks(),
      number(),
     ).map(|(_, _, _, _, nvar, _, nclause)| CNFProblem { num_variables : nvar, num_clauses : nclause })
 }
 
 /// In DIMACS, comments are a line that starts with the character 'c' until the end of the line
-fn comment<Input>() -> impl Parser<Input, Output = ()>
+///
+/// The 'c' must be followed by whitespace or the end of the line, so a line
+/// like `c3 ...` is a syntax error rather than a comment.
+///
+/// Comments of the form `c phase <var> <0|1>` are directives that set the
+/// initial phase of a variable; any other comment produces `None`
+fn comment<Input>() -> impl Parser<Input, Output = Option<PhaseDirective>>
 where
     Input : Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
 {
     (char::char('c'),
-     repeat::skip_until(line_end())
-     ).map(|(_, _)| ())
+     combinator::look_ahead(choice::or(token::one_of(BLANKS.chars()).map(|_| ()), line_end())),
+     choice::or(combinator::attempt(phase_directive().skip(combinator::look_ahead(line_end()))).map(Some),
+                repeat::skip_until(line_end()).map(|_| None))
+     ).map(|(_, _, directive)| directive)
+}
+
+/// Reject a line starting with a letter other than 'c' (comments) or 'p' (the
+/// problem line), which would otherwise be reported as a confusing error from
+/// whatever parser comes next
+///
+/// This consumes no input.
+fn no_stray_line<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input : Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    choice::optional(combinator::look_ahead(token::satisfy(|c : char| c.is_alphabetic() && c != 'c' && c != 'p')))
+        .then(|letter| match letter {
+            None => combine::value(()).left(),
+            Some(c) => {
+                let msg = format!("line starting with '{}' (expected a comment or the problem line)", c);
+                combine::unexpected_any(combine::error::Format(msg)).right()
+            }
+        })
+}
+
+/// The initial phase of a variable requested by a `c phase` comment
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct PhaseDirective {
+    var : ParsedVar,
+    positive : bool
+}
+
+/// The body of a `c phase <var> <0|1>` directive (after the `c`)
+fn phase_directive<Input>() -> impl Parser<Input, Output = PhaseDirective>
+where
+    Input : Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>
+{
+    let blanks1 = || repeat::skip_many1(token::one_of(BLANKS.chars()));
+    (blanks1(),
+     char::string("phase"),
+     blanks1(),
+     number(),
+     blanks1(),
+     token::one_of("01".chars()),
+     blanks()
+     ).map(|(_, _, _, var, _, phase, _)| PhaseDirective { var : ParsedVar(var), positive : phase == '1' })
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
@@ -122,14 +197,63 @@ where
     // Note the extra ship of 0 at the end; repeat_until does not consume the
     // token that causes it to stop
     (choice::optional(whitespace()),
-     repeat::repeat_until(literal().skip(whitespace()), char::char('0')).skip(char::char('0'))
+     repeat::repeat_until(literal().skip(whitespace()), clause_end()).skip(clause_end())
      ).map(|(_, lits)| lits)
 }
 
+/// The `0` that ends a clause
+///
+/// This ends the clause even if digits follow, so `00` is two clause endings.
+fn clause_end<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input : Stream<Token = char>
+{
+    char::char('0').map(|_| ())
+}
+
+/// One token of a clause: either a literal or the end of the clause
+#[derive(Debug, PartialEq, Eq)]
+enum ClauseToken {
+    Lit(ParsedLit),
+    End
+}
+
+/// The tokens of the clauses on a single line (which need not start or end a
+/// clause), and the position of the `%` end marker if the line has one
+///
+/// This accepts the same clauses as `clause`, a line at a time, for parsing
+/// input as it is read.
+fn clause_line<Input>() -> impl Parser<Input, Output = (Vec<ClauseToken>, Option<Input::Position>)>
+where
+    Input : Stream<Token = char>
+{
+    // As in `clause`, the end of a clause is checked before a literal
+    let clause_token = choice::or(clause_end().map(|_| ClauseToken::End), literal().map(ClauseToken::Lit));
+    (whitespace(),
+     repeat::many(clause_token.skip(whitespace())),
+     choice::optional(token::position().skip(end_marker())),
+     token::eof()
+    ).map(|(_, tokens, end, _)| (tokens, end))
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct ParsedDIMACS {
     cnf_problem : CNFProblem,
-    clauses : Vec<Vec<ParsedLit>>
+    clauses : Vec<Vec<ParsedLit>>,
+    phases : Vec<PhaseDirective>
+}
+
+/// The comments and the problem line that precede the clauses, with the phase
+/// directives found in the comments
+fn header<Input>() -> impl Parser<Input, Output = (CNFProblem, Vec<PhaseDirective>)>
+where
+    Input : Stream<Token = char>
+{
+    (repeat::many::<Vec<_>, _, _>(comment().skip(line_end())),
+     no_stray_line(),
+     problem().skip(line_end()),
+     repeat::many::<Vec<_>, _, _>(comment().skip(line_end()))
+    ).map(|(pre, _, cnf, post)| (cnf, pre.into_iter().chain(post).flatten().collect()))
 }
 
 /// Parse an entire DIMACS file
@@ -139,104 +263,474 @@ struct ParsedDIMACS {
 /// The program declaration must come before the clauses
 ///
 /// Clauses do not have to be one per line
+///
+/// Some generators end the clauses with a line containing `%` (often followed
+/// by a stray `0`); everything after the `%` is ignored
 fn dimacs<Input>() -> impl Parser<Input, Output = ParsedDIMACS>
 where
     Input : Stream<Token = char>
 {
-    (repeat::many::<Vec<_>, _, _>(comment().skip(line_end()).with(token::value(()))),
-     problem().skip(line_end()),
-     repeat::many::<Vec<_>, _, _>(comment().skip(line_end()).with(token::value(()))),
-     repeat::many1(clause().skip(repeat::many::<Vec<_>, _, _>(line_end()))),
+    (header(),
+     no_stray_line(),
+     // A problem with no clauses is fine, but parsing the clauses with `many`
+     // instead would lose the position of syntax errors in the first clause
+     // Clauses are separated by any whitespace (including newlines), so several
+     // can share a line and trailing whitespace is not mistaken for the start
+     // of another clause
+     choice::or(repeat::many1(clause().skip(whitespace())),
+                combinator::look_ahead(choice::optional(end_marker()).with(token::eof())).map(|_| Vec::new())),
+     choice::optional(end_marker()),
      token::eof()
-    ).map(|(_, cnf, _, cs, _)| ParsedDIMACS { cnf_problem : cnf, clauses : cs })
+    ).map(|((cnf, phases), _, cs, _, _)| ParsedDIMACS {
+        cnf_problem : cnf,
+        clauses : cs,
+        phases
+    })
+}
+
+/// The `%` end marker, along with any junk that follows it
+fn end_marker<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input : Stream<Token = char>
+{
+    char::char('%').with(repeat::skip_many(token::any()))
 }
 
 
 fn to_core_lit(pl : &ParsedLit, cv : &core::Variable) -> core::Literal {
     match pl {
-        ParsedLit::PosLit(_) => cv.to_positive_literal(),
-        ParsedLit::NegLit(_) => cv.to_negative_literal()
+        ParsedLit::PosLit(_) => core::Literal::with_polarity(*cv, core::Polarity::Positive),
+        ParsedLit::NegLit(_) => core::Literal::with_polarity(*cv, core::Polarity::Negative)
     }
 }
 
-struct Env {
+/// Interns clauses as they are parsed, checking each literal
+///
+/// Variables are numbered in the order they are first seen, and renumbered
+/// densely in ascending DIMACS order by `finish` (so that DIMACS variable 1, if
+/// it appears, is always our first variable, and a formula that uses every
+/// variable up to its largest gets the numbering of
+/// `core::Variable::from_dimacs`).
+#[derive(Default)]
+struct Interner {
     var_map : BTreeMap<ParsedVar, core::Variable>,
-    next_var : core::Variable,
-    next_id : i64
-}
-
-fn intern_lit(env : &mut Env, pl : &ParsedLit) -> core::Literal {
-    match env.var_map.get(&parsed_lit_var(pl)) {
-        Some(cv) => to_core_lit(pl, cv),
-        None => {
-            let this_var = env.next_var;
-            env.next_var = this_var.next_variable();
-            env.var_map.insert(parsed_lit_var(pl), this_var);
-            to_core_lit(pl, &this_var)
+    clauses : Vec<clause::Clause>,
+    num_literals : usize,
+    num_unit_clauses : usize,
+    num_empty_clauses : usize
+}
+
+impl Interner {
+    fn intern_lit(&mut self, pl : &ParsedLit) -> Result<core::Literal, DimacsError> {
+        let ParsedVar(vnum) = parsed_lit_var(pl);
+        if vnum == 0 {
+            // The parser only produces a zero variable for `-0`, since a
+            // bare `0` terminates the clause
+            return Err(DimacsError::ZeroVariable);
+        }
+        if vnum > MAX_DIMACS_VARIABLE {
+            return Err(DimacsError::Overflow(vnum));
+        }
+
+        let next_var = Variable::from_index(self.var_map.len());
+        let cv = *self.var_map.entry(ParsedVar(vnum)).or_insert(next_var);
+        Ok(to_core_lit(pl, &cv))
+    }
+
+    fn intern_clause(&mut self, parsed_clause : &[ParsedLit]) -> Result<(), DimacsError> {
+        let mut lits = Vec::with_capacity(parsed_clause.len());
+        for parsed_lit in parsed_clause {
+            lits.push(self.intern_lit(parsed_lit)?);
+        }
+
+        self.num_literals += lits.len();
+        match lits.len() {
+            0 => self.num_empty_clauses += 1,
+            1 => self.num_unit_clauses += 1,
+            _ => {}
+        }
+
+        let hdr = clause::ClauseHeader::new(clause::ClauseId(self.clauses.len() as i64), lits.len(), 0);
+        self.clauses.push(clause::Clause::new(hdr, lits));
+        Ok(())
+    }
+
+    /// Check the interned clauses against the problem line, and number their
+    /// variables in ascending DIMACS order
+    fn finish(mut self, cnf_problem : CNFProblem, phases : &[PhaseDirective], strictness : Strictness) -> Result<DIMACS, DimacsError> {
+        check_clause_count(&cnf_problem, self.clauses.len())?;
+        if strictness == Strictness::Strict {
+            let declared = cnf_problem.num_variables;
+            if let Some(ParsedVar(var)) = self.var_map.keys().next_back().filter(|ParsedVar(v)| *v > declared) {
+                return Err(DimacsError::UndeclaredVariable { var : *var, declared });
+            }
+        }
+
+        let next_var = Variable::from_index(self.var_map.len());
+        let mut renumbered = TaggedVec::new();
+        renumbered.ensure_index(&next_var, Variable::FIRST_VARIABLE);
+        for (ix, v) in self.var_map.values_mut().enumerate() {
+            renumbered[*v] = Variable::from_index(ix);
+            *v = Variable::from_index(ix);
         }
+        for cl in self.clauses.iter_mut() {
+            for lit in cl.literals_mut() {
+                *lit = core::Literal::with_polarity(renumbered[lit.variable()], lit.polarity());
+            }
+        }
+
+        let phases = phases.iter().filter_map(|directive| {
+            let polarity = if directive.positive { core::Polarity::Positive } else { core::Polarity::Negative };
+            self.var_map.get(&directive.var).map(|v| (*v, polarity))
+        }).collect();
+
+        Ok(DIMACS {
+            phases,
+            max_variable : self.var_map.keys().next_back().map_or(0, |ParsedVar(v)| *v),
+            num_literals : self.num_literals,
+            num_unit_clauses : self.num_unit_clauses,
+            num_empty_clauses : self.num_empty_clauses,
+            clauses : self.clauses,
+            next_var,
+            num_declared_variables : cnf_problem.num_variables,
+            num_declared_clauses : cnf_problem.num_clauses,
+            var_map : self.var_map
+        })
     }
 }
 
 pub struct DIMACS {
     pub next_var : core::Variable,
-    pub clauses : Vec<clause::Clause>
+    pub clauses : Vec<clause::Clause>,
+    /// The number of variables declared in the problem line
+    pub num_declared_variables : u32,
+    /// The number of clauses declared in the problem line (which is the number
+    /// of clauses in `clauses`)
+    pub num_declared_clauses : u32,
+    /// The initial phases requested by `c phase` directives (in order), for
+    /// variables that appear in the formula
+    pub phases : Vec<(core::Variable, core::Polarity)>,
+    /// The largest DIMACS variable number that appears in a clause (or 0 if
+    /// there are none), which can differ from `num_declared_variables`
+    pub max_variable : u32,
+    /// The total number of literals in all clauses
+    pub num_literals : usize,
+    /// The number of clauses with exactly one literal
+    pub num_unit_clauses : usize,
+    /// The number of clauses with no literals
+    pub num_empty_clauses : usize,
+    /// The mapping from DIMACS variable numbers to our internal variables
+    var_map : BTreeMap<ParsedVar, core::Variable>
+}
+
+impl DIMACS {
+    /// The internal variable corresponding to a DIMACS variable number, if it
+    /// appears in the formula
+    pub fn variable(&self, dimacs_var : u32) -> Option<core::Variable> {
+        self.var_map.get(&ParsedVar(dimacs_var)).copied()
+    }
+
+    /// The DIMACS variable number of each internal variable (the inverse of
+    /// `variable`)
+    pub fn dimacs_variables(&self) -> TaggedVec<core::Variable, u32> {
+        let mut dimacs_vars = TaggedVec::new();
+        dimacs_vars.ensure_index(&self.next_var, 0);
+        for (ParsedVar(dimacs_var), v) in self.var_map.iter() {
+            dimacs_vars[*v] = *dimacs_var;
+        }
+
+        dimacs_vars
+    }
+
+    /// Translate a model (a list of signed DIMACS literals that are true) into
+    /// an assignment over our internal variables
+    ///
+    /// Variables not mentioned by the model are unassigned, and literals over
+    /// variables that do not appear in the formula are ignored.
+    pub fn assignment_from_model(&self, model : &[i32]) -> TaggedVec<core::Variable, core::Value> {
+        let mut assignment = TaggedVec::new();
+        assignment.ensure_index(&self.next_var, core::Value::UNASSIGNED);
+        for dimacs_lit in model {
+            if let Some(v) = self.variable(dimacs_lit.unsigned_abs()) {
+                let polarity = if *dimacs_lit < 0 { core::Polarity::Negative } else { core::Polarity::Positive };
+                let lit = core::Literal::with_polarity(v, polarity);
+                assignment[v] = lit.satisfy();
+            }
+        }
+
+        assignment
+    }
 }
 
+/// Errors that can arise while reading a DIMACS file
 #[derive(Debug, thiserror::Error)]
-enum Error<E> {
-    Io(std::io::Error),
-    Parse(E),
+pub enum DimacsError {
+    /// The input could not be read
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// The input is not syntactically valid DIMACS
+    #[error("syntax error at line {line}, column {col}: {msg}")]
+    Syntax { line : i32, col : i32, msg : String },
+    /// The number of clauses in the file does not match the problem line
+    #[error("problem line declares {declared} clauses, but {actual} were found")]
+    CountMismatch { declared : u32, actual : usize },
+    /// A literal refers to variable 0, which is reserved as the clause terminator
+    #[error("variable 0 cannot be used in a literal")]
+    ZeroVariable,
+    /// A variable number is too large to be represented as a signed DIMACS
+    /// literal (or internally)
+    #[error("variable number {0} is out of range")]
+    Overflow(u32),
+    /// A literal refers to a variable beyond the count in the problem line
+    /// (only reported by `Strictness::Strict` parsing)
+    #[error("variable {var} is larger than the {declared} variables declared in the problem line")]
+    UndeclaredVariable { var : u32, declared : u32 },
+    /// The input is longer than the byte limit of the `ParseBudget`
+    #[error("input exceeds the limit of {0} bytes")]
+    TooLarge(u64),
+    /// Parsing took longer than the time limit of the `ParseBudget`
+    #[error("parsing exceeded the time limit of {0:?}")]
+    Timeout(std::time::Duration),
+    /// The input is a QDIMACS formula, whose quantifier prefix starts on the
+    /// given line (see `parse_qdimacs_matrix`)
+    #[error("QBF prefix not supported (quantifier on line {0})")]
+    QuantifierPrefix(i32),
 }
 
-impl<E> std::fmt::Display for Error<E>
-where
-    E: std::fmt::Display,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            Error::Io(ref err) => write!(f, "{}", err),
-            Error::Parse(ref err) => write!(f, "{}", err),
-        }
+/// Limits on the resources used to read and parse an input (see
+/// `parse_dimacs_reader_with`)
+///
+/// The default has no limits.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseBudget {
+    /// The largest input (in bytes) to accept
+    pub max_bytes : Option<u64>,
+    /// The longest to spend reading and parsing
+    ///
+    /// The limit is checked between chunks of input and after each stage of
+    /// parsing, so a single stage can run over it, but the total time is
+    /// still bounded when combined with `max_bytes`.
+    pub time_limit : Option<std::time::Duration>
+}
+
+/// How closely the input must agree with its problem line
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Strictness {
+    /// Reject literals over variables larger than the declared variable count
+    Strict,
+    /// Accept any variable number; the per-variable structures of the solver
+    /// are sized from the variables that actually appear (see
+    /// `DIMACS::max_variable`), never from the problem line
+    Lenient
+}
+
+/// The largest variable number that can appear in a DIMACS file
+///
+/// Literals are signed, so variables must fit in an `i32`.
+const MAX_DIMACS_VARIABLE : u32 = i32::MAX as u32;
+
+fn syntax_error(err : easy::Errors<char, String, position::SourcePosition>) -> DimacsError {
+    let msgs : Vec<String> = err.errors.iter().map(|e| e.to_string()).collect();
+    DimacsError::Syntax {
+        line : err.position.line,
+        col : err.position.column,
+        msg : msgs.join("; ")
     }
 }
 
-pub fn parse_dimacs<'a>(input : &'a str) -> anyhow::Result<DIMACS> {
-    let (res, _rest) = dimacs().easy_parse(position::Stream::new(input))
-        .map_err(|err| Error::Parse(err.map_range(|s| s.to_string())))?;
-    let mut env = Env {
-        var_map : BTreeMap::new(),
-        next_var : Variable::FIRST_VARIABLE,
-        next_id : 0
+/// Parse a DIMACS file from any reader
+pub fn parse_dimacs_reader<R : std::io::Read>(reader : R) -> Result<DIMACS, DimacsError> {
+    parse_dimacs_reader_with(reader, &ParseBudget::default())
+}
+
+/// The size of the buffer used by `parse_dimacs_reader_with`
+const READ_CHUNK_SIZE : usize = 64 * 1024;
+
+/// Parse a DIMACS file from any reader, giving up with an error if the input
+/// exceeds the byte limit of the budget or parsing exceeds its time limit
+///
+/// The input is read a line at a time, and each clause is checked and interned
+/// as it is read, so a malformed literal is reported (with its line) without
+/// reading the rest of the input.  Input beyond the byte limit is never read,
+/// so this is safe to use on unbounded streams.
+pub fn parse_dimacs_reader_with<R : std::io::Read>(reader : R, budget : &ParseBudget) -> Result<DIMACS, DimacsError> {
+    use std::io::BufRead;
+
+    let start = std::time::Instant::now();
+    let check_time = || match budget.time_limit {
+        Some(limit) if start.elapsed() >= limit => Err(DimacsError::Timeout(limit)),
+        _ => Ok(())
     };
 
-    let mut interned_clauses = Vec::new();
+    // Reading one byte past the limit is enough to know that it was exceeded
+    let limit = budget.max_bytes.map_or(u64::MAX, |max_bytes| max_bytes.saturating_add(1));
+    let mut reader = std::io::BufReader::with_capacity(READ_CHUNK_SIZE, reader.take(limit));
+    let mut bytes_read = 0;
+    let mut line = Vec::new();
+    let mut line_num = 0;
+    // The comments and problem line, which are parsed together once the first
+    // line of clauses is found
+    let mut header_text = String::new();
+    let mut seen_problem = false;
+    let mut parsed_header = None;
+    let mut interner = Interner::default();
+    // The literals of a clause that is not yet ended, which can span lines
+    let mut current = Vec::new();
+    // The position just past the last character read, for reporting a clause
+    // that is never ended
+    let mut end = position::SourcePosition { line : 1, column : 1 };
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            break;
+        }
+        bytes_read += n as u64;
+        if let Some(max_bytes) = budget.max_bytes.filter(|max_bytes| bytes_read > *max_bytes) {
+            return Err(DimacsError::TooLarge(max_bytes));
+        }
+        check_time()?;
+        line_num += 1;
+        let text = std::str::from_utf8(&line)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
 
-    let mut clause_iter = res.clauses.iter();
-    while let Some(parsed_clause) = clause_iter.next() {
-        let hdr = clause::ClauseHeader {
-            id : clause::ClauseId(env.next_id),
-            lit_count : parsed_clause.len(),
-            activity : 0.0
+        if parsed_header.is_none() {
+            if !seen_problem || text.starts_with('c') {
+                seen_problem |= text.starts_with('p');
+                header_text.push_str(text);
+                continue;
+            }
+            if is_quantifier_line(text) {
+                return Err(DimacsError::QuantifierPrefix(line_num));
+            }
+            parsed_header = Some(parse_header(&header_text)?);
+        }
+        let start_pos = position::SourcePosition { line : line_num, column : 1 };
+        let ((tokens, end_marker), _rest) = clause_line().easy_parse(position::Stream::with_positioner(text, start_pos))
+            .map_err(|err| syntax_error(err.map_range(|s| s.to_string())))?;
+        for token in tokens {
+            match token {
+                ClauseToken::Lit(parsed_lit) => current.push(parsed_lit),
+                ClauseToken::End => interner.intern_clause(&std::mem::take(&mut current))?
+            }
+        }
+        if let Some(pos) = end_marker {
+            if !current.is_empty() {
+                return Err(unended_clause(pos, "`%`"));
+            }
+            break;
+        }
+        end = if text.ends_with('\n') {
+            position::SourcePosition { line : line_num + 1, column : 1 }
+        } else {
+            position::SourcePosition { line : line_num, column : text.chars().count() as i32 + 1 }
         };
+    }
 
-        env.next_id = env.next_id + 1;
+    let (cnf_problem, phases) = match parsed_header {
+        Some(header) => header,
+        None => parse_header(&header_text)?
+    };
+    if !current.is_empty() {
+        return Err(unended_clause(end, "end of input"));
+    }
+    let dimacs = interner.finish(cnf_problem, &phases, Strictness::Lenient)?;
+    check_time()?;
+    Ok(dimacs)
+}
+
+/// The error for a clause that is missing its `0` when the given token is found
+fn unended_clause(pos : position::SourcePosition, found : &str) -> DimacsError {
+    DimacsError::Syntax {
+        line : pos.line,
+        col : pos.column,
+        msg : format!("unexpected {} (expected `0` to end the clause)", found)
+    }
+}
 
-        let mut lits = Vec::new();
-        let mut lit_iter = parsed_clause.iter();
-        while let Some(parsed_lit) = lit_iter.next() {
-            let core_lit = intern_lit(&mut env, parsed_lit);
-            lits.push(core_lit);
+/// True if the line is a QDIMACS quantifier (`a` or `e`, then the variables)
+fn is_quantifier_line(line : &str) -> bool {
+    let mut chars = line.chars();
+    matches!(chars.next(), Some('a') | Some('e')) && chars.next().is_some_and(|c| BLANKS.contains(c))
+}
+
+/// The numbers (from 1) of the lines holding the QDIMACS quantifier prefix:
+/// the quantifier lines (possibly among comments) right after the problem line
+fn quantifier_prefix(input : &str) -> Vec<i32> {
+    let mut lines = input.lines().zip(1..).skip_while(|(line, _)| !line.starts_with('p')).skip(1);
+    let mut prefix = Vec::new();
+    while let Some((line, line_num)) = lines.next().filter(|(line, _)| line.starts_with('c') || is_quantifier_line(line)) {
+        if is_quantifier_line(line) {
+            prefix.push(line_num);
         }
+    }
+    prefix
+}
 
-        interned_clauses.push(clause::Clause::new(hdr, lits));
+fn parse_header(input : &str) -> Result<(CNFProblem, Vec<PhaseDirective>), DimacsError> {
+    let (header, _rest) = header().skip(token::eof()).easy_parse(position::Stream::new(input))
+        .map_err(|err| syntax_error(err.map_range(|s| s.to_string())))?;
+    Ok(header)
+}
+
+/// Parse a DIMACS formula, accepting variables beyond the declared count (see
+/// `Strictness::Lenient`)
+pub fn parse_dimacs(input : &str) -> Result<DIMACS, DimacsError> {
+    parse_dimacs_with(input, Strictness::Lenient)
+}
+
+/// Parse a DIMACS formula with the given strictness
+///
+/// A QDIMACS formula is rejected (rather than misreading its quantifiers as
+/// clauses); see `parse_qdimacs_matrix` to solve its matrix instead.
+pub fn parse_dimacs_with(input : &str, strictness : Strictness) -> Result<DIMACS, DimacsError> {
+    if let Some(line) = quantifier_prefix(input).first() {
+        return Err(DimacsError::QuantifierPrefix(*line));
     }
+    let (res, _rest) = dimacs().easy_parse(position::Stream::new(input))
+        .map_err(|err| syntax_error(err.map_range(|s| s.to_string())))?;
+    build_dimacs(res, strictness)
+}
 
-    Ok(DIMACS {
-        clauses : interned_clauses,
-        next_var : env.next_var
-    })
+/// Parse the matrix of a QDIMACS formula as a DIMACS formula, ignoring the
+/// quantifier prefix (which treats every variable as existential)
+///
+/// The result is only meaningful for formulas without universal quantifiers:
+/// a model of the matrix need not make a QBF true, and a QBF can be false even
+/// if its matrix is satisfiable.  Input without a prefix is parsed as DIMACS.
+pub fn parse_qdimacs_matrix(input : &str) -> Result<DIMACS, DimacsError> {
+    let prefix = quantifier_prefix(input);
+    if prefix.is_empty() {
+        return parse_dimacs(input);
+    }
+
+    // Blank out the prefix with empty comments, so that line numbers in errors
+    // still refer to the input
+    let matrix : String = input.split_inclusive('\n').zip(1..).map(|(line, line_num)| {
+        if prefix.contains(&line_num) { "c\n" } else { line }
+    }).collect();
+    parse_dimacs(&matrix)
+}
+
+/// Check that the number of clauses matches the problem line
+fn check_clause_count(cnf_problem : &CNFProblem, actual : usize) -> Result<(), DimacsError> {
+    if actual != cnf_problem.num_clauses as usize {
+        return Err(DimacsError::CountMismatch { declared : cnf_problem.num_clauses, actual });
+    }
+    Ok(())
 }
 
+/// Check the parsed clauses against the problem line, and intern them
+fn build_dimacs(res : ParsedDIMACS, strictness : Strictness) -> Result<DIMACS, DimacsError> {
+    check_clause_count(&res.cnf_problem, res.clauses.len())?;
+    // The clause count has been checked against the problem line, so this is
+    // exactly the space we need
+    let mut interner = Interner { clauses : Vec::with_capacity(res.clauses.len()), ..Interner::default() };
+    for parsed_clause in res.clauses.iter() {
+        interner.intern_clause(parsed_clause)?;
+    }
+    interner.finish(res.cnf_problem, &res.phases, strictness)
+}
 
 #[test]
 fn test_program_decl() {
@@ -295,6 +789,22 @@ fn test_clause_leading_ws() {
     assert_eq!(result, Ok(expected));
 }
 
+#[test]
+fn test_clause_line() {
+    let result = clause_line().parse(" 1 -2 00 3\n").map(|t| t.0);
+    let expected = vec![ClauseToken::Lit(ParsedLit::PosLit(ParsedVar(1))),
+                        ClauseToken::Lit(ParsedLit::NegLit(ParsedVar(2))),
+                        ClauseToken::End,
+                        ClauseToken::End,
+                        ClauseToken::Lit(ParsedLit::PosLit(ParsedVar(3)))
+    ];
+    assert_eq!(result, Ok((expected, None)));
+
+    let result = clause_line().parse(position::Stream::new("1 0 % 0\n")).map(|t| (t.0).1);
+    assert_eq!(result, Ok(Some(position::SourcePosition { line : 1, column : 5 })));
+    assert!(clause_line().parse("1 x 0\n").is_err());
+}
+
 #[test]
 fn test_dimacs1() {
     let result = dimacs().parse("c Header\n\
@@ -303,6 +813,7 @@ c commentary\n\
 1 5 2 -1 0\n\
 -5 3 0\n").map(|t| t.0);
     let expected = ParsedDIMACS {
+        phases : Vec::new(),
         cnf_problem : CNFProblem {
             num_variables : 5,
             num_clauses : 2
@@ -330,6 +841,7 @@ c .commentary\n\
 1 5 2     -1 0\n\
 -5 3 0\n").map(|t| t.0);
     let expected = ParsedDIMACS {
+        phases : Vec::new(),
         cnf_problem : CNFProblem {
             num_variables : 5,
             num_clauses : 2
@@ -356,6 +868,116 @@ c commentary\n\
 1 5 2 -1 0\n\
 -5 3 0\n\n\n").map(|t| t.0);
     let expected = ParsedDIMACS {
+        phases : Vec::new(),
+        cnf_problem : CNFProblem {
+            num_variables : 5,
+            num_clauses : 2
+        },
+        clauses : vec![
+            vec![ParsedLit::PosLit(ParsedVar(1)),
+                 ParsedLit::PosLit(ParsedVar(5)),
+                 ParsedLit::PosLit(ParsedVar(2)),
+                 ParsedLit::NegLit(ParsedVar(1))],
+            vec![ParsedLit::NegLit(ParsedVar(5)),
+                 ParsedLit::PosLit(ParsedVar(3))
+            ]
+        ]
+    };
+
+    assert_eq!(result, Ok(expected));
+}
+
+#[test]
+fn test_parse_dimacs_syntax_error() {
+    let result = parse_dimacs("p cnf 2 1\n1 x 0\n");
+    match result {
+        Err(DimacsError::Syntax { line, col, .. }) => {
+            assert_eq!(line, 2);
+            assert_eq!(col, 3);
+        },
+        _ => panic!("Expected a syntax error")
+    }
+}
+
+#[test]
+fn test_parse_dimacs_count_mismatch() {
+    let result = parse_dimacs("p cnf 2 3\n1 2 0\n-1 0\n");
+    match result {
+        Err(DimacsError::CountMismatch { declared, actual }) => {
+            assert_eq!(declared, 3);
+            assert_eq!(actual, 2);
+        },
+        _ => panic!("Expected a clause count mismatch")
+    }
+}
+
+#[test]
+fn test_parse_dimacs_zero_variable() {
+    let result = parse_dimacs("p cnf 2 1\n1 -0 0\n");
+    assert!(matches!(result, Err(DimacsError::ZeroVariable)));
+}
+
+#[test]
+fn test_parse_dimacs_overflow() {
+    let result = parse_dimacs("p cnf 1 1\n99999999999 0\n");
+    assert!(matches!(result, Err(DimacsError::Overflow(u32::MAX))));
+
+    let result = parse_dimacs("p cnf 1 1\n-2147483648 0\n");
+    assert!(matches!(result, Err(DimacsError::Overflow(2147483648))));
+}
+
+#[test]
+fn test_parse_dimacs_io_error() {
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf : &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("unreadable"))
+        }
+    }
+
+    let result = parse_dimacs_reader(FailingReader);
+    assert!(matches!(result, Err(DimacsError::Io(_))));
+}
+
+#[test]
+fn test_parse_dimacs_ok() {
+    let result = parse_dimacs("p cnf 3 2\n1 -3 0\n2 3 -1 0\n").unwrap();
+    assert_eq!(result.clauses.len(), 2);
+    assert_eq!(result.next_var, Variable::FIRST_VARIABLE.next_variable().next_variable().next_variable());
+}
+
+#[test]
+fn test_assignment_from_model() {
+    let result = parse_dimacs("p cnf 7 2\n7 -3 0\n3 5 0\n").unwrap();
+    let assignment = result.assignment_from_model(&[-3, 7, 100]);
+    let v7 = result.variable(7).unwrap();
+    let v3 = result.variable(3).unwrap();
+    let v5 = result.variable(5).unwrap();
+    assert_eq!(result.variable(1), None);
+    assert_eq!(assignment[v7], core::Value::LIFTED_TRUE);
+    assert_eq!(assignment[v3], core::Value::LIFTED_FALSE);
+    assert!(assignment[v5].is_unassigned());
+}
+
+#[test]
+fn test_parse_dimacs_declared_counts() {
+    let result = parse_dimacs("p cnf 10 3\n1 -3 0\n2 3 -1 0\n4 0\n").unwrap();
+    assert_eq!(result.num_declared_variables, 10);
+    assert_eq!(result.num_declared_clauses, 3);
+    // The clause database is allocated once, at exactly the declared size
+    assert_eq!(result.clauses.capacity(), 3);
+}
+
+#[test]
+fn test_dimacs_end_marker() {
+    let result = dimacs().parse("p cnf 5 2\n\
+1 5 2 -1 0\n\
+-5 3 0\n\
+%\n\
+0\n").map(|t| t.0);
+    let expected = ParsedDIMACS {
+        phases : Vec::new(),
         cnf_problem : CNFProblem {
             num_variables : 5,
             num_clauses : 2
@@ -373,3 +995,264 @@ c commentary\n\
 
     assert_eq!(result, Ok(expected));
 }
+
+#[test]
+fn test_parse_dimacs_end_marker() {
+    let result = parse_dimacs("p cnf 2 2\n1 2 0\n-1 0\n%\n0\n\n").unwrap();
+    assert_eq!(result.clauses.len(), 2);
+}
+
+#[test]
+fn test_parse_empty_problem() {
+    let result = parse_dimacs("p cnf 0 0\n").unwrap();
+    assert!(result.clauses.is_empty());
+    assert_eq!(result.next_var, Variable::FIRST_VARIABLE);
+    assert_eq!(result.num_declared_variables, 0);
+
+    let result = parse_dimacs("c nothing to see\np cnf 0 0\n%\n").unwrap();
+    assert!(result.clauses.is_empty());
+}
+
+#[test]
+fn test_phase_directives() {
+    let input = "c phase 1 1\nc phase 2   0  \nc phase 2 x\nc phases 1 0\np cnf 3 1\nc phase 3 0\nc phase 9 1\n1 2 3 0\n";
+    let result = parse_dimacs(input).unwrap();
+    let v1 = result.variable(1).unwrap();
+    let v2 = result.variable(2).unwrap();
+    let v3 = result.variable(3).unwrap();
+    // Malformed directives are ordinary comments, and directives for variables
+    // not in the formula are dropped
+    assert_eq!(result.phases, vec![(v1, core::Polarity::Positive),
+                                   (v2, core::Polarity::Negative),
+                                   (v3, core::Polarity::Negative)]);
+}
+
+#[test]
+fn test_clauses_on_one_line() {
+    let result = parse_dimacs("p cnf 4 3\n1 2 0 3 4 0\t-1 -3 0\n").unwrap();
+    assert_eq!(result.clauses.len(), 3);
+    let v1 = result.variable(1).unwrap();
+    let v3 = result.variable(3).unwrap();
+    assert_eq!(result.clauses[1].literals(), &[v3.to_positive_literal(), result.variable(4).unwrap().to_positive_literal()]);
+    assert_eq!(result.clauses[2].literals(), &[v1.to_negative_literal(), v3.to_negative_literal()]);
+}
+
+#[test]
+fn test_trailing_whitespace_after_clause() {
+    let result = parse_dimacs("p cnf 2 2\n1 2 0 \n-1 0  \n").unwrap();
+    assert_eq!(result.clauses.len(), 2);
+}
+
+#[test]
+fn test_dimacs_statistics() {
+    let input = "p cnf 10 5\n1 -7 3 0\n-3 0\n0\n2 0\n7 1 0\n";
+    let result = parse_dimacs(input).unwrap();
+    assert_eq!(result.num_declared_variables, 10);
+    assert_eq!(result.max_variable, 7);
+    assert_eq!(result.num_literals, 7);
+    assert_eq!(result.num_unit_clauses, 2);
+    assert_eq!(result.num_empty_clauses, 1);
+
+    let result = parse_dimacs("p cnf 0 0\n").unwrap();
+    assert_eq!(result.max_variable, 0);
+    assert_eq!(result.num_literals, 0);
+}
+
+#[test]
+fn test_dimacs_variable_one_is_first() {
+    // Variable 1 is numbered first even though it is not mentioned first
+    let result = parse_dimacs("p cnf 3 2\n3 -2 0\n2 1 0\n").unwrap();
+    for n in 1..=3 {
+        assert_eq!(result.variable(n), core::Variable::from_dimacs(n));
+        assert_eq!(result.dimacs_variables()[result.variable(n).unwrap()], n);
+    }
+    let first = core::Variable::FIRST_VARIABLE;
+    assert_eq!(result.clauses[1].literals()[1], first.to_positive_literal());
+    let assignment = result.assignment_from_model(&[-1, 2, 3]);
+    assert_eq!(assignment[first], core::Value::LIFTED_FALSE);
+}
+
+#[test]
+fn test_parse_dimacs_undeclared_variable() {
+    let input = "p cnf 50 2\n1 -100 0\n100 50 0\n";
+    match parse_dimacs_with(input, Strictness::Strict) {
+        Err(DimacsError::UndeclaredVariable { var : 100, declared : 50 }) => {},
+        Err(err) => panic!("Unexpected error: {}", err),
+        Ok(_) => panic!("Expected an error for variable 100")
+    }
+    assert!(parse_dimacs_with("p cnf 100 2\n1 -100 0\n100 50 0\n", Strictness::Strict).is_ok());
+
+    let result = parse_dimacs(input).unwrap();
+    assert_eq!(result.max_variable, 100);
+    assert_eq!(result.next_var, Variable::from_index(3));
+    let v100 = result.variable(100).unwrap();
+    let dimacs_variables = result.dimacs_variables();
+    assert_eq!(dimacs_variables[v100], 100);
+    let mut solver = crate::satir::dpll::Solver::new(result.clauses, result.next_var, Default::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    solver.set_dimacs_variables(dimacs_variables);
+    let mut out = Vec::new();
+    solver.write_model(&mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("100 "));
+}
+
+#[test]
+fn test_comment_requires_whitespace() {
+    assert_eq!(comment().parse("c\n").map(|t| t.0), Ok(None));
+    assert_eq!(comment().parse("c\tnote\n").map(|t| t.0), Ok(None));
+    assert!(comment().parse("c3 note\n").is_err());
+
+    match parse_dimacs("c3 note\np cnf 1 1\n1 0\n") {
+        Err(DimacsError::Syntax { line, col, .. }) => assert_eq!((line, col), (1, 2)),
+        res => panic!("Expected a syntax error, got {:?}", res.map(|d| d.clauses.len()))
+    }
+}
+
+#[test]
+fn test_unexpected_leading_letter() {
+    for input in &["x note\np cnf 1 1\n1 0\n", "p cnf 1 1\nc fine\nx note\n1 0\n"] {
+        match parse_dimacs(input) {
+            Err(DimacsError::Syntax { col, msg, .. }) => {
+                assert_eq!(col, 1);
+                assert!(msg.contains("line starting with 'x'"), "{}", msg);
+            }
+            res => panic!("Expected a syntax error, got {:?}", res.map(|d| d.clauses.len()))
+        }
+    }
+}
+
+#[test]
+fn test_tab_and_form_feed_separators() {
+    let result = clause().parse("1\t-5\x0c11\x0b\t2 0").map(|t| t.0);
+    let expected = vec![ParsedLit::PosLit(ParsedVar(1)),
+                        ParsedLit::NegLit(ParsedVar(5)),
+                        ParsedLit::PosLit(ParsedVar(11)),
+                        ParsedLit::PosLit(ParsedVar(2))];
+    assert_eq!(result, Ok(expected));
+
+    let dimacs = parse_dimacs("p\tcnf\x0c3 2\n1\t-2\x0c0\x0c\n2\x0b3\t0\n").unwrap();
+    assert_eq!(dimacs.clauses.len(), 2);
+    assert_eq!(dimacs.next_var, Variable::from_index(3));
+}
+
+#[test]
+fn test_problem_line_is_one_line() {
+    assert!(problem().parse("p cnf 5 10").is_ok());
+    assert!(problem().parse("p cnf\n5 10").is_err());
+    // Other Unicode whitespace is not a separator
+    assert!(clause().parse("1\u{a0}2 0").is_err());
+}
+
+#[test]
+fn test_parse_budget() {
+    let input = "p cnf 3 2\n1 -2 0\n2 3 0\n";
+    let budget = |max_bytes| ParseBudget { max_bytes : Some(max_bytes), time_limit : None };
+    assert!(parse_dimacs_reader_with(input.as_bytes(), &budget(input.len() as u64)).is_ok());
+    match parse_dimacs_reader_with(input.as_bytes(), &budget(input.len() as u64 - 1)) {
+        Err(DimacsError::TooLarge(limit)) => assert_eq!(limit, input.len() as u64 - 1),
+        res => panic!("Expected the input to be too large, got {:?}", res.map(|d| d.clauses.len()))
+    }
+
+    // An endless input is cut off at the limit
+    let endless = std::io::repeat(b' ');
+    assert!(matches!(parse_dimacs_reader_with(endless, &budget(1 << 20)), Err(DimacsError::TooLarge(_))));
+
+    let no_time = ParseBudget { max_bytes : None, time_limit : Some(std::time::Duration::from_secs(0)) };
+    assert!(matches!(parse_dimacs_reader_with(input.as_bytes(), &no_time), Err(DimacsError::Timeout(_))));
+}
+
+#[test]
+fn test_quantifier_prefix() {
+    let input = "c a QBF\np cnf 4 2\na 1 2 0\nc inner\ne 3 4 0\n1 -3 0\n2 4 0\n";
+    assert_eq!(quantifier_prefix(input), vec![3, 5]);
+    assert!(matches!(parse_dimacs(input), Err(DimacsError::QuantifierPrefix(3))));
+    assert!(matches!(parse_dimacs_reader(input.as_bytes()), Err(DimacsError::QuantifierPrefix(3))));
+    assert_eq!(DimacsError::QuantifierPrefix(3).to_string(), "QBF prefix not supported (quantifier on line 3)");
+
+    // Leniently, the prefix is skipped and the matrix is an ordinary formula
+    let matrix = parse_qdimacs_matrix(input).unwrap();
+    let expected = parse_dimacs("p cnf 4 2\n1 -3 0\n2 4 0\n").unwrap();
+    let lits = |d : &DIMACS| d.clauses.iter().map(|cl| cl.literals().to_vec()).collect::<Vec<_>>();
+    assert_eq!(lits(&matrix), lits(&expected));
+    assert_eq!(matrix.next_var, expected.next_var);
+
+    // Errors in the matrix keep their line numbers
+    match parse_qdimacs_matrix("p cnf 2 1\ne 1 2 0\n1 x 0\n") {
+        Err(DimacsError::Syntax { line, .. }) => assert_eq!(line, 3),
+        res => panic!("Expected a syntax error, got {:?}", res.map(|d| d.next_var))
+    }
+
+    // Lines that merely start with the letters are not quantifiers
+    assert!(quantifier_prefix("p cnf 1 1\n1 0\ne 1 0\n").is_empty());
+    assert!(!is_quantifier_line("exists 1 0\n"));
+}
+
+#[test]
+fn test_reader_agrees_with_parser() {
+    let inputs = [
+        "p cnf 3 2\n1 -2 0\n2 3 0\n",
+        "c a comment\nc phase 2 0\np cnf 3 2\nc after\n1 -2 0 2\n3 0\n",
+        "p cnf 3 3\r\n1 -2 0\r\n\r\n2 3 0 -1 0",
+        "p cnf 3 2\n1-2 0 2 00\n",
+        "p cnf 5 2\n1 5 2 -1 0\n-5 3 0\n%\n0\n",
+        "p cnf 0 0\n",
+        "p cnf 0 0\n%\n",
+        "p cnf 2 1\n1 2 0\nc late comment\n",
+        "p cnf 2 1\n1 2\n",
+        "p cnf 3 2\n1\n-2\n0 3\n 0\n",
+        "p cnf 2 1\n1 2 %\n0\n",
+        "p cnf 2 1\n1 -0 0\n",
+        "p cnf 2 1\n1 99999999999 0\n",
+        "p cnf 2 2\n1 2 0\n",
+        "c3 note\np cnf 1 1\n1 0\n",
+        "x\np cnf 1 1\n1 0\n",
+        "p cnf 1 1\nx 1 0\n",
+        "p cnf 1 1",
+        "1 0\n",
+        "p cnf 3 1\nc prefix\ne 1 3 0\n1 -3 0\n",
+    ];
+    let summary = |res : Result<DIMACS, DimacsError>| match res {
+        Ok(d) => Ok((d.clauses.iter().map(|cl| cl.literals().to_vec()).collect::<Vec<_>>(), d.next_var, d.phases)),
+        Err(DimacsError::Syntax { line, .. }) => Err(format!("syntax error on line {}", line)),
+        Err(err) => Err(err.to_string())
+    };
+    for input in inputs.iter() {
+        assert_eq!(summary(parse_dimacs_reader(input.as_bytes())), summary(parse_dimacs(input)), "{:?}", input);
+    }
+}
+
+#[test]
+fn test_reader_fails_fast() {
+    /// A large formula with a bad literal on line 5, generated as it is read
+    struct Generator {
+        line : usize,
+        pending : Vec<u8>,
+        bytes : usize
+    }
+
+    impl std::io::Read for Generator {
+        fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() && self.line < 1_000_000 {
+                self.line += 1;
+                self.pending = match self.line {
+                    1 => "p cnf 1000 999999\n".to_string(),
+                    5 => "1 -2 x3 0\n".to_string(),
+                    n => format!("{} -{} {} 0\n", n % 1000 + 1, (n * 7) % 1000 + 1, (n * 13) % 1000 + 1)
+                }.into_bytes();
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            self.bytes += n;
+            Ok(n)
+        }
+    }
+
+    let mut generator = Generator { line : 0, pending : Vec::new(), bytes : 0 };
+    match parse_dimacs_reader(&mut generator) {
+        Err(DimacsError::Syntax { line, col, .. }) => assert_eq!((line, col), (5, 6)),
+        res => panic!("Expected a syntax error, got {:?}", res.map(|d| d.clauses.len()))
+    }
+    // Only the first buffer of the (roughly 14MB) input was read
+    assert!(generator.bytes <= READ_CHUNK_SIZE, "read {} bytes", generator.bytes);
+}
@@ -1 +1,3 @@
+pub mod binary;
 pub mod dimacs;
+pub mod model;
@@ -0,0 +1,87 @@
+/// Errors that can arise while reading a model
+#[derive(Debug, thiserror::Error)]
+pub enum ModelError {
+    /// The input could not be read
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// A line of the model is malformed
+    #[error("syntax error on line {line}: {msg}")]
+    Syntax { line : usize, msg : String },
+    /// The model assigns both polarities of a variable
+    #[error("variable {0} is assigned both true and false")]
+    Inconsistent(u32),
+}
+
+/// Parse a model in the SAT competition output format
+///
+/// The model is given on lines starting with `v`, each holding a sequence of
+/// signed DIMACS literals (those that are true in the model).  The model may be
+/// terminated by a 0.  Comment lines (`c`) and the status line (`s`) are
+/// ignored, as are blank lines.
+///
+/// Returns the literals of the model, in order
+pub fn parse_model(input : &str) -> Result<Vec<i32>, ModelError> {
+    let mut lits = Vec::new();
+    let mut seen = std::collections::BTreeMap::new();
+    for (line_idx, line) in input.lines().enumerate() {
+        let line_num = line_idx + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            None | Some("c") | Some("s") => continue,
+            Some("v") => {},
+            Some(tok) => {
+                return Err(ModelError::Syntax {
+                    line : line_num,
+                    msg : format!("unexpected line start `{}`", tok)
+                });
+            }
+        }
+
+        for tok in tokens {
+            let lit : i32 = tok.parse().map_err(|_| ModelError::Syntax {
+                line : line_num,
+                msg : format!("invalid literal `{}`", tok)
+            })?;
+
+            if lit == 0 {
+                continue;
+            }
+
+            let var = lit.unsigned_abs();
+            match seen.insert(var, lit > 0) {
+                Some(polarity) if polarity != (lit > 0) => return Err(ModelError::Inconsistent(var)),
+                _ => lits.push(lit)
+            }
+        }
+    }
+
+    Ok(lits)
+}
+
+/// Parse a model from any reader
+pub fn parse_model_reader<R : std::io::Read>(mut reader : R) -> Result<Vec<i32>, ModelError> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    parse_model(&contents)
+}
+
+#[test]
+fn test_parse_model() {
+    let result = parse_model("c a comment\ns SATISFIABLE\nv 1 -2\nv 3 0\n").unwrap();
+    assert_eq!(result, vec![1, -2, 3]);
+}
+
+#[test]
+fn test_parse_model_inconsistent() {
+    let result = parse_model("v 1 -2 -1 0\n");
+    assert!(matches!(result, Err(ModelError::Inconsistent(1))));
+}
+
+#[test]
+fn test_parse_model_syntax_error() {
+    let result = parse_model("v 1 2\nv 3 x 0\n");
+    assert!(matches!(result, Err(ModelError::Syntax { line : 2, .. })));
+
+    let result = parse_model("1 2 0\n");
+    assert!(matches!(result, Err(ModelError::Syntax { line : 1, .. })));
+}
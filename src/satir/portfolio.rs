@@ -0,0 +1,107 @@
+use std::sync::mpsc;
+
+use crate::satir::core::{Polarity, Variable};
+use crate::satir::core;
+use crate::satir::clause::Clause;
+use crate::satir::dpll::{RestartPolicy, Solver, SolverConfig};
+
+/// The configuration of the solver in the given slot of a portfolio
+///
+/// The first solver uses the default configuration; the others vary the seed,
+/// the restart policy, and the default polarity so that they explore the
+/// search space differently.
+fn portfolio_config(slot : usize) -> SolverConfig {
+    if slot == 0 {
+        return SolverConfig::default();
+    }
+
+    let restart_policy = match slot % 3 {
+        0 => RestartPolicy::Luby { unit : 100 },
+        1 => RestartPolicy::Luby { unit : 512 },
+        _ => RestartPolicy::Geometric { first : 100, factor : 1.5 }
+    };
+    let default_phase = match slot % 2 {
+        0 => Polarity::Positive,
+        _ => Polarity::Negative
+    };
+    SolverConfig {
+        seed : Some(slot as u64),
+        random_decision_freq : 0.02,
+        restart_policy,
+        default_phase,
+        ..SolverConfig::default()
+    }
+}
+
+/// Solve a formula with several differently configured solvers in parallel,
+/// returning the answer of the first to finish
+///
+/// The remaining solvers are interrupted, and all of the threads are joined
+/// before this returns.  At least one solver is always run.
+pub fn solve_portfolio(clauses : Vec<Clause>, next_var : Variable, n_threads : usize) -> core::Result {
+    let n_threads = std::cmp::max(n_threads, 1);
+    let solvers : Vec<Solver> = (0..n_threads).map(|slot| {
+        Solver::new(clauses.clone(), next_var, portfolio_config(slot))
+    }).collect();
+    let handles : Vec<_> = solvers.iter().map(|s| s.interrupt_handle()).collect();
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for mut solver in solvers {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                // The receiver stops listening after the first answer
+                let _ = sender.send(solver.solve());
+            });
+        }
+
+        // Interrupted solvers report `Unknown`, which only happens after the
+        // first real answer has arrived
+        let res = receiver.recv().unwrap_or(core::Result::Unknown);
+        for handle in handles.iter() {
+            handle.interrupt();
+        }
+        res
+    })
+}
+
+#[cfg(test)]
+fn pigeonhole_clauses(pigeons : usize, holes : usize) -> (Vec<Clause>, Variable) {
+    let mut input = format!("p cnf {} {}\n", pigeons * holes, pigeons + holes * pigeons * (pigeons - 1) / 2);
+    for p in 0..pigeons {
+        for h in 0..holes {
+            input.push_str(&format!("{} ", p * holes + h + 1));
+        }
+        input.push_str("0\n");
+    }
+    for h in 0..holes {
+        for p1 in 0..pigeons {
+            for p2 in (p1 + 1)..pigeons {
+                input.push_str(&format!("-{} -{} 0\n", p1 * holes + h + 1, p2 * holes + h + 1));
+            }
+        }
+    }
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(&input).unwrap();
+    (dimacs.clauses, dimacs.next_var)
+}
+
+#[test]
+fn test_portfolio_verdicts() {
+    for n_threads in 0..5 {
+        let (clauses, next_var) = pigeonhole_clauses(5, 4);
+        assert_eq!(solve_portfolio(clauses, next_var, n_threads), core::Result::Unsat);
+        let (clauses, next_var) = pigeonhole_clauses(4, 4);
+        assert_eq!(solve_portfolio(clauses, next_var, n_threads), core::Result::Sat);
+    }
+}
+
+#[test]
+fn test_portfolio_configs_differ() {
+    let configs : Vec<SolverConfig> = (0..4).map(portfolio_config).collect();
+    assert_eq!(configs[0].seed, None);
+    for (slot, config) in configs.iter().enumerate().skip(1) {
+        assert_eq!(config.seed, Some(slot as u64));
+    }
+    assert!(configs.iter().any(|c| c.default_phase == Polarity::Negative));
+    assert!(configs.iter().any(|c| c.restart_policy != configs[0].restart_policy));
+}
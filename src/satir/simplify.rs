@@ -0,0 +1,367 @@
+use crate::satir::core::{Literal, Value, Variable};
+use crate::satir::clause::{Clause, ClauseHeader, ClauseId};
+use crate::satir::tagged::{TaggedIndexable, TaggedVec};
+
+/// A formula in conjunctive normal form
+#[derive(Clone)]
+pub struct Cnf {
+    pub clauses : Vec<Clause>,
+    /// One past the largest variable of the formula
+    pub next_var : Variable
+}
+
+/// Options for `Cnf::simplify`
+#[derive(Clone, Debug)]
+pub struct SimplifyConfig {
+    /// Remove clauses that are subsumed by other clauses (which is quadratic
+    /// in the worst case)
+    pub subsumption : bool,
+    /// Record why each clause was removed (see `Simplified::removed`)
+    pub record_removals : bool
+}
+
+impl Default for SimplifyConfig {
+    fn default() -> Self {
+        SimplifyConfig { subsumption : true, record_removals : false }
+    }
+}
+
+/// The simplification that removed a clause from the formula
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The clause was (or was reduced to) a unit, whose literal was fixed
+    Unit,
+    /// The clause contained both polarities of a variable
+    Tautology,
+    /// The clause was satisfied by a pure literal
+    PureLiteral,
+    /// The clause was subsumed by another (or duplicated an earlier one)
+    Subsumed,
+    /// The clause was satisfied by the literal of another unit
+    SatisfiedByUnit
+}
+
+/// The result of simplifying a formula
+pub struct Simplified {
+    /// The simplified formula, over the same variables as the original
+    ///
+    /// If simplification proves the original unsatisfiable, this is the
+    /// formula with a single empty clause.
+    pub cnf : Cnf,
+    /// The variables fixed by simplification (all others are unassigned)
+    pub assignment : TaggedVec<Variable, Value>,
+    /// The literals fixed by simplification, in the order they were fixed
+    ///
+    /// These are units and pure literals, neither of which occurs in the
+    /// simplified formula.
+    pub elimination_stack : Vec<Literal>,
+    /// The clauses removed by simplification, in the order they were removed
+    ///
+    /// Clauses are identified by their index in the original formula.  This is
+    /// only recorded if `SimplifyConfig::record_removals` is set; if the
+    /// formula is unsatisfiable, it only covers the removals made before that
+    /// was discovered.
+    pub removed : Vec<(ClauseId, RemovalReason)>
+}
+
+impl Simplified {
+    /// Extend a model of the simplified formula to a model of the original
+    pub fn extend_model(&self, assignment : &mut TaggedVec<Variable, Value>) {
+        for lit in self.elimination_stack.iter() {
+            assignment[lit.variable()] = lit.satisfy();
+        }
+    }
+
+    /// True if simplification alone proved the formula unsatisfiable
+    pub fn is_unsat(&self) -> bool {
+        self.cnf.clauses.iter().any(|cl| cl.lit_count() == 0)
+    }
+}
+
+fn make_clause(ix : usize, lits : Vec<Literal>) -> Clause {
+    Clause::new(ClauseHeader::new(ClauseId(ix as i64), lits.len(), 0), lits)
+}
+
+/// The removals made by simplification, if they are being recorded
+struct RemovalLog(Option<Vec<(ClauseId, RemovalReason)>>);
+
+impl RemovalLog {
+    fn record(&mut self, id : ClauseId, reason : RemovalReason) {
+        if let Some(log) = self.0.as_mut() {
+            log.push((id, reason));
+        }
+    }
+}
+
+/// Drop clauses containing a true literal and remove false literals from the
+/// rest, normalizing each remaining clause (sorting and removing duplicate
+/// literals) and dropping tautologies
+///
+/// Variables marked in `pure` were fixed as pure literals (rather than units).
+/// Returns the new units, or `None` if a clause is falsified
+fn reduce_clauses(clauses : &mut Vec<(ClauseId, Vec<Literal>)>,
+                  assignment : &TaggedVec<Variable, Value>,
+                  pure : &TaggedVec<Variable, bool>,
+                  log : &mut RemovalLog) -> Option<Vec<Literal>> {
+    let mut units = Vec::new();
+    let mut falsified = false;
+    clauses.retain_mut(|(id, lits)| {
+        if let Some(lit) = lits.iter().find(|l| l.under_value(assignment[l.variable()]) == Value::LIFTED_TRUE) {
+            let reason = if pure[lit.variable()] {
+                RemovalReason::PureLiteral
+            } else if lits.iter().all(|l| *l == *lit || !assignment[l.variable()].is_unassigned()) {
+                RemovalReason::Unit
+            } else {
+                RemovalReason::SatisfiedByUnit
+            };
+            log.record(*id, reason);
+            return false;
+        }
+        lits.retain(|l| assignment[l.variable()].is_unassigned());
+        lits.sort();
+        lits.dedup();
+        // Complementary literals are adjacent once sorted
+        if lits.windows(2).any(|w| w[0].variable() == w[1].variable()) {
+            log.record(*id, RemovalReason::Tautology);
+            return false;
+        }
+        match lits.len() {
+            0 => falsified = true,
+            1 => units.push(lits[0]),
+            _ => {}
+        }
+        true
+    });
+
+    if falsified { None } else { Some(units) }
+}
+
+/// The literals of unassigned variables that occur in only one polarity
+fn pure_literals(clauses : &[(ClauseId, Vec<Literal>)], next_var : &Variable) -> Vec<Literal> {
+    let mut occurs = TaggedVec::new();
+    occurs.ensure_index(&Literal::from_index(Variable::max_literal_index(next_var)), false);
+    for lit in clauses.iter().flat_map(|(_, lits)| lits) {
+        occurs[*lit] = true;
+    }
+
+    (0..next_var.as_index()).filter_map(|ix| {
+        let v = Variable::from_index(ix);
+        let (pos, neg) = (v.to_positive_literal(), v.to_negative_literal());
+        match (occurs[pos], occurs[neg]) {
+            (true, false) => Some(pos),
+            (false, true) => Some(neg),
+            _ => None
+        }
+    }).collect()
+}
+
+/// Remove every clause subsumed by another (keeping the first of any
+/// duplicates)
+///
+/// Returns true if any clause was removed
+fn remove_subsumed(clauses : &mut Vec<(ClauseId, Vec<Literal>)>, log : &mut RemovalLog) -> bool {
+    let built : Vec<Clause> = clauses.iter().enumerate().map(|(ix, (_, lits))| make_clause(ix, lits.clone())).collect();
+    let mut removed = vec![false; built.len()];
+    for ix in 0..built.len() {
+        if removed[ix] {
+            continue;
+        }
+        for other in 0..built.len() {
+            if other != ix && !removed[other] && built[ix].subsumes(&built[other])
+                && (built[ix].lit_count() < built[other].lit_count() || ix < other) {
+                removed[other] = true;
+            }
+        }
+    }
+
+    let mut ix = 0;
+    clauses.retain(|(id, _)| {
+        ix += 1;
+        if removed[ix - 1] {
+            log.record(*id, RemovalReason::Subsumed);
+        }
+        !removed[ix - 1]
+    });
+    removed.iter().any(|r| *r)
+}
+
+impl Cnf {
+    /// The number of positive and negative occurrences of each variable (in
+    /// that order) among the active literals of the clauses
+    pub fn polarity_counts(&self) -> TaggedVec<Variable, (u32, u32)> {
+        let mut counts : TaggedVec<Variable, (u32, u32)> = (0..self.next_var.as_index()).map(|_| (0, 0)).collect();
+        for lit in self.clauses.iter().flat_map(|cl| cl.literals()) {
+            let count = &mut counts[lit.variable()];
+            if lit.is_negated() {
+                count.1 += 1;
+            } else {
+                count.0 += 1;
+            }
+        }
+        counts
+    }
+
+    /// Simplify the formula, repeating until nothing changes
+    ///
+    /// This propagates units, removes tautologies and duplicate literals,
+    /// fixes pure literals, and (optionally) removes subsumed clauses.  The
+    /// simplified formula is satisfiable exactly when the original is, and
+    /// `Simplified::extend_model` turns its models into models of the
+    /// original.  Simplifying the result again changes nothing.  With
+    /// `SimplifyConfig::record_removals`, the result also says why each removed
+    /// clause was dropped.
+    ///
+    /// The solver does not need this: it does its own (cheaper) preprocessing.
+    pub fn simplify(&self, config : &SimplifyConfig) -> Simplified {
+        let mut assignment = TaggedVec::new();
+        assignment.ensure_index(&self.next_var, Value::UNASSIGNED);
+        let mut pure = TaggedVec::new();
+        pure.ensure_index(&self.next_var, false);
+        let mut elimination_stack = Vec::new();
+        let mut log = RemovalLog(if config.record_removals { Some(Vec::new()) } else { None });
+        let mut clauses : Vec<(ClauseId, Vec<Literal>)> =
+            self.clauses.iter().enumerate().map(|(ix, cl)| (ClauseId(ix as i64), cl.literals().to_vec())).collect();
+
+        let unsat = loop {
+            let mut fixed = match reduce_clauses(&mut clauses, &assignment, &pure, &mut log) {
+                None => break true,
+                Some(units) => units
+            };
+            let mut changed = false;
+            let mut fixed_pure = false;
+            if fixed.is_empty() {
+                fixed = pure_literals(&clauses, &self.next_var);
+                fixed_pure = true;
+                if fixed.is_empty() && config.subsumption {
+                    changed = remove_subsumed(&mut clauses, &mut log);
+                }
+            }
+
+            for lit in fixed {
+                let val = assignment[lit.variable()];
+                if val.is_unassigned() {
+                    assignment[lit.variable()] = lit.satisfy();
+                    pure[lit.variable()] = fixed_pure;
+                    elimination_stack.push(lit);
+                    changed = true;
+                } else if lit.under_value(val) != Value::LIFTED_TRUE {
+                    // Two units with opposite polarities
+                    clauses.push((ClauseId(self.clauses.len() as i64), Vec::new()));
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break false;
+            }
+        };
+
+        if unsat {
+            clauses = vec![(ClauseId(0), Vec::new())];
+        }
+        Simplified {
+            cnf : Cnf {
+                clauses : clauses.into_iter().enumerate().map(|(ix, (_, lits))| make_clause(ix, lits)).collect(),
+                next_var : self.next_var
+            },
+            assignment,
+            elimination_stack,
+            removed : log.0.unwrap_or_default()
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse_cnf(input : &str) -> Cnf {
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    Cnf { clauses : dimacs.clauses, next_var : dimacs.next_var }
+}
+
+#[cfg(test)]
+fn clause_lits(cnf : &Cnf) -> Vec<Vec<Literal>> {
+    cnf.clauses.iter().map(|cl| cl.literals().to_vec()).collect()
+}
+
+#[test]
+fn test_simplify_fixpoint() {
+    // 1 is a unit, which satisfies the first clause and shortens the second;
+    // 4 | 5 subsumes 4 | 5 | -6; 2 | 2 | -3 has a duplicate literal; 6 | -6 is
+    // a tautology
+    let input = "p cnf 6 7\n1 0\n1 2 0\n-1 -2 3 0\n2 2 -3 0\n4 5 0\n4 5 -6 0\n6 -6 0\n";
+    for subsumption in &[false, true] {
+        let config = SimplifyConfig { subsumption : *subsumption, ..SimplifyConfig::default() };
+        let once = parse_cnf(input).simplify(&config);
+        let twice = once.cnf.simplify(&config);
+        assert_eq!(clause_lits(&twice.cnf), clause_lits(&once.cnf));
+        assert!(twice.elimination_stack.is_empty());
+        assert!(!once.is_unsat());
+        // 4 and 5 are pure, leaving 2 <-> 3
+        let v = |n| Variable::from_dimacs(n).unwrap();
+        assert_eq!(clause_lits(&once.cnf), vec![vec![v(2).to_negative_literal(), v(3).to_positive_literal()],
+                                                vec![v(2).to_positive_literal(), v(3).to_negative_literal()]]);
+        assert_eq!(once.elimination_stack[0], Variable::FIRST_VARIABLE.to_positive_literal());
+    }
+}
+
+#[test]
+fn test_simplify_keeps_models() {
+    let input = "p cnf 4 6\n1 2 0\n-1 -2 0\n1 -2 3 0\n-1 2 3 0\n-3 4 0\n-3 -4 1 0\n";
+    let original = parse_cnf(input);
+    let simplified = original.simplify(&SimplifyConfig::default());
+    assert!(!simplified.is_unsat());
+    assert!(simplified.cnf.simplify(&SimplifyConfig::default()).elimination_stack.is_empty());
+
+    let dimacs = crate::satir::parse::dimacs::parse_dimacs(input).unwrap();
+    let mut solver = crate::satir::dpll::Solver::new(simplified.cnf.clauses.clone(), simplified.cnf.next_var, Default::default());
+    assert_eq!(solver.solve(), crate::satir::core::Result::Sat);
+    let model = solver.model().unwrap();
+    let mut assignment : TaggedVec<Variable, Value> = (0..=dimacs.next_var.as_index()).map(|ix| {
+        let v = Variable::from_index(ix);
+        if ix < model.len() && !model.value(v) { Value::LIFTED_FALSE } else { Value::LIFTED_TRUE }
+    }).collect();
+    simplified.extend_model(&mut assignment);
+    assert!(crate::satir::clause::evaluate(&original.clauses, &assignment));
+}
+
+#[test]
+fn test_simplify_removal_reasons() {
+    // Clause 0 is a unit satisfying clause 1; clause 2 is a tautology; clause
+    // 4 is subsumed by clause 3, after which -4 is pure, satisfying clause 6
+    let input = "p cnf 5 7\n1 0\n1 2 0\n5 -5 2 0\n2 3 0\n2 3 4 0\n-2 -3 0\n-2 3 -4 0\n";
+    let cnf = parse_cnf(input);
+    assert!(cnf.simplify(&SimplifyConfig::default()).removed.is_empty());
+
+    let simplified = cnf.simplify(&SimplifyConfig { record_removals : true, ..SimplifyConfig::default() });
+    let mut removed = simplified.removed.clone();
+    removed.sort_by_key(|(id, _)| id.0);
+    assert_eq!(removed, [(ClauseId(0), RemovalReason::Unit),
+                         (ClauseId(1), RemovalReason::SatisfiedByUnit),
+                         (ClauseId(2), RemovalReason::Tautology),
+                         (ClauseId(4), RemovalReason::Subsumed),
+                         (ClauseId(6), RemovalReason::PureLiteral)]);
+    assert_eq!(simplified.cnf.clauses.len(), 2);
+}
+
+#[test]
+fn test_simplify_unsat() {
+    let simplified = parse_cnf("p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n").simplify(&SimplifyConfig::default());
+    // No units or pure literals, so this is not detected
+    assert!(!simplified.is_unsat());
+
+    let simplified = parse_cnf("p cnf 2 3\n1 0\n-1 2 0\n-2 0\n").simplify(&SimplifyConfig::default());
+    assert!(simplified.is_unsat());
+    assert_eq!(simplified.cnf.clauses.len(), 1);
+    assert!(simplified.cnf.simplify(&SimplifyConfig::default()).is_unsat());
+}
+
+#[test]
+fn test_polarity_counts() {
+    let cnf = parse_cnf("p cnf 4 5\n1 -2 0\n-1 -2 3 0\n2 3 0\n-3 1 1 0\n4 0\n");
+    let counts = cnf.polarity_counts();
+    let v = |n| Variable::from_dimacs(n).unwrap();
+    assert_eq!(counts.len(), 4);
+    assert_eq!(counts[v(1)], (3, 1));
+    assert_eq!(counts[v(2)], (1, 2));
+    assert_eq!(counts[v(3)], (2, 1));
+    assert_eq!(counts[v(4)], (1, 0));
+}
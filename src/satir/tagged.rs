@@ -1,5 +1,6 @@
-use std::ops::{Index, IndexMut};
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+use core::marker::PhantomData;
 
 // Type-safe array indexing
 
@@ -44,10 +45,30 @@ impl<I,T> TaggedVec<I,T> {
         }
     }
 
-    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+    pub fn with_capacity(capacity : usize) -> Self {
+        TaggedVec {
+            index_type : PhantomData,
+            tagged_vec : Vec::with_capacity(capacity)
+        }
+    }
+
+    /// Reserve space for at least `additional` more elements
+    pub fn reserve(&mut self, additional : usize) {
+        self.tagged_vec.reserve(additional);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.tagged_vec.capacity()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
         self.tagged_vec.iter()
     }
 
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, T> {
+        self.tagged_vec.iter_mut()
+    }
+
     pub fn push(&mut self, t: T) -> () {
         self.tagged_vec.push(t);
     }
@@ -69,3 +90,61 @@ impl<I,T> TaggedVec<I,T> {
         }
     }
 }
+
+impl<I, T> core::iter::FromIterator<T> for TaggedVec<I, T> {
+    fn from_iter<It : IntoIterator<Item = T>>(iter : It) -> Self {
+        TaggedVec {
+            index_type : PhantomData,
+            tagged_vec : iter.into_iter().collect()
+        }
+    }
+}
+
+impl<I, T> IntoIterator for TaggedVec<I, T> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tagged_vec.into_iter()
+    }
+}
+
+impl<'a, I, T> IntoIterator for &'a TaggedVec<I, T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tagged_vec.iter()
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Copy)]
+struct TestIndex(usize);
+
+#[cfg(test)]
+impl TaggedIndexable for TestIndex {
+    fn as_index(&self) -> usize {
+        self.0
+    }
+}
+
+#[test]
+fn test_collect_preserves_order() {
+    let tv : TaggedVec<TestIndex, i32> = vec![10, 20, 30].into_iter().collect();
+    assert_eq!(tv.len(), 3);
+    assert_eq!(tv[TestIndex(0)], 10);
+    assert_eq!(tv[TestIndex(2)], 30);
+
+    let by_ref : Vec<i32> = (&tv).into_iter().copied().collect();
+    assert_eq!(by_ref, vec![10, 20, 30]);
+
+    let mut sum = 0;
+    for x in &tv {
+        sum += x;
+    }
+    assert_eq!(sum, 60);
+
+    let owned : Vec<i32> = tv.into_iter().collect();
+    assert_eq!(owned, vec![10, 20, 30]);
+}
@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Write a file into a fresh scratch directory for this test
+fn scratch_file(test : &str, name : &str, contents : &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("satir-cli-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn satir() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_satir"))
+}
+
+const FORMULA : &str = "p cnf 3 3\n1 2 0\n-1 3 0\n-3 -2 0\n";
+
+#[test]
+fn test_solve() {
+    let cnf = scratch_file("solve", "f.cnf", FORMULA);
+    let out = satir().arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "sat\n");
+}
+
+#[test]
+fn test_check_valid_witness() {
+    let cnf = scratch_file("check-valid", "f.cnf", FORMULA);
+    let model = scratch_file("check-valid", "f.model", "s SATISFIABLE\nv 1 -2 3 0\n");
+    let out = satir().arg("check").arg(&cnf).arg(&model).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "valid\n");
+}
+
+#[test]
+fn test_check_invalid_witness() {
+    let cnf = scratch_file("check-invalid", "f.cnf", FORMULA);
+    let model = scratch_file("check-invalid", "f.model", "s SATISFIABLE\nv 1 2 3 0\n");
+    let out = satir().arg("check").arg(&cnf).arg(&model).output().unwrap();
+    assert!(!out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "invalid\n");
+}
+
+#[test]
+fn test_competition_output() {
+    let cnf = scratch_file("competition", "f.cnf", FORMULA);
+    let out = satir().arg("--competition").arg(&cnf).output().unwrap();
+    assert_eq!(out.status.code(), Some(10));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("s SATISFIABLE\nv "));
+
+    // The model must pass the witness checker
+    let model = scratch_file("competition", "f.model", &stdout);
+    let out = satir().arg("check").arg(&cnf).arg(&model).output().unwrap();
+    assert!(out.status.success());
+
+    let cnf = scratch_file("competition", "u.cnf", "p cnf 1 2\n1 0\n-1 0\n");
+    let out = satir().arg("--competition").arg(&cnf).output().unwrap();
+    assert_eq!(out.status.code(), Some(20));
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "s UNSATISFIABLE\n");
+}
+
+#[test]
+fn test_json_output() {
+    let cnf = scratch_file("json", "f.cnf", FORMULA);
+    let out = satir().arg("--model-format").arg("json").arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let model = stdout.trim().strip_prefix("{\"sat\": true, \"model\": {").unwrap().strip_suffix("}}").unwrap();
+    let mut lits = Vec::new();
+    for entry in model.split(", ") {
+        let (var, value) = entry.split_once(": ").unwrap();
+        let var : i32 = var.trim_matches('"').parse().unwrap();
+        lits.push(if value.parse::<bool>().unwrap() { var } else { -var });
+    }
+    lits.sort_by_key(|l| l.abs());
+    assert_eq!(lits.iter().map(|l| l.abs()).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    // The model must pass the witness checker
+    let v_line : Vec<String> = lits.iter().map(|l| l.to_string()).collect();
+    let witness = scratch_file("json", "f.model", &format!("v {} 0\n", v_line.join(" ")));
+    let out = satir().arg("check").arg(&cnf).arg(&witness).output().unwrap();
+    assert!(out.status.success());
+
+    let cnf = scratch_file("json", "u.cnf", "p cnf 1 2\n1 0\n-1 0\n");
+    let out = satir().arg("--model-format").arg("json").arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "{\"sat\": false}\n");
+}
+
+#[test]
+fn test_stats_output() {
+    let cnf = scratch_file("stats", "f.cnf", FORMULA);
+    let out = satir().arg("--stats").arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    // Statistics go to stderr, so the result is unchanged
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "sat\n");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.lines().all(|line| line.starts_with("c ")));
+    assert!(stderr.lines().any(|line| line.starts_with("c restarts: ")));
+}
+
+/// Three pigeons in two holes
+const UNSAT_FORMULA : &str = "p cnf 6 9\n1 2 0\n3 4 0\n5 6 0\n-1 -3 0\n-1 -5 0\n-3 -5 0\n-2 -4 0\n-2 -6 0\n-4 -6 0\n";
+
+#[test]
+fn test_output_streams() {
+    let cnf = scratch_file("streams", "u.cnf", UNSAT_FORMULA);
+    let proof = cnf.with_file_name("u.drup");
+    let out = satir().arg("--proof").arg(&proof).arg("--stats").arg("-v").arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "unsat\n");
+
+    // The trace and the statistics are comments on stderr
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.lines().all(|line| line.starts_with("c ")));
+    assert!(stderr.lines().any(|line| line.starts_with("c conflict at level ")));
+    assert!(stderr.lines().any(|line| line.starts_with("c conflicts: ")));
+
+    // The proof holds only clauses, ending with the empty clause
+    let proof = std::fs::read_to_string(&proof).unwrap();
+    assert!(proof.lines().all(|line| line.split(' ').all(|tok| tok.parse::<i32>().is_ok()) && line.ends_with('0')));
+    assert_eq!(proof.lines().last(), Some("0"));
+
+    // Nothing goes to stderr unless asked for
+    let out = satir().arg(&cnf).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "unsat\n");
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn test_json_stats() {
+    let cnf = scratch_file("json-stats", "u.cnf", UNSAT_FORMULA);
+    let out = satir().arg("--stats").arg("--model-format").arg("json").arg(&cnf).output().unwrap();
+    assert!(out.status.success());
+    assert!(out.stderr.is_empty());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stats = stdout.trim().strip_prefix("{\"sat\": false, \"stats\": {").unwrap().strip_suffix("}}").unwrap();
+    let keys : Vec<&str> = stats.split(", ").map(|field| field.split_once(": ").unwrap().0).collect();
+    assert!(keys.contains(&"\"conflicts\"") && keys.contains(&"\"restarts\""));
+}
@@ -0,0 +1,43 @@
+use satirlib::satir::clause::{Clause, ClauseHeader, ClauseId};
+use satirlib::satir::core::{self, Literal, Variable};
+use satirlib::satir::dpll::{Solver, SolverConfig};
+
+fn lit(dimacs_lit : i32) -> Literal {
+    Literal::from_dimacs(dimacs_lit).unwrap()
+}
+
+fn var(dimacs_var : u32) -> Variable {
+    Variable::from_dimacs(dimacs_var).unwrap()
+}
+
+/// Solve a small formula (this runs with whatever features the library was
+/// built with; see `test_solves_without_std`)
+#[test]
+fn test_solve() {
+    let formula : &[&[i32]] = &[&[1, 2], &[-1, 2], &[-2, 3]];
+    let clauses = formula.iter().zip(0..).map(|(lits, id)| {
+        Clause::new(ClauseHeader::new(ClauseId(id), lits.len(), 0), lits.iter().map(|l| lit(*l)))
+    }).collect();
+    let mut solver = Solver::new(clauses, Variable::from_index(3), SolverConfig::default());
+    assert_eq!(solver.solve(), core::Result::Sat);
+    let model = solver.model().unwrap();
+    assert!(model.value(var(2)) && model.value(var(3)));
+
+    solver.add_clause(&[lit(-3)]);
+    assert_eq!(solver.solve(), core::Result::Unsat);
+}
+
+/// The solver builds and runs without the standard library (see the `std`
+/// feature)
+#[cfg(feature = "std")]
+#[test]
+fn test_solves_without_std() {
+    let out = std::process::Command::new(env!("CARGO"))
+        .args(["test", "--no-default-features", "--test", "no_std", "--", "--exact", "test_solve"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("CARGO_TARGET_DIR", std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-std"))
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("1 passed"), "{}", String::from_utf8_lossy(&out.stdout));
+}